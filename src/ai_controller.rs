@@ -48,7 +48,7 @@ pub async fn run_ai_controller(
             let mut state = shared_state.lock().await;
             state.min_profit_threshold = new_min_profit;
             // Weight best protocol highest, others lower
-            for (protocol, _profit) in &protocol_profit {
+            for protocol in protocol_profit.keys() {
                 state.protocol_weights.insert(protocol.clone(), if *protocol == best_protocol { 1.0 } else { 0.5 });
             }
             // Update scan intervals if changed
@@ -4,8 +4,11 @@ use crate::config::Settings;
 
 use crate::matrix2d::{Matrix2D, PriceCell};
 
+/// (buy dex, asset, sell dex, buy price, asset, sell price, profit %, timestamp)
+pub type Opportunity = (String, String, String, f64, String, f64, f64, u64);
+
 /// Scan the Matrix2D for arbitrage opportunities
-pub fn scan_matrix2d(matrix: &Matrix2D, profit_threshold_pct: f64) -> Vec<(String, String, String, f64, String, f64, f64, u64)> {
+pub fn scan_matrix2d(matrix: &Matrix2D, profit_threshold_pct: f64) -> Vec<Opportunity> {
     use log::info;
     // Print the full price matrix for visibility
     info!("[DEX SCAN] Current price matrix:");
@@ -33,21 +36,21 @@ pub fn scan_matrix2d(matrix: &Matrix2D, profit_threshold_pct: f64) -> Vec<(Strin
                 }
             }
         }
-        if let (Some((buy_idx, buy_cell)), Some((sell_idx, sell_cell))) = (best_buy, best_sell) {
-            if sell_cell.price > buy_cell.price {
-                let profit_pct = (sell_cell.price - buy_cell.price) / buy_cell.price * 100.0;
-                if profit_pct >= profit_threshold_pct {
-                    opps.push((
-                        matrix.dexes[buy_idx].clone(),
-                        asset.clone(),
-                        matrix.dexes[sell_idx].clone(),
-                        buy_cell.price,
-                        asset.clone(),
-                        sell_cell.price,
-                        profit_pct,
-                        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
-                    ));
-                }
+        if let (Some((buy_idx, buy_cell)), Some((sell_idx, sell_cell))) = (best_buy, best_sell)
+            && sell_cell.price > buy_cell.price
+        {
+            let profit_pct = (sell_cell.price - buy_cell.price) / buy_cell.price * 100.0;
+            if profit_pct >= profit_threshold_pct {
+                opps.push((
+                    matrix.dexes[buy_idx].clone(),
+                    asset.clone(),
+                    matrix.dexes[sell_idx].clone(),
+                    buy_cell.price,
+                    asset.clone(),
+                    sell_cell.price,
+                    profit_pct,
+                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+                ));
             }
         }
     }
@@ -59,6 +62,21 @@ pub fn scan_matrix2d(matrix: &Matrix2D, profit_threshold_pct: f64) -> Vec<(Strin
 
 use ethers::middleware::Middleware;
 use std::sync::Arc;
+use crate::gas_oracle::FeeSuggestion;
+use crate::execution_planner::{ArbitrageOpportunity, ArbitragePlan, ExecutionPlanner};
+
+/// Profit in USD of an opportunity of `profit_pct` on a `trade_size_usd` trade, after the
+/// worst-case gas cost of `gas_limit` at the suggested fees.
+pub fn net_profit_usd(
+    profit_pct: f64,
+    trade_size_usd: f64,
+    fees: &FeeSuggestion,
+    gas_limit: u64,
+    native_price_usd: f64,
+) -> f64 {
+    trade_size_usd * profit_pct / 100.0 - fees.cost_usd(gas_limit, native_price_usd)
+}
+
 /// The AnalysisHub is responsible for running arbitrage analysis on all matrices.
 pub struct AnalysisHub;
 
@@ -71,7 +89,7 @@ impl AnalysisHub {
         profit_threshold_pct: f64,
         _settings: &Settings,
         _client: Arc<M>,
-    ) -> Vec<(String, String, String, f64, String, f64, f64, u64)> {
+    ) -> Vec<Opportunity> {
         // Just call the sync version for now
         scan_matrix2d(matrix, profit_threshold_pct)
     }
@@ -83,7 +101,7 @@ impl AnalysisHub {
         profit_threshold_pct: f64,
         settings: &Settings,
         client: Arc<M>,
-    ) -> Vec<(String, String, String, f64, String, f64, f64, u64)> {
+    ) -> Vec<Opportunity> {
         let mut all_opps = Vec::new();
         for matrix in matrices {
             let mut opps = Self::scan_matrix2d_async(matrix, profit_threshold_pct, settings, client.clone()).await;
@@ -92,4 +110,42 @@ impl AnalysisHub {
         // Only collect and return tuple-based arbitrage opportunities for Matrix2D
         all_opps
     }
+
+    /// Keep opportunities that still clear `min_profit_usd` on a `trade_size_usd` trade once
+    /// the gas for `arbitrage_gas_limit` at the oracle's current fees is paid.
+    pub fn filter_by_net_profit(
+        opps: Vec<Opportunity>,
+        settings: &Settings,
+        fees: &FeeSuggestion,
+        trade_size_usd: f64,
+        native_price_usd: f64,
+    ) -> Vec<Opportunity> {
+        opps.into_iter()
+            .filter(|opp| {
+                let net = net_profit_usd(opp.6, trade_size_usd, fees, settings.arbitrage_gas_limit, native_price_usd);
+                if net < settings.min_profit_usd {
+                    log::debug!("[ANALYSIS] Dropping {} {}->{}: net profit ${:.2} after gas", opp.1, opp.0, opp.2, net);
+                }
+                net >= settings.min_profit_usd
+            })
+            .collect()
+    }
+
+    /// Turn opportunities into executeArbitrage calls of `trade_size` quote tokens each. Those
+    /// that don't quote profitably on chain (or can't be planned) are logged and dropped.
+    pub async fn plan_opportunities<M: Middleware + 'static>(
+        opps: &[Opportunity],
+        planner: &ExecutionPlanner,
+        client: Arc<M>,
+        trade_size: f64,
+    ) -> Vec<(ArbitrageOpportunity, ArbitragePlan)> {
+        let mut plans = Vec::new();
+        for opp in opps.iter().map(ArbitrageOpportunity::from_scan) {
+            match planner.plan_opportunity(client.clone(), &opp, trade_size).await {
+                Ok(plan) => plans.push((opp, plan)),
+                Err(e) => log::debug!("[ANALYSIS] Dropping {} {}->{}: {}", opp.asset, opp.buy_dex, opp.sell_dex, e),
+            }
+        }
+        plans
+    }
 }
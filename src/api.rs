@@ -1,7 +1,10 @@
 use crate::matrix2d::Matrix2D;
 use std::sync::{Arc, Mutex};
 use crate::providers::ProviderManager;
-use actix_web::{HttpResponse, Responder, get, post, web};
+use actix_web::{HttpRequest, HttpResponse, Responder, get, post, web};
+use crate::risk_manager::RiskManager;
+use crate::ledger::Ledger;
+use serde::Deserialize;
 use std::time::SystemTime;
 
 
@@ -77,10 +80,12 @@ pub async fn post_transfer(
             );
         }
     };
-    let chain = match payload.get("chain").and_then(|v| v.as_str()) {
-        Some(c) => c.to_uppercase(),
-        None => {
-            return HttpResponse::BadRequest().json(serde_json::json!({"status": "error", "reason": "Missing 'chain' parameter (must be 'BSC' or 'ETH')"}));
+    // Chain name from the chain registry (e.g. "BSC", "ETH", "Sepolia") or numeric chain ID
+    let chain = match payload.get("chain") {
+        Some(serde_json::Value::String(c)) => c.clone(),
+        Some(serde_json::Value::Number(n)) => n.to_string(),
+        _ => {
+            return HttpResponse::BadRequest().json(serde_json::json!({"status": "error", "reason": "Missing 'chain' parameter (chain name or chain ID)"}));
         }
     };
     let to_addr = match to.parse::<Address>() {
@@ -91,28 +96,18 @@ pub async fn post_transfer(
             );
         }
     };
-    let provider = match chain.as_str() {
-        "BSC" => {
-            match &provider_data.bsc_provider {
-                Some(p) => p.http_provider.clone(),
-                None => {
-                    log::error!("BSC provider not configured");
-                    return HttpResponse::InternalServerError().json(serde_json::json!({"status": "error", "reason": "BSC provider not configured"}));
-                }
-            }
-        }
-        "ETH" => {
-            match &provider_data.eth_provider {
-                Some(p) => p.http_provider.clone(),
-                None => {
-                    log::error!("ETH provider not configured");
-                    return HttpResponse::InternalServerError().json(serde_json::json!({"status": "error", "reason": "ETH provider not configured"}));
-                }
-            }
-        }
-        _ => {
+    let chain_id = match provider_data.registry.resolve(&chain) {
+        Some(config) => config.chain_id,
+        None => {
             log::error!("Unsupported chain: {}", chain);
-            return HttpResponse::BadRequest().json(serde_json::json!({"status": "error", "reason": "Unsupported chain. Must be 'BSC' or 'ETH'"}));
+            return HttpResponse::BadRequest().json(serde_json::json!({"status": "error", "reason": format!("Unsupported chain: {}", chain)}));
+        }
+    };
+    let provider = match provider_data.chain(chain_id) {
+        Some(p) => p.http_provider.clone(),
+        None => {
+            log::error!("{} provider not configured", chain);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"status": "error", "reason": format!("{} provider not configured", chain)}));
         }
     };
     let from_addr = provider.address();
@@ -166,3 +161,72 @@ pub async fn post_connect_wallet(provider_data: web::Data<Arc<ProviderManager>>)
     HttpResponse::Ok()
         .json(serde_json::json!({"status": "connected", "address": format!("0x{:x}", address)}))
 }
+
+#[get("/api/wallets")]
+pub async fn get_wallets(provider_data: web::Data<Arc<ProviderManager>>) -> impl Responder {
+    HttpResponse::Ok().json(provider_data.wallet_reports())
+}
+
+#[get("/api/providers/quorum")]
+pub async fn get_quorum_metrics(provider_data: web::Data<Arc<ProviderManager>>) -> impl Responder {
+    HttpResponse::Ok().json(provider_data.quorum_metrics.snapshot())
+}
+
+#[get("/api/providers/health")]
+pub async fn get_provider_health(provider_data: web::Data<Arc<ProviderManager>>) -> impl Responder {
+    HttpResponse::Ok().json(provider_data.provider_health_reports())
+}
+
+#[get("/api/risk")]
+pub async fn get_risk_status(risk: web::Data<Arc<RiskManager>>) -> impl Responder {
+    HttpResponse::Ok().json(risk.status())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KillSwitchRequest {
+    /// "engage" or "reset"
+    pub action: String,
+    pub reason: Option<String>,
+}
+
+/// Engage or reset the circuit breaker. Requires `Authorization: Bearer <risk_admin_token>`.
+#[post("/api/risk/kill_switch")]
+pub async fn post_kill_switch(
+    req: HttpRequest,
+    body: web::Json<KillSwitchRequest>,
+    risk: web::Data<Arc<RiskManager>>,
+) -> HttpResponse {
+    if !risk.kill_switch_enabled() {
+        return HttpResponse::Forbidden()
+            .json(serde_json::json!({"status": "error", "reason": "Kill switch disabled: risk_admin_token is not configured"}));
+    }
+    let presented = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|token| risk.authorize(token.trim())) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({"status": "error", "reason": "Invalid or missing admin token"}));
+    }
+    let reason = body.reason.clone().unwrap_or_else(|| "kill switch".to_string());
+    match body.action.as_str() {
+        "engage" => risk.engage(&format!("kill switch: {}", reason)),
+        "reset" => risk.reset(&reason),
+        other => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"status": "error", "reason": format!("Unknown action '{}': expected 'engage' or 'reset'", other)}));
+        }
+    }
+    HttpResponse::Ok().json(serde_json::json!({"status": "success", "risk": risk.status()}))
+}
+
+/// Total PnL in USD (realized plus unrealized), as a bare number for the dashboard.
+#[get("/api/profit")]
+pub async fn get_profit(ledger: web::Data<Arc<Ledger>>) -> impl Responder {
+    HttpResponse::Ok().json(ledger.total_pnl_usd())
+}
+
+#[get("/api/ledger")]
+pub async fn get_ledger(ledger: web::Data<Arc<Ledger>>) -> impl Responder {
+    HttpResponse::Ok().json(ledger.report())
+}
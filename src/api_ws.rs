@@ -1,23 +1,46 @@
-use actix::{Actor, StreamHandler};
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
-use actix::ActorContext;
-use std::time::Instant;
-use log::{error, info};
-use actix::fut;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use log::{error, info, warn};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
 
-use crate::events::{WebSocketEvent};
+use crate::events::WebSocketEvent;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct Matrix2dWs {
     last_ping: Instant,
-    event_rx: Receiver<WebSocketEvent>,
+    event_rx: Option<Receiver<WebSocketEvent>>,
 }
 
 impl Matrix2dWs {
     pub fn new(event_rx: Receiver<WebSocketEvent>) -> Self {
         Self {
             last_ping: Instant::now(),
-            event_rx,
+            event_rx: Some(event_rx),
+        }
+    }
+
+    /// Validate an event sent by the client and build the reply: the event itself when it
+    /// is well-formed, otherwise an `{"error": ...}` object.
+    fn client_event_reply(text: &str) -> Value {
+        let event: Value = match serde_json::from_str(text) {
+            Ok(event) => event,
+            Err(e) => return json!({ "error": format!("Invalid JSON: {}", e) }),
+        };
+        let Some(event_type) = event.get("type").and_then(Value::as_str) else {
+            return json!({ "error": "Missing required 'type' field" });
+        };
+        match event_type {
+            "dex" => event,
+            "liquidation" if event.get("account").and_then(Value::as_str).is_none() => {
+                json!({ "error": "Missing required 'account' field in liquidation event" })
+            }
+            "liquidation" => event,
+            other => json!({ "error": format!("Unknown event type: {}", other) }),
         }
     }
 }
@@ -27,61 +50,71 @@ impl Actor for Matrix2dWs {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         log::info!("WebSocket connection established");
-        ctx.text(r#"{"type": "welcome", "message": "Connected to Fusion WebSocket server"}"#);
+        ctx.text(r#"{"type": "welcome", "message": "Welcome to the Fusion WebSocket server"}"#);
         self.last_ping = Instant::now();
-    }
-}
 
-impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Matrix2dWs {
-    fn started(&mut self, ctx: &mut Self::Context) {
-        // Start event listener
-        ctx.run_interval(std::time::Duration::from_secs(1), move |act, ctx| {
-            act.event_rx.recv().then(|result| {
-                match result {
-                    Ok(event) => match serde_json::to_string(&event) {
-                        Ok(json) => {
-                            if let Err(e) = ctx.text(json) {
-                                error!("Failed to send event to client: {}", e);
-                                ctx.stop();
-                            }
+        // Forward broadcast events to the client
+        if let Some(rx) = self.event_rx.take() {
+            let events = futures::stream::unfold(rx, |mut rx| async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(event) => return Some((event, rx)),
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("WebSocket client lagged, {} events skipped", skipped);
                         }
-                        Err(e) => {
-                            error!("Failed to serialize event: {}", e);
-                            ctx.stop();
-                        }
-                    },
-                    Err(e) => {
-                        error!("Failed to receive event: {}", e);
-                        ctx.stop();
+                        Err(RecvError::Closed) => return None,
                     }
                 }
-                actix::fut::ready(())
-            })
-        });
+            });
+            ctx.add_stream(events);
+        }
 
-        // Send heartbeat every 10 seconds
-        ctx.run_interval(std::time::Duration::from_secs(10), |_, ctx| {
-            ctx.ping(b"ping").then(|result| {
-                if let Err(e) = result {
-                    error!("Failed to send ping: {}", e);
-                    ctx.stop();
-                }
-                actix::fut::ready(())
-            })
+        // Ping the client and drop it once it stops answering
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.last_ping) > CLIENT_TIMEOUT {
+                warn!("WebSocket client heartbeat timed out, disconnecting");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"ping");
         });
     }
+}
+
+impl StreamHandler<WebSocketEvent> for Matrix2dWs {
+    fn handle(&mut self, event: WebSocketEvent, ctx: &mut Self::Context) {
+        match serde_json::to_string(&event) {
+            Ok(json) => ctx.text(json),
+            Err(e) => error!("Failed to serialize event: {}", e),
+        }
+    }
+
+    // The event channel closing must not close the client connection
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
 
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Matrix2dWs {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         match msg {
             Ok(ws::Message::Ping(msg)) => {
                 ctx.pong(&msg);
                 self.last_ping = Instant::now();
             }
+            Ok(ws::Message::Pong(_)) => {
+                self.last_ping = Instant::now();
+            }
             Ok(ws::Message::Text(text)) => {
                 info!("Received text message: {}", text);
+                let text = text.trim();
+                if text.starts_with('{') {
+                    ctx.text(Self::client_event_reply(text).to_string());
+                } else {
+                    ctx.text(format!("Echo: {}", text));
+                }
             }
             Ok(ws::Message::Close(reason)) => {
                 info!("WebSocket connection closed: {:?}", reason);
+                ctx.close(reason);
                 ctx.stop();
             }
             Err(e) => {
@@ -100,14 +133,14 @@ pub async fn ws_matrix2d_handler(
 ) -> Result<HttpResponse, Error> {
     log::info!("WebSocket connection attempt from: {}", req.connection_info().realip_remote_addr().unwrap_or("unknown"));
     log::info!("Request headers: {:#?}", req.headers());
-    
+
     let rx = event_sender.subscribe();
     let response = ws::start(
         Matrix2dWs::new(rx),
         &req,
         stream,
     )?;
-    
+
     log::info!("WebSocket connection response sent");
     Ok(response)
 }
@@ -0,0 +1,231 @@
+// Private bundle submission: the signed executor transaction (optionally behind a backrun
+// target) is sent as an `eth_sendBundle` bundle to the configured builder/relay endpoints
+// instead of the public mempool. Requests carry an `X-Flashbots-Signature` header signed with
+// the bundle auth key. Bundles target one block at a time and are retargeted at the next block
+// until the transaction is included or `bundle_target_blocks` blocks have passed.
+
+use crate::config::Settings;
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::{hex, keccak256};
+use futures::future::join_all;
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use std::time::Duration;
+use thiserror::Error;
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const SIGNATURE_HEADER: &str = "X-Flashbots-Signature";
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("No bundle relays configured")]
+    NoRelays,
+    #[error("Invalid bundle_auth_private_key: {0}")]
+    AuthKey(String),
+    #[error("Cannot sign executor transaction: {0}")]
+    Signing(String),
+    #[error("Relay {relay} rejected the bundle: {message}")]
+    Relay { relay: String, message: String },
+    #[error("Every relay rejected the bundle for block {0}")]
+    AllRelaysFailed(u64),
+    #[error("Provider error: {0}")]
+    Provider(String),
+}
+
+/// An `eth_sendBundle` bundle: signed raw transactions, executed in order in `block_number`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    pub txs: Vec<Bytes>,
+    pub block_number: u64,
+}
+
+impl Bundle {
+    fn request_body(&self) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendBundle",
+            "params": [{
+                "txs": self.txs,
+                "blockNumber": format!("0x{:x}", self.block_number),
+            }],
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BundleConfig {
+    pub relays: Vec<String>,
+    /// Blocks to target, one after the other, before giving up
+    pub target_blocks: u64,
+    pub relay_timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl BundleConfig {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            relays: settings.bundle_relay_urls.clone(),
+            target_blocks: settings.bundle_target_blocks.max(1),
+            relay_timeout: Duration::from_millis(settings.bundle_relay_timeout_ms),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BundleOutcome {
+    Included { block: u64, tx_hash: TxHash },
+    NotIncluded { last_block: u64 },
+}
+
+/// Fill in and sign `tx` with the client's signer, returning the raw transaction and its hash.
+pub async fn sign_raw<M: Middleware>(client: &M, tx: &mut TypedTransaction) -> Result<(Bytes, TxHash), BundleError> {
+    client.fill_transaction(tx, None).await.map_err(|e| BundleError::Signing(e.to_string()))?;
+    let from = tx
+        .from()
+        .copied()
+        .or_else(|| client.default_sender())
+        .ok_or_else(|| BundleError::Signing("no sender".to_string()))?;
+    let signature = client.sign_transaction(tx, from).await.map_err(|e| BundleError::Signing(e.to_string()))?;
+    let raw = tx.rlp_signed(&signature);
+    let tx_hash = TxHash::from(keccak256(&raw));
+    Ok((raw, tx_hash))
+}
+
+#[derive(Debug)]
+pub struct BundleSubmitter {
+    config: BundleConfig,
+    // Identifies us to relays (reputation); holds no funds
+    auth: LocalWallet,
+    http: reqwest::Client,
+}
+
+impl BundleSubmitter {
+    pub fn new(config: BundleConfig, auth: LocalWallet) -> Result<Self, BundleError> {
+        if config.relays.is_empty() {
+            return Err(BundleError::NoRelays);
+        }
+        let http = reqwest::Client::builder()
+            .timeout(config.relay_timeout)
+            .build()
+            .map_err(|e| BundleError::Provider(e.to_string()))?;
+        Ok(Self { config, auth, http })
+    }
+
+    pub fn from_settings(settings: &Settings) -> Result<Self, BundleError> {
+        let auth = match settings.bundle_auth_private_key.as_ref().filter(|k| !k.is_unset()) {
+            Some(key) => key
+                .expose()
+                .trim_start_matches("0x")
+                .parse::<LocalWallet>()
+                .map_err(|e| BundleError::AuthKey(e.to_string()))?,
+            None => {
+                let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+                info!("[Bundles] No bundle_auth_private_key set; using ephemeral identity {:?}", wallet.address());
+                wallet
+            }
+        };
+        Self::new(BundleConfig::from_settings(settings), auth)
+    }
+
+    pub fn auth_address(&self) -> Address {
+        self.auth.address()
+    }
+
+    /// `X-Flashbots-Signature` value for a request body: `<address>:<signature>` over the
+    /// hex-encoded keccak256 of the body, signed as an EIP-191 message.
+    pub async fn signature_header(&self, body: &str) -> Result<String, BundleError> {
+        let digest = format!("0x{}", hex::encode(keccak256(body.as_bytes())));
+        let signature = self.auth.sign_message(digest).await.map_err(|e| BundleError::Signing(e.to_string()))?;
+        Ok(format!("{:?}:0x{}", self.auth.address(), signature))
+    }
+
+    /// Send `bundle` to one relay and return the bundle hash it reported, if any.
+    pub async fn send_to_relay(&self, relay: &str, bundle: &Bundle) -> Result<Option<H256>, BundleError> {
+        let relay_error = |message: String| BundleError::Relay { relay: relay.to_string(), message };
+        let body = bundle.request_body().to_string();
+        let signature = self.signature_header(&body).await?;
+        let response = self
+            .http
+            .post(relay)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| relay_error(e.to_string()))?;
+        let status = response.status();
+        let reply: Value = response.json().await.map_err(|e| relay_error(format!("HTTP {}: {}", status, e)))?;
+        if let Some(error) = reply.get("error") {
+            return Err(relay_error(error.get("message").and_then(Value::as_str).unwrap_or("unknown error").to_string()));
+        }
+        Ok(reply
+            .get("result")
+            .and_then(|r| r.get("bundleHash"))
+            .and_then(|h| serde_json::from_value(h.clone()).ok()))
+    }
+
+    /// Send `bundle` to every relay concurrently. Fails only if all of them reject it.
+    pub async fn send_bundle(&self, bundle: &Bundle) -> Result<usize, BundleError> {
+        let results = join_all(self.config.relays.iter().map(|relay| self.send_to_relay(relay, bundle))).await;
+        let mut accepted = 0;
+        for (relay, result) in self.config.relays.iter().zip(results) {
+            match result {
+                Ok(bundle_hash) => {
+                    debug!("[Bundles] {} accepted bundle for block {} ({:?})", relay, bundle.block_number, bundle_hash);
+                    accepted += 1;
+                }
+                Err(e) => warn!("[Bundles] {}", e),
+            }
+        }
+        if accepted == 0 {
+            return Err(BundleError::AllRelaysFailed(bundle.block_number));
+        }
+        Ok(accepted)
+    }
+
+    /// Submit `raw_tx` (hash `tx_hash`), placed right after `backrun_target` if given, for the
+    /// next `bundle_target_blocks` blocks in turn until it is included.
+    pub async fn submit<M: Middleware>(
+        &self,
+        client: &M,
+        raw_tx: Bytes,
+        tx_hash: TxHash,
+        backrun_target: Option<Bytes>,
+    ) -> Result<BundleOutcome, BundleError> {
+        let txs: Vec<Bytes> = backrun_target.into_iter().chain(std::iter::once(raw_tx)).collect();
+        let head = self.block_number(client).await?;
+        let last_block = head + self.config.target_blocks;
+        for block_number in head + 1..=last_block {
+            let bundle = Bundle { txs: txs.clone(), block_number };
+            if let Err(e) = self.send_bundle(&bundle).await {
+                warn!("[Bundles] {}", e);
+            }
+            while self.block_number(client).await? < block_number {
+                tokio::time::sleep(self.config.poll_interval).await;
+            }
+            if let Some(receipt) = client
+                .get_transaction_receipt(tx_hash)
+                .await
+                .map_err(|e| BundleError::Provider(e.to_string()))?
+            {
+                let block = receipt.block_number.map(|b| b.as_u64()).unwrap_or(block_number);
+                info!("[Bundles] 0x{:x} included in block {}", tx_hash, block);
+                return Ok(BundleOutcome::Included { block, tx_hash });
+            }
+            debug!("[Bundles] 0x{:x} not in block {}; retargeting", tx_hash, block_number);
+        }
+        warn!("[Bundles] 0x{:x} not included by block {}", tx_hash, last_block);
+        Ok(BundleOutcome::NotIncluded { last_block })
+    }
+
+    async fn block_number<M: Middleware>(&self, client: &M) -> Result<u64, BundleError> {
+        client
+            .get_block_number()
+            .await
+            .map(|b| b.as_u64())
+            .map_err(|e| BundleError::Provider(e.to_string()))
+    }
+}
@@ -0,0 +1,279 @@
+// Data-driven chain registry loaded from the `[[chains]]` tables in config.
+// Each entry carries the chain ID, native token, per-provider RPC/WS URLs, explorer, gas model
+// and the contracts the bot uses there (executor, DEX routers, flashloan lenders, lending markets
+// watched for liquidations), so testnets
+// and further EVM chains can be enabled without code changes.
+
+use crate::config::Settings;
+use crate::token_registry::BSC_MAINNET_CHAIN_ID;
+use ethers::types::Address;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// How transactions on a chain are priced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GasModel {
+    /// Single `gasPrice` (e.g. BSC)
+    #[default]
+    Legacy,
+    /// Base fee + priority fee (EIP-1559)
+    Eip1559,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    /// Short name used in API requests and logs, e.g. "BSC" or "Sepolia"
+    pub name: String,
+    pub native_token: String,
+    /// Symbol of the wrapped native token, used to price the native coin (e.g. "WBNB")
+    #[serde(default)]
+    pub wrapped_native_token: String,
+    /// Token addresses keyed by symbol, registered in the TokenRegistry
+    #[serde(default)]
+    pub tokens: HashMap<String, String>,
+    /// Deployed ArbitrageExecutor; empty if it isn't deployed on this chain
+    #[serde(default)]
+    pub executor_contract: String,
+    /// DEX router addresses keyed by DEX name (as in `dexes`)
+    #[serde(default)]
+    pub routers: HashMap<String, String>,
+    /// Flashloan lender addresses
+    #[serde(default)]
+    pub flashloan: FlashloanAddresses,
+    /// Lending markets watched for liquidations
+    #[serde(default)]
+    pub lending: LendingAddresses,
+    /// RPC endpoints keyed by provider name (matching `provider_priority_order`)
+    pub rpc_urls: HashMap<String, String>,
+    /// WebSocket endpoints keyed by provider name
+    pub ws_urls: HashMap<String, String>,
+    pub explorer_url: String,
+    pub gas_model: GasModel,
+    /// Cap on fee suggestions (wei): the gas price on legacy chains, maxFeePerGas on EIP-1559 ones
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u64>,
+    /// Cap on maxPriorityFeePerGas suggestions (wei)
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<u64>,
+    pub enabled: bool,
+    pub testnet: bool,
+}
+
+/// Flashloan lender contracts on one chain, named like the global settings they override.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FlashloanAddresses {
+    pub aave_pool_address_provider: Option<String>,
+    pub balancer_vault_address: Option<String>,
+    pub uniswap_v3_factory: Option<String>,
+    pub pancakeswap_v2_factory: Option<String>,
+    pub pancakeswap_v3_factory: Option<String>,
+}
+
+/// Comptrollers of the Compound v2-style lending markets on one chain. Aave is read through the
+/// chain's `aave_pool_address_provider`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct LendingAddresses {
+    pub venus_comptroller: Option<String>,
+    pub compound_comptroller: Option<String>,
+}
+
+impl LendingAddresses {
+    pub fn venus(&self) -> Option<Address> {
+        parse_address(&self.venus_comptroller)
+    }
+
+    pub fn compound(&self) -> Option<Address> {
+        parse_address(&self.compound_comptroller)
+    }
+}
+
+fn parse_address(addr: &Option<String>) -> Option<Address> {
+    addr.as_deref()?.trim().parse::<Address>().ok().filter(|a| !a.is_zero())
+}
+
+/// Substitute ${INFURA_API_KEY}, ${ALCHEMY_API_KEY}, ${NODEREAL_API_KEY} in a URL string
+pub fn substitute_provider_keys(url: &str) -> String {
+    let mut out = url.to_string();
+    for var in ["INFURA_API_KEY", "ALCHEMY_API_KEY", "NODEREAL_API_KEY"] {
+        if let Ok(val) = std::env::var(var) {
+            out = out.replace(&format!("${{{}}}", var), &val);
+        }
+    }
+    out
+}
+
+// Order a provider -> URL map by `priority`; providers missing from the priority list follow
+// in name order. Empty URLs are dropped and API key placeholders are substituted.
+fn in_priority_order(urls: &HashMap<String, String>, priority: &[String]) -> Vec<(String, String)> {
+    let rank = |name: &str| {
+        priority
+            .iter()
+            .position(|p| p.eq_ignore_ascii_case(name))
+            .unwrap_or(priority.len())
+    };
+    let mut ordered: Vec<(&String, &String)> = urls.iter().filter(|(_, url)| !url.trim().is_empty()).collect();
+    ordered.sort_by(|(a, _), (b, _)| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+    ordered
+        .into_iter()
+        .map(|(name, url)| (name.clone(), substitute_provider_keys(url)))
+        .collect()
+}
+
+impl ChainConfig {
+    /// (provider name, RPC URL) pairs in provider priority order
+    pub fn rpc_endpoints(&self, priority: &[String]) -> Vec<(String, String)> {
+        in_priority_order(&self.rpc_urls, priority)
+    }
+
+    /// (provider name, WebSocket URL) pairs in provider priority order
+    pub fn ws_endpoints(&self, priority: &[String]) -> Vec<(String, String)> {
+        in_priority_order(&self.ws_urls, priority)
+    }
+
+    /// Explorer link for a transaction hash
+    pub fn explorer_tx_url(&self, tx_hash: &str) -> String {
+        format!("{}/tx/{}", self.explorer_url.trim_end_matches('/'), tx_hash)
+    }
+
+    /// The ArbitrageExecutor deployed on this chain, if any
+    pub fn executor_address(&self) -> Option<Address> {
+        self.executor_contract.trim().parse::<Address>().ok().filter(|a| !a.is_zero())
+    }
+
+    /// Router addresses keyed by DEX name. BSC mainnet's default to the `router_*` settings;
+    /// entries in `routers` take precedence.
+    pub fn router_addresses(&self, settings: &Settings) -> HashMap<String, String> {
+        let mut routers = HashMap::new();
+        if self.chain_id == BSC_MAINNET_CHAIN_ID {
+            for (dex, addr) in settings.router_addresses() {
+                routers.insert(dex.to_string(), addr.to_string());
+            }
+        }
+        routers.extend(self.routers.iter().map(|(dex, addr)| (dex.clone(), addr.clone())));
+        routers
+    }
+
+    /// Flashloan lender addresses. BSC mainnet's default to the global settings; entries in
+    /// `flashloan` take precedence. Unset lenders are not used on this chain.
+    pub fn flashloan_addresses(&self, settings: &Settings) -> FlashloanAddresses {
+        let bsc = self.chain_id == BSC_MAINNET_CHAIN_ID;
+        let pick = |own: &Option<String>, global: &String| {
+            own.clone().or_else(|| Some(global.clone()).filter(|_| bsc)).filter(|addr| !addr.trim().is_empty())
+        };
+        FlashloanAddresses {
+            aave_pool_address_provider: pick(&self.flashloan.aave_pool_address_provider, &settings.aave_pool_address_provider),
+            balancer_vault_address: pick(&self.flashloan.balancer_vault_address, &settings.balancer_vault_address),
+            uniswap_v3_factory: pick(&self.flashloan.uniswap_v3_factory, &settings.uniswap_v3_factory),
+            pancakeswap_v2_factory: pick(&self.flashloan.pancakeswap_v2_factory, &settings.pancakeswap_v2_factory),
+            pancakeswap_v3_factory: pick(&self.flashloan.pancakeswap_v3_factory, &settings.pancakeswap_v3_factory),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChainRegistry {
+    chains: HashMap<u64, ChainConfig>,
+}
+
+impl ChainRegistry {
+    pub fn new(chains: Vec<ChainConfig>) -> Self {
+        Self { chains: chains.into_iter().map(|c| (c.chain_id, c)).collect() }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(settings.chains.clone())
+    }
+
+    pub fn get(&self, chain_id: u64) -> Option<&ChainConfig> {
+        self.chains.get(&chain_id)
+    }
+
+    /// Enabled chains, ordered by chain ID
+    pub fn enabled(&self) -> Vec<&ChainConfig> {
+        let mut chains: Vec<&ChainConfig> = self.chains.values().filter(|c| c.enabled).collect();
+        chains.sort_by_key(|c| c.chain_id);
+        chains
+    }
+
+    /// Resolve a chain by numeric ID or (case-insensitive) name, e.g. "56" or "bsc"
+    pub fn resolve(&self, chain: &str) -> Option<&ChainConfig> {
+        let chain = chain.trim();
+        match chain.parse::<u64>() {
+            Ok(chain_id) => self.get(chain_id),
+            Err(_) => self.chains.values().find(|c| c.name.eq_ignore_ascii_case(chain)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(chain_id: u64, name: &str, enabled: bool) -> ChainConfig {
+        ChainConfig {
+            chain_id,
+            name: name.to_string(),
+            native_token: "BNB".to_string(),
+            rpc_urls: HashMap::from([
+                ("NodeReal".to_string(), "https://nodereal".to_string()),
+                ("Infura".to_string(), "https://infura".to_string()),
+                ("Public".to_string(), "https://public".to_string()),
+                ("Alchemy".to_string(), "".to_string()),
+            ]),
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rpc_endpoints_follow_priority() {
+        let priority = vec!["Infura".to_string(), "Alchemy".to_string(), "NodeReal".to_string()];
+        let names: Vec<String> = chain(56, "BSC", true)
+            .rpc_endpoints(&priority)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["Infura", "NodeReal", "Public"]);
+    }
+
+    #[test]
+    fn test_contracts_are_per_chain() {
+        let settings = Settings {
+            router_pancakeswap: "0x10ED43C718714eb63d5aA57B78B54704E256024E".to_string(),
+            balancer_vault_address: "0xBA12222222228d8Ba445958a75a0704d566BF2C8".to_string(),
+            ..Default::default()
+        };
+        // BSC mainnet falls back to the global settings
+        let bsc = chain(56, "BSC", true);
+        assert_eq!(bsc.router_addresses(&settings)["PancakeSwap"], settings.router_pancakeswap);
+        assert_eq!(bsc.flashloan_addresses(&settings).balancer_vault_address, Some(settings.balancer_vault_address.clone()));
+        assert_eq!(bsc.flashloan_addresses(&settings).aave_pool_address_provider, None);
+        assert_eq!(bsc.executor_address(), None);
+
+        // Other chains only use their own
+        let sepolia = ChainConfig {
+            executor_contract: "0x0000000000000000000000000000000000000007".to_string(),
+            routers: HashMap::from([("Uniswap".to_string(), "0x0000000000000000000000000000000000000008".to_string())]),
+            flashloan: FlashloanAddresses { aave_pool_address_provider: Some("0x09".to_string()), ..Default::default() },
+            ..chain(11155111, "Sepolia", true)
+        };
+        assert_eq!(sepolia.executor_address(), Some(Address::from_low_u64_be(7)));
+        assert_eq!(sepolia.router_addresses(&settings).keys().collect::<Vec<_>>(), vec!["Uniswap"]);
+        let flashloan = sepolia.flashloan_addresses(&settings);
+        assert_eq!((flashloan.aave_pool_address_provider.as_deref(), flashloan.balancer_vault_address), (Some("0x09"), None));
+
+        let lending = LendingAddresses { venus_comptroller: Some("0x000000000000000000000000000000000000000a".to_string()), compound_comptroller: Some(String::new()) };
+        assert_eq!((lending.venus(), lending.compound()), (Some(Address::from_low_u64_be(10)), None));
+    }
+
+    #[test]
+    fn test_resolve_by_id_or_name() {
+        let registry = ChainRegistry::new(vec![chain(56, "BSC", true), chain(97, "BSC-Testnet", false)]);
+        assert_eq!(registry.resolve("bsc-testnet").map(|c| c.chain_id), Some(97));
+        assert_eq!(registry.resolve("56").map(|c| c.name.as_str()), Some("BSC"));
+        assert!(registry.resolve("polygon").is_none());
+        assert_eq!(registry.enabled().len(), 1);
+    }
+}
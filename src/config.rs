@@ -1,5 +1,7 @@
 // /home/user/Fusion/src/config.rs
 
+use crate::chain_registry::ChainConfig;
+use crate::secrets::SecretString;
 use serde::Deserialize;
 
 // --- Helper function to parse comma-separated strings ---
@@ -22,39 +24,20 @@ pub struct Settings {
 
     // --- Infura ---
     /// Infura API key (must be set via environment variable in production)
-    pub infura_api_key: Option<String>,
+    pub infura_api_key: Option<SecretString>,
     pub rpc_url: String,
-    pub bsc_rpc_url: String,
-    pub bsc_testnet_rpc_url: String,
-    pub eth_rpc_url: String,
-    pub eth_sepolia_rpc_url: String,
-    pub infura_eth_websocket_url: String,
-    pub infura_eth_sepolia_websocket_url: String,
-    pub infura_bsc_websocket_url: String,
 
     // --- Alchemy ---
     /// Alchemy API key (must be set via environment variable in production)
-    pub alchemy_api_key: Option<String>,
-    pub alchemy_bsc_rpc_url: String,
-    pub alchemy_eth_rpc_url: String,
-    pub alchemy_bsc_testnet_rpc_url: String,
-    pub alchemy_eth_sepolia_rpc_url: String,
-    pub alchemy_eth_websocket_url: String,
-    pub alchemy_eth_sepolia_websocket_url: String,
-    pub alchemy_bsc_websocket_url: String,
-    pub alchemy_bsc_testnet_websocket_url: String,
+    pub alchemy_api_key: Option<SecretString>,
 
     // --- NodeReal ---
     /// NodeReal API key (must be set via environment variable in production)
-    pub nodereal_api_key: Option<String>,
-    pub nodereal_bsc_rpc_url: String,
-    pub nodereal_eth_rpc_url: String,
-    pub nodereal_bsc_testnet_rpc_url: String,
-    pub nodereal_eth_sepolia_rpc_url: String,
-    pub nodereal_eth_websocket_url: String,
-    pub nodereal_eth_sepolia_websocket_url: String,
-    pub nodereal_bsc_websocket_url: String,
-    pub nodereal_bsc_testnet_websocket_url: String,
+    pub nodereal_api_key: Option<SecretString>,
+
+    // --- Chains ---
+    /// Chain registry entries (`[[chains]]` tables): chain ID, RPC/WS URLs per provider, gas model
+    pub chains: Vec<ChainConfig>,
 
     // --- DEX WebSockets ---
     pub websocket_pancakeswap: String,
@@ -78,7 +61,16 @@ pub struct Settings {
 
     // --- Deployment Keys ---
     /// Private key for deployment (must be set via environment variable in production)
-    pub private_key: Option<String>,
+    pub private_key: Option<SecretString>,
+    /// Encrypted JSON keystore for the executor wallet; takes precedence over `private_key`
+    pub keystore_path: Option<String>,
+    /// File holding the keystore password (else KEYSTORE_PASSWORD, else an interactive prompt)
+    pub keystore_password_file: Option<String>,
+    /// Web3Signer-compatible signing service; when set, no key is loaded into the process
+    pub remote_signer_url: Option<String>,
+    /// Key to use on the remote signer (defaults to the first one it reports)
+    pub remote_signer_address: Option<String>,
+    pub remote_signer_timeout_ms: u64,
     /// Profit wallet address (must be set via environment variable in production)
     pub profit_wallet: Option<String>,
 
@@ -93,6 +85,10 @@ pub struct Settings {
     pub balancer_vault_address: String,
     pub uniswap_v3_factory: String,
     pub pancakeswap_v2_factory: String,
+    pub pancakeswap_v3_factory: String,
+    /// Tokens paired with the borrowed token when looking up V2 pairs and V3 pools to flash from
+    #[serde(deserialize_with = "parse_comma_separated_string")]
+    pub flashloan_pair_tokens: Vec<String>,
     pub liquidity_cache_ttl_ms: u64,
     pub liquidity_threshold_minimum_usd: f64, // Changed to f64 for potential decimals
     pub refresh_liquidity_interval_ms: u64,
@@ -110,7 +106,9 @@ pub struct Settings {
     pub marginal_optimizer: f64,
     pub min_profit_usd: f64,
     pub max_slippage: f64,
-    pub gas_price_buffer: i64, // Assuming integer Gwei or similar, verify unit
+    pub execution_quote_token: String, // Symbol of the token arbitrage loans are taken in and profit is measured in
+    pub execution_chain: String, // Chain (name or ID from [[chains]]) the DEX matrix, executor and sweeper run on
+    pub gas_price_buffer: i64, // Percent added on top of observed gas prices by the gas oracle
     pub gas_price_buffer_percentage: f64,
 
     // --- Parallel Processing Configuration ---
@@ -124,10 +122,9 @@ pub struct Settings {
     pub concurrent_matrix_processing: bool,
 
     // --- Gas Settings ---
-    pub max_fee_per_gas: u64,
-    pub max_priority_fee_per_gas: u64,
     pub gas_estimator: String,
     pub gas_price_update_interval_ms: u64,
+    pub arbitrage_gas_limit: u64, // Gas units budgeted per arbitrage when estimating net profit
 
     // --- DEX Router Addresses ---
     pub router_pancakeswap: String,
@@ -263,14 +260,44 @@ pub struct Settings {
     pub matrix5_liquidity_check: bool,
     pub matrix5_max_liquidity_utilization: f64,
 
+    // --- Executor Wallet Pool ---
+    /// "matrix", "round_robin" or "least_pending"
+    pub wallet_assignment_strategy: String,
+    /// Native balance (BNB/ETH) below which a wallet gets no new transactions
+    pub wallet_min_balance: f64,
+    pub wallet_balance_refresh_interval_ms: u64,
+    pub matrix1_wallet_private_key: Option<SecretString>,
+    pub matrix1_wallet_address: Option<String>,
+    pub matrix2_wallet_private_key: Option<SecretString>,
+    pub matrix2_wallet_address: Option<String>,
+    pub matrix3_wallet_private_key: Option<SecretString>,
+    pub matrix3_wallet_address: Option<String>,
+    pub matrix4_wallet_private_key: Option<SecretString>,
+    pub matrix4_wallet_address: Option<String>,
+    pub matrix5_wallet_private_key: Option<SecretString>,
+    pub matrix5_wallet_address: Option<String>,
+    pub matrix6_wallet_private_key: Option<SecretString>,
+    pub matrix6_wallet_address: Option<String>,
+    pub matrix7_wallet_private_key: Option<SecretString>,
+    pub matrix7_wallet_address: Option<String>,
+    pub matrix8_wallet_private_key: Option<SecretString>,
+    pub matrix8_wallet_address: Option<String>,
+    pub matrix9_wallet_private_key: Option<SecretString>,
+    pub matrix9_wallet_address: Option<String>,
+    pub matrix10_wallet_private_key: Option<SecretString>,
+    pub matrix10_wallet_address: Option<String>,
+
     // --- Pre-Execution Validation ---
     #[serde(deserialize_with = "parse_comma_separated_string")]
     pub pre_execution_checks: Vec<String>,
     pub transaction_max_age_ms: u64,
     pub transaction_assembly_max_time_ms: u64,
+    #[serde(deserialize_with = "parse_comma_separated_string")]
+    pub token_allowlist: Vec<String>, // Token symbols executions may touch; empty allows every registered token
+    pub max_exposure_usd: f64, // Largest loan (in USD) a single execution may take
     pub mempool_monitoring: bool,
     // pub gas_price_buffer_percentage: f64, // Already defined above
-    pub simulate_transaction_before_sending: bool,
+    pub simulate_transaction_before_sending: bool, // Unused: executor calls are always simulated before sending
 
     // --- Performance Optimization ---
     pub use_shared_memory: bool,
@@ -286,9 +313,42 @@ pub struct Settings {
     pub transaction_confirmation_blocks: u64,
     pub auto_retry_failed_transactions: bool,
     pub minimum_profitable_amount_usd: f64,
+    pub nonce_gap_check_interval_ms: u64,
+    pub nonce_gap_grace_ms: u64,
+    pub bundle_submission_enabled: bool, // Send executor transactions as private bundles instead of via the public mempool
+    #[serde(deserialize_with = "parse_comma_separated_string")]
+    pub bundle_relay_urls: Vec<String>,
+    pub bundle_auth_private_key: Option<SecretString>, // Identity key for X-Flashbots-Signature; random per run if unset
+    pub bundle_target_blocks: u64,
+    pub bundle_relay_timeout_ms: u64,
+
+    // --- Risk Management ---
+    pub risk_management_enabled: bool,
+    pub max_daily_loss_usd: f64, // Realized loss (UTC day) that trips the circuit breaker; 0 disables
+    pub max_rolling_loss_usd: f64, // Realized loss within rolling_loss_window_ms that trips the breaker; 0 disables
+    pub rolling_loss_window_ms: u64,
+    pub max_consecutive_failures: u32, // Failed or reverted executions in a row that trip the breaker; 0 disables
+    pub max_trade_notional_usd: f64, // Largest loan (USD) per execution; 0 disables
+    pub max_token_notional_usd: f64, // Executed notional (USD) per token per UTC day; 0 disables
+    pub revert_cooldown_ms: u64, // No executions for this long after a revert
+    pub risk_admin_token: Option<SecretString>, // Bearer token for the kill-switch API; the endpoint is disabled if unset
+
+    // --- Profit Sweeping ---
+    pub profit_sweep_enabled: bool,
+    pub profit_sweep_interval_ms: u64,
+    pub sweep_native_working_balance: f64, // BNB left in each executor wallet for gas (at least wallet_min_balance)
+    /// "SYMBOL:working_balance" per token to sweep; the working balance (whole tokens) stays in each wallet
+    #[serde(deserialize_with = "parse_comma_separated_string")]
+    pub sweep_tokens: Vec<String>,
+    pub sweep_min_usd: f64, // Smaller excess waits for a later sweep
+    pub sweep_max_gas_pct: f64, // Skip a transfer whose gas would cost more than this % of its value
+    pub sweep_max_transfers_per_run: usize,
+
+    // --- Ledger ---
+    pub ledger_mark_price_max_age_ms: u64, // Matrix prices older than this are not used to mark inventory or value gas
 
     // --- Verification ---
-    pub etherscan_api_key: Option<String>, // Use Option for optional keys
+    pub etherscan_api_key: Option<SecretString>, // Use Option for optional keys
 
     // --- Logging ---
     pub log_level: String,
@@ -308,6 +368,176 @@ pub struct Settings {
     // --- Provider Rotation ---
     pub provider_rotation_enabled: bool,
     pub provider_rotation_interval_ms: u64,
+    pub provider_health_probe_interval_ms: u64,
+    pub provider_rate_limits: Vec<String>, // "Provider:requests_per_minute"; unlisted providers are unlimited
+
+    // --- Provider Quotas ---
+    pub quota_store_path: String,
+    pub quota_persist_interval_ms: u64,
+    pub provider_billing_reset_days: Vec<String>, // "Provider:day", e.g. "Infura:1"
+    pub quota_warn_percentage: f64,
+    pub quota_hard_stop_percentage: f64,
+    pub provider_monthly_limits: Vec<String>, // "Provider:requests", e.g. "Infura:3000000"; unlisted = unlimited
+    pub provider_daily_limits: Vec<String>,   // "Provider:requests"; unlisted = unlimited
+    pub provider_hourly_limits: Vec<String>,  // "Provider:requests"; unlisted = unlimited
+
+    // --- Quorum Reads ---
+    pub quorum_enabled: bool,
+    pub quorum_threshold: usize,
+    pub quorum_timeout_ms: u64,
+}
+
+// Value for `provider` in a "Provider:value" list (provider names match case-insensitively)
+fn provider_value<T: std::str::FromStr>(specs: &[String], provider: &str) -> Option<T> {
+    specs
+        .iter()
+        .filter_map(|spec| spec.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case(provider))
+        .and_then(|(_, value)| value.trim().parse().ok())
 }
 
-impl Settings {}
+impl Settings {
+    /// Day of month a provider's quota resets, from `provider_billing_reset_days` (default 1).
+    pub fn billing_reset_day(&self, provider: &str) -> u32 {
+        provider_value(&self.provider_billing_reset_days, provider).unwrap_or(1)
+    }
+
+    /// Requests per minute allowed to a provider, from `provider_rate_limits` (default unlimited).
+    pub fn provider_rate_limit(&self, provider: &str) -> u32 {
+        provider_value(&self.provider_rate_limits, provider).unwrap_or(u32::MAX)
+    }
+
+    /// Monthly, daily and hourly request quotas for a provider, from `provider_monthly_limits`,
+    /// `provider_daily_limits` and `provider_hourly_limits` (None = unlimited).
+    pub fn provider_quota_limits(&self, provider: &str) -> (Option<u64>, Option<u64>, Option<u64>) {
+        (
+            provider_value(&self.provider_monthly_limits, provider),
+            provider_value(&self.provider_daily_limits, provider),
+            provider_value(&self.provider_hourly_limits, provider),
+        )
+    }
+
+    /// Configured `matrixN_wallet_private_key` / `matrixN_wallet_address` slots as
+    /// (matrix number, key, address); slots with neither are skipped.
+    pub fn matrix_wallets(&self) -> Vec<(usize, Option<&SecretString>, Option<&str>)> {
+        [
+            (&self.matrix1_wallet_private_key, &self.matrix1_wallet_address),
+            (&self.matrix2_wallet_private_key, &self.matrix2_wallet_address),
+            (&self.matrix3_wallet_private_key, &self.matrix3_wallet_address),
+            (&self.matrix4_wallet_private_key, &self.matrix4_wallet_address),
+            (&self.matrix5_wallet_private_key, &self.matrix5_wallet_address),
+            (&self.matrix6_wallet_private_key, &self.matrix6_wallet_address),
+            (&self.matrix7_wallet_private_key, &self.matrix7_wallet_address),
+            (&self.matrix8_wallet_private_key, &self.matrix8_wallet_address),
+            (&self.matrix9_wallet_private_key, &self.matrix9_wallet_address),
+            (&self.matrix10_wallet_private_key, &self.matrix10_wallet_address),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (key, address))| {
+            (
+                i + 1,
+                key.as_ref().filter(|k| !k.is_unset()),
+                address.as_deref().map(str::trim).filter(|a| !a.is_empty()),
+            )
+        })
+        .filter(|(_, key, address)| key.is_some() || address.is_some())
+        .collect()
+    }
+
+    /// `matrixN_max_liquidity_utilization` (percent) for 1-based `matrix`, if set.
+    pub fn matrix_max_liquidity_utilization(&self, matrix: usize) -> Option<f64> {
+        let cap = match matrix {
+            1 => self.matrix1_max_liquidity_utilization,
+            2 => self.matrix2_max_liquidity_utilization,
+            3 => self.matrix3_max_liquidity_utilization,
+            4 => self.matrix4_max_liquidity_utilization,
+            5 => self.matrix5_max_liquidity_utilization,
+            _ => return None,
+        };
+        (cap > 0.0).then_some(cap)
+    }
+
+    /// All configured `router_*` addresses keyed by DEX name as used in `dexes`.
+    /// Entries with an empty address are skipped.
+    pub fn router_addresses(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("PancakeSwap", &self.router_pancakeswap),
+            ("Biswap", &self.router_biswap),
+            ("MDEX", &self.router_mdex),
+            ("BabySwap", &self.router_babyswap),
+            ("ApeSwap", &self.router_apeswap),
+            ("KokoSwap", &self.router_kokoswap),
+            ("Thena", &self.router_thena),
+            ("WaultSwap", &self.router_waultswap),
+            ("DODO", &self.router_dodo),
+            ("Ellipsis", &self.router_ellipsis),
+        ]
+        .into_iter()
+        .filter(|(_, addr)| !addr.trim().is_empty())
+        .map(|(dex, addr)| (dex, addr.as_str()))
+        .collect()
+    }
+
+    /// All configured `token_*` addresses keyed by their upper-case symbol (BSC mainnet).
+    /// Entries with an empty address are skipped.
+    pub fn token_addresses(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("WBNB", &self.token_wbnb),
+            ("CAKE", &self.token_cake),
+            ("BAKE", &self.token_bake),
+            ("XVS", &self.token_xvs),
+            ("SXP", &self.token_sxp),
+            ("ALPACA", &self.token_alpaca),
+            ("BSW", &self.token_bsw),
+            ("BABY", &self.token_baby),
+            ("BSCPAD", &self.token_bscpads),
+            ("BUSD", &self.token_busd),
+            ("USDT", &self.token_usdt),
+            ("USDC", &self.token_usdc),
+            ("DAI", &self.token_dai),
+            ("TUSD", &self.token_tusd),
+            ("FRAX", &self.token_frax),
+            ("VAI", &self.token_vai),
+            ("MIM", &self.token_mim),
+            ("USDP", &self.token_usdp),
+            ("ETH", &self.token_eth),
+            ("BTCB", &self.token_btcb),
+            ("DOT", &self.token_dot),
+            ("ADA", &self.token_ada),
+            ("XRP", &self.token_xrp),
+            ("SOL", &self.token_sol),
+            ("AVAX", &self.token_avax),
+            ("MATIC", &self.token_matic),
+            ("ATOM", &self.token_atom),
+            ("NEAR", &self.token_near),
+            ("FTM", &self.token_ftm),
+            ("TRX", &self.token_trx),
+            ("LTC", &self.token_ltc),
+            ("FIL", &self.token_fil),
+            ("LINK", &self.token_link),
+            ("UNI", &self.token_uni),
+            ("AAVE", &self.token_aave),
+            ("COMP", &self.token_comp),
+            ("MKR", &self.token_mkr),
+            ("SNX", &self.token_snx),
+            ("1INCH", &self.token_1inch),
+            ("CRV", &self.token_crv),
+            ("YFI", &self.token_yfi),
+            ("SUSHI", &self.token_sushi),
+            ("DOGE", &self.token_doge),
+            ("SHIB", &self.token_shib),
+            ("FLOKI", &self.token_floki),
+            ("BABYDOGE", &self.token_babydoge),
+            ("SAFEMOON", &self.token_safemoon),
+            ("CATE", &self.token_cate),
+            ("ELONGATE", &self.token_elongate),
+            ("LOWB", &self.token_lowb),
+            ("SAFEMARS", &self.token_safemars),
+        ]
+        .into_iter()
+        .filter(|(_, addr)| !addr.trim().is_empty())
+        .map(|(symbol, addr)| (symbol, addr.as_str()))
+        .collect()
+    }
+}
@@ -3,9 +3,11 @@ use chrono::Utc;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum WebSocketEvent {
     Dex(DexEvent),
     Liquidation(LiquidationEvent),
+    Alert(AlertEvent),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +30,18 @@ pub struct LiquidationEvent {
     pub created_at: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub id: String,
+    pub timestamp: String,
+    /// What raised the alert, e.g. "circuit_breaker"
+    pub source: String,
+    /// "info", "warning" or "critical"
+    pub severity: String,
+    pub message: String,
+    pub created_at: String,
+}
+
 pub struct WebSocketEventSender {
     tx: tokio::sync::broadcast::Sender<WebSocketEvent>,
 }
@@ -60,6 +74,18 @@ impl WebSocketEventSender {
         });
         let _ = self.tx.send(event);
     }
+
+    pub fn send_alert_event(&self, source: &str, severity: &str, message: &str) {
+        let event = WebSocketEvent::Alert(AlertEvent {
+            id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            source: source.to_string(),
+            severity: severity.to_string(),
+            message: message.to_string(),
+            created_at: Utc::now().to_rfc3339(),
+        });
+        let _ = self.tx.send(event);
+    }
 }
 
 pub struct WebSocketEventReceiver {
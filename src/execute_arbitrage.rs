@@ -1,48 +1,130 @@
 use ethers::prelude::*;
 use ethers::abi::Abi;
+use ethers::types::transaction::eip2718::TypedTransaction;
 use std::sync::Arc;
+use crate::execution_planner::ArbitragePlan;
+use crate::gas_oracle::FeeSuggestion;
+use crate::nonce_manager::is_nonce_error;
+use crate::simulation::{simulate, ProfitCheck, SimulationError};
+use crate::bundles::{sign_raw, BundleError, BundleOutcome, BundleSubmitter};
+use crate::pre_execution::{CheckPipeline, CheckRejection, ExecutionContext};
+use log::info;
+use thiserror::Error;
 
-/// Calls the ArbitrageExecutor contract's executeArbitrage function on BSC mainnet.
-pub async fn execute_arbitrage_onchain(
+/// Why `execute_arbitrage_onchain` did not submit. Only `Send` errors can have used the nonce.
+#[derive(Debug, Error)]
+pub enum ExecuteError {
+    #[error("Cannot build executeArbitrage call: {0}")]
+    Build(String),
+    #[error(transparent)]
+    Simulation(#[from] SimulationError),
+    #[error(transparent)]
+    Rejected(#[from] CheckRejection),
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+    #[error("Bundle with 0x{tx_hash:x} not included by block {last_block}")]
+    NotIncluded { tx_hash: TxHash, last_block: u64 },
+    #[error("Sending executeArbitrage failed: {0}")]
+    Send(String),
+}
+
+impl ExecuteError {
+    /// Whether the node refused the transaction's nonce, so the local counter is out of sync
+    pub fn is_nonce_error(&self) -> bool {
+        matches!(self, ExecuteError::Send(message) if is_nonce_error(message))
+    }
+}
+
+/// Per-submission parameters for `execute_arbitrage_onchain`.
+#[derive(Debug, Clone)]
+pub struct ExecutionOptions {
+    /// From the NonceManager, so concurrent submissions don't race on the same nonce
+    pub nonce: U256,
+    /// From the chain's GasOracle
+    pub fees: FeeSuggestion,
+    /// Minimum simulated profit
+    pub profit_check: Option<ProfitCheck>,
+    /// Send as a private bundle to the configured relays instead of the public mempool
+    pub bundles: Option<Arc<BundleSubmitter>>,
+    /// Run after simulation, right before sending; a rejection aborts the send
+    pub checks: Option<Arc<CheckPipeline>>,
+    /// Executor-side facts for the checks (simulation and gas cost are filled in here)
+    pub context: ExecutionContext,
+}
+
+/// A sent executeArbitrage transaction, kept for confirmation tracking and fee bumping.
+#[derive(Debug, Clone)]
+pub struct SubmittedTx {
+    pub tx_hash: TxHash,
+    /// The transaction as sent (nonce, fees and gas limit filled in)
+    pub tx: TypedTransaction,
+}
+
+// Headroom added on top of the simulated gas estimate
+const GAS_LIMIT_HEADROOM_PCT: u64 = 20;
+
+/// Calls the ArbitrageExecutor contract's executeArbitrage function with `plan`'s arguments.
+/// The call is always simulated against the pending block first; a revert or a profit below
+/// `profit_check` aborts the send, and the gas limit is the simulated estimate plus headroom.
+/// `client` is the signer for the executor wallet leased from the WalletPool.
+pub async fn execute_arbitrage_onchain<M: Middleware + 'static>(
     contract_address: Address,
     abi_path: &str,
-    flashloan_provider: Address,
-    loan_token: Address,
-    loan_amount: U256,
-    routers: Vec<Address>,
-    swap_paths: Vec<Vec<Address>>,
-    amounts_in: Vec<U256>,
-    amounts_out_min: Vec<U256>,
-    private_key: &str,
-    rpc_url: &str,
-) -> Result<TxHash, Box<dyn std::error::Error>> {
-    // Load ABI
-    let abi = std::fs::read_to_string(abi_path)?;
-    let abi: Abi = serde_json::from_str(&abi)?;
-
-    // Setup provider and wallet
-    let provider = Provider::<Http>::try_from(rpc_url)?;
-    let wallet = private_key.parse::<LocalWallet>()?;
-    let client = SignerMiddleware::new(provider, wallet);
-    let client = Arc::new(client);
+    plan: &ArbitragePlan,
+    client: Arc<M>,
+    options: &ExecutionOptions,
+) -> Result<SubmittedTx, ExecuteError> {
+    // Load ABI: a bare ABI array or a Foundry artifact (out/*.sol/*.json) holding it under "abi"
+    let abi = std::fs::read_to_string(abi_path).map_err(|e| ExecuteError::Build(format!("{}: {}", abi_path, e)))?;
+    let abi: serde_json::Value =
+        serde_json::from_str(&abi).map_err(|e| ExecuteError::Build(format!("{}: {}", abi_path, e)))?;
+    let abi: Abi = serde_json::from_value(abi.get("abi").cloned().unwrap_or(abi))
+        .map_err(|e| ExecuteError::Build(format!("{}: {}", abi_path, e)))?;
 
     // Instantiate contract
-    let contract = Contract::new(contract_address, abi, client);
+    let contract = Contract::new(contract_address, abi.clone(), client.clone());
 
     // Call the contract
-    let method = contract
+    let mut method = contract
         .method::<_, ()>(
             "executeArbitrage",
             (
-                flashloan_provider,
-                loan_token,
-                loan_amount,
-                routers,
-                swap_paths,
-                amounts_in,
-                amounts_out_min,
+                plan.flashloan_provider,
+                plan.loan_token,
+                plan.loan_amount,
+                plan.routers.clone(),
+                plan.swap_paths.clone(),
+                plan.amounts_in.clone(),
+                plan.amounts_out_min.clone(),
             ),
-        )?;
-    let pending_tx = method.send().await?;
-    Ok(*pending_tx)
+        )
+        .map_err(|e| ExecuteError::Build(e.to_string()))?
+        .nonce(options.nonce);
+    options.fees.apply(&mut method.tx);
+
+    let simulation = simulate(&*client, &method.tx, Some(&abi), options.profit_check.as_ref()).await?;
+    info!(
+        "[execute_arbitrage] Simulation passed: gas estimate {}, profit {:?}",
+        simulation.gas_estimate, simulation.profit
+    );
+    method.tx.set_gas(simulation.gas_estimate * (100 + GAS_LIMIT_HEADROOM_PCT) / 100);
+    if let Some(checks) = &options.checks {
+        let mut context = options.context.clone();
+        context.simulation = Some(simulation);
+        context.max_gas_cost = method.tx.gas().map(|gas| gas * options.fees.max_price_per_gas());
+        checks.run(&context)?;
+    }
+    if let Some(bundles) = &options.bundles {
+        let (raw_tx, tx_hash) = sign_raw(&*client, &mut method.tx).await?;
+        return match bundles.submit(&*client, raw_tx, tx_hash, None).await? {
+            BundleOutcome::Included { .. } => Ok(SubmittedTx { tx_hash, tx: method.tx }),
+            BundleOutcome::NotIncluded { last_block } => Err(ExecuteError::NotIncluded { tx_hash, last_block }),
+        };
+    }
+    let tx_hash = *method.send().await.map_err(|e| ExecuteError::Send(e.to_string()))?;
+    let mut tx = method.tx;
+    if let (None, Some(sender)) = (tx.from(), client.default_sender()) {
+        tx.set_from(sender);
+    }
+    Ok(SubmittedTx { tx_hash, tx })
 }
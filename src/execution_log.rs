@@ -1,6 +1,37 @@
 use std::sync::{Arc, Mutex};
 use chrono::{DateTime, Utc};
 
+/// Lifecycle of the transaction behind an execution record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// DRY_RUN: nothing was sent
+    Simulated,
+    /// Failed before a transaction was sent
+    Failed,
+    /// Sent, not yet confirmed
+    Pending,
+    /// Mined successfully with the required confirmations
+    Confirmed,
+    /// Mined but reverted
+    Reverted,
+    /// Replaced by a zero-value self-transfer after timing out
+    Cancelled,
+    /// Never mined and no longer tracked
+    Dropped,
+}
+
+/// A balance moved out of an executor wallet (protocol "sweep").
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferRecord {
+    /// Token symbol, or the native coin's ("BNB")
+    pub token: String,
+    /// Exact amount in whole-token units
+    pub amount: String,
+    pub from: String,
+    pub to: String,
+    pub usd: Option<f64>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ExecutionRecord {
     pub timestamp: DateTime<Utc>,
@@ -11,8 +42,13 @@ pub struct ExecutionRecord {
     pub success: bool,
     pub profit: f64,
     pub gas_used: Option<u64>,
+    /// Effective gas cost in native units (BNB), from the receipt
+    pub gas_cost_native: Option<f64>,
     pub tx_hash: Option<String>,
     pub error: Option<String>,
+    pub status: TxStatus,
+    /// Set for sweeps rather than executions
+    pub transfer: Option<TransferRecord>,
 }
 
 #[derive(Clone)]
@@ -20,6 +56,12 @@ pub struct ExecutionLog {
     pub records: Arc<Mutex<Vec<ExecutionRecord>>>,
 }
 
+impl Default for ExecutionLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ExecutionLog {
     pub fn new() -> Self {
         Self { records: Arc::new(Mutex::new(Vec::new())) }
@@ -32,6 +74,17 @@ impl ExecutionLog {
         let records = self.records.lock().unwrap();
         records.iter().rev().take(n).cloned().collect()
     }
+    /// Apply `update` to the most recent record for `tx_hash`. Returns false if there is none.
+    pub fn update_by_tx_hash(&self, tx_hash: &str, update: impl FnOnce(&mut ExecutionRecord)) -> bool {
+        let mut records = self.records.lock().unwrap();
+        match records.iter_mut().rev().find(|r| r.tx_hash.as_deref() == Some(tx_hash)) {
+            Some(record) => {
+                update(record);
+                true
+            }
+            None => false,
+        }
+    }
     pub fn all(&self) -> Vec<ExecutionRecord> {
         let records = self.records.lock().unwrap();
         records.clone()
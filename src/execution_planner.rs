@@ -0,0 +1,581 @@
+// Execution planner: turns a scanned arbitrage opportunity or a liquidation event into a fully
+// populated executeArbitrage call - flashloan provider and token, router addresses from config,
+// token paths, sized amounts and slippage-protected minimum outputs. Leg outputs are quoted
+// on-chain with the routers' getAmountsOut, one leg after the other.
+
+use crate::chain_registry::{ChainConfig, FlashloanAddresses};
+use crate::config::Settings;
+use crate::flashloan::{
+    configured_sources, select_flashloan, utilization_cap, BorrowLimits, FlashloanCallback, FlashloanQuote, FlashloanSource,
+};
+use crate::liquidation_monitor::LiquidationEvent;
+use crate::liquidity_cache::LiquidityCache;
+use crate::matrix2d::Matrix2D;
+use crate::time_sync;
+use crate::token_amount::TokenAmount;
+use crate::token_registry::{TokenCategory, TokenRegistry, TokenRegistryError};
+use ethers::abi::Abi;
+use ethers::prelude::*;
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+static ROUTER_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&[
+        "function getAmountsOut(uint256 amountIn, address[] path) view returns (uint256[] amounts)",
+    ])
+    .expect("ABI parse error")
+});
+
+#[derive(Debug, Error)]
+pub enum PlannerError {
+    #[error("No router configured for DEX {0}")]
+    UnknownDex(String),
+    #[error("No executor_contract deployed on chain {0}")]
+    NoExecutor(u64),
+    #[error("Unknown token {0}")]
+    UnknownToken(String),
+    #[error("Liquidation of {0} does not name its {1} token")]
+    MissingToken(String, &'static str),
+    #[error(transparent)]
+    Token(#[from] TokenRegistryError),
+    #[error("No flashloan provider has liquidity for {0:?}")]
+    NoFlashloanLiquidity(Address),
+    #[error("Cannot price {0:?} to enforce the minimum lender liquidity")]
+    Unpriced(Address),
+    #[error("No route quoted for path {0:?}")]
+    NoQuote(Vec<Address>),
+    #[error("Route returns {returned} for a loan repayment of {loan}")]
+    Unprofitable { loan: U256, returned: U256 },
+}
+
+/// An opportunity as reported by `scan_matrix2d`: buy `asset` on `buy_dex`, sell on `sell_dex`.
+/// Prices are in the quote token (`execution_quote_token`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitrageOpportunity {
+    pub buy_dex: String,
+    pub asset: String,
+    pub sell_dex: String,
+    pub buy_price: f64,
+    pub sell_price: f64,
+    pub profit_pct: f64,
+    /// Matrix (1-based) the opportunity was scanned from, for its liquidity utilization cap
+    pub matrix: Option<usize>,
+}
+
+impl ArbitrageOpportunity {
+    /// From an `AnalysisHub`/`scan_matrix2d` result tuple.
+    pub fn from_scan(opp: &crate::analysis::Opportunity) -> Self {
+        Self {
+            buy_dex: opp.0.clone(),
+            asset: opp.1.clone(),
+            sell_dex: opp.2.clone(),
+            buy_price: opp.3,
+            sell_price: opp.5,
+            profit_pct: opp.6,
+            matrix: None,
+        }
+    }
+}
+
+/// One swap of a route. The router quoting the best output among `routers` is used.
+#[derive(Debug, Clone)]
+pub struct RouteLeg {
+    pub routers: Vec<Address>,
+    pub path: Vec<Address>,
+}
+
+/// Arguments of an executeArbitrage call. All amounts are raw token units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitragePlan {
+    pub flashloan_provider: Address,
+    pub loan_token: Address,
+    pub loan_amount: U256,
+    pub routers: Vec<Address>,
+    pub swap_paths: Vec<Vec<Address>>,
+    /// The first leg spends the loan; each later leg spends the previous leg's minimum output
+    pub amounts_in: Vec<U256>,
+    pub amounts_out_min: Vec<U256>,
+    /// Owed to the flashloan provider on top of the loan
+    pub flashloan_fee: U256,
+    /// Callback the executor must implement to borrow from `flashloan_provider`
+    pub flashloan_callback: FlashloanCallback,
+    /// Quoted output of the last leg minus the loan and the flashloan fee
+    pub expected_profit: U256,
+}
+
+impl ArbitragePlan {
+    /// Profit if every leg fills at exactly its minimum output.
+    pub fn min_profit(&self) -> U256 {
+        self.amounts_out_min
+            .last()
+            .copied()
+            .unwrap_or_default()
+            .saturating_sub(self.loan_amount + self.flashloan_fee)
+    }
+}
+
+/// `amount` less `max_slippage_pct` percent (0.5 = 0.5%), rounded down to whole basis points.
+pub fn apply_slippage(amount: U256, max_slippage_pct: f64) -> U256 {
+    let bps = (max_slippage_pct.clamp(0.0, 100.0) * 100.0).round() as u64;
+    amount * U256::from(10_000 - bps) / U256::from(10_000)
+}
+
+async fn quote<M: Middleware + 'static>(client: Arc<M>, router: Address, amount_in: U256, path: &[Address]) -> Option<U256> {
+    let contract = Contract::new(router, ROUTER_ABI.clone(), client);
+    let call = contract.method::<_, Vec<U256>>("getAmountsOut", (amount_in, path.to_vec())).ok()?;
+    match call.call().await {
+        Ok(amounts) => amounts.last().copied(),
+        Err(e) => {
+            debug!("[ExecutionPlanner] getAmountsOut on {:?} failed: {}", router, e);
+            None
+        }
+    }
+}
+
+pub struct ExecutionPlanner {
+    settings: Arc<Settings>,
+    token_registry: Arc<TokenRegistry>,
+    // Chain the planned transactions run on (`execution_chain`)
+    chain_id: u64,
+    // Symbol of the chain's wrapped native token
+    wrapped_native: String,
+    // The chain's ArbitrageExecutor deployment
+    executor: Option<Address>,
+    // Router address per lower-cased DEX name
+    routers: HashMap<String, Address>,
+    // The chain's flashloan lenders
+    flashloan: FlashloanAddresses,
+    // Background-refreshed flashloan quotes; live quotes if unset
+    liquidity: Option<Arc<LiquidityCache>>,
+    // DEX price matrix; its wrapped native price values the native coin (gas)
+    matrix: Option<Arc<std::sync::Mutex<Matrix2D>>>,
+}
+
+impl ExecutionPlanner {
+    pub fn new(settings: Arc<Settings>, token_registry: Arc<TokenRegistry>, chain: &ChainConfig) -> Self {
+        let mut routers = HashMap::new();
+        for (dex, addr) in chain.router_addresses(&settings) {
+            match addr.parse::<Address>() {
+                Ok(address) => {
+                    routers.insert(dex.to_lowercase(), address);
+                }
+                Err(e) => warn!("[ExecutionPlanner] Invalid router address for {}: {} ({})", dex, addr, e),
+            }
+        }
+        let flashloan = chain.flashloan_addresses(&settings);
+        Self {
+            settings,
+            token_registry,
+            chain_id: chain.chain_id,
+            wrapped_native: chain.wrapped_native_token.clone(),
+            executor: chain.executor_address(),
+            routers,
+            flashloan,
+            liquidity: None,
+            matrix: None,
+        }
+    }
+
+    pub fn with_liquidity_cache(mut self, cache: Arc<LiquidityCache>) -> Self {
+        self.liquidity = Some(cache);
+        self
+    }
+
+    /// Value the native coin at `matrix`'s wrapped native price
+    pub fn with_matrix(mut self, matrix: Arc<std::sync::Mutex<Matrix2D>>) -> Self {
+        self.matrix = Some(matrix);
+        self
+    }
+
+    pub fn token_registry(&self) -> &Arc<TokenRegistry> {
+        &self.token_registry
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// The chain's wrapped native token, if registered
+    pub fn wrapped_native(&self) -> Option<Address> {
+        self.token_registry.resolve_symbol(self.chain_id, &self.wrapped_native)
+    }
+
+    /// The ArbitrageExecutor contract on the chain
+    pub fn executor(&self) -> Result<Address, PlannerError> {
+        self.executor.ok_or(PlannerError::NoExecutor(self.chain_id))
+    }
+
+    pub fn router(&self, dex: &str) -> Result<Address, PlannerError> {
+        self.routers.get(&dex.to_lowercase()).copied().ok_or_else(|| PlannerError::UnknownDex(dex.to_string()))
+    }
+
+    // Routers in `liquidity_source_priority_order`, then the remaining `dexes`
+    fn routers_by_priority(&self) -> Vec<Address> {
+        let mut routers = Vec::new();
+        for dex in self.settings.liquidity_source_priority_order.iter().chain(self.settings.dexes.iter()) {
+            if let Ok(router) = self.router(dex)
+                && !routers.contains(&router)
+            {
+                routers.push(router);
+            }
+        }
+        routers
+    }
+
+    fn token(&self, symbol: &str) -> Result<Address, PlannerError> {
+        self.token_registry
+            .resolve_symbol(self.chain_id, symbol)
+            .ok_or_else(|| PlannerError::UnknownToken(symbol.to_string()))
+    }
+
+    /// Price of one whole `token` in the quote token, from the first router in priority order
+    /// that quotes it.
+    pub async fn price<M: Middleware + 'static>(&self, client: Arc<M>, token: Address) -> Option<TokenAmount> {
+        let quote_token = self.token(&self.settings.execution_quote_token).ok()?;
+        let one = self.token_registry.parse_amount(self.chain_id, token, 1.0).ok()?;
+        let path = [token, quote_token];
+        for router in self.routers_by_priority() {
+            if let Some(out) = quote(client.clone(), router, one.raw(), &path).await {
+                return self.token_registry.amount(self.chain_id, quote_token, out).ok();
+            }
+        }
+        None
+    }
+
+    /// USD price of one `token`: its quote-token price, with stablecoins taken at 1.0.
+    pub async fn price_usd<M: Middleware + 'static>(&self, client: Arc<M>, token: Address) -> Option<f64> {
+        if self.token_registry.category(self.chain_id, token) == TokenCategory::Stable {
+            return Some(1.0);
+        }
+        self.price(client, token).await.map(|price| price.to_f64())
+    }
+
+    /// USD price of the native coin: the matrix's mark price of the wrapped native token, if
+    /// updated within `ledger_mark_price_max_age_ms`.
+    pub fn native_price_usd(&self) -> Option<f64> {
+        let matrix = self.matrix.as_ref()?.lock().unwrap();
+        matrix.mark_price(&self.wrapped_native, self.settings.ledger_mark_price_max_age_ms, time_sync::now_millis())
+    }
+
+    /// The matrix's last wrapped native price, however old; for valuing gas already spent when
+    /// no fresh price is available.
+    pub fn last_native_price_usd(&self) -> Option<f64> {
+        self.matrix.as_ref()?.lock().unwrap().last_price(&self.wrapped_native)
+    }
+
+    /// Configured flashloan sources, with `flashloan_pair_tokens` resolved for pool lookups.
+    pub fn flashloan_sources<M: Middleware + 'static>(&self) -> Vec<Box<dyn FlashloanSource<M>>> {
+        let pair_tokens: Vec<Address> = self
+            .settings
+            .flashloan_pair_tokens
+            .iter()
+            .filter_map(|symbol| self.token_registry.resolve_symbol(self.chain_id, symbol))
+            .collect();
+        configured_sources::<M>(&self.settings, &self.flashloan, &pair_tokens)
+    }
+
+    /// Borrow limits for `token` traded by `matrix`: the stricter of its category's and the
+    /// matrix's utilization caps, and no lender holding less than
+    /// `liquidity_threshold_minimum_usd` worth of it. Pricing a non-stable token takes a quote.
+    pub async fn borrow_limits<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        token: Address,
+        matrix: Option<usize>,
+    ) -> Result<BorrowLimits, PlannerError> {
+        let category = self.token_registry.category(self.chain_id, token);
+        let utilization = utilization_cap(&self.settings, category, matrix);
+        let threshold_usd = self.settings.liquidity_threshold_minimum_usd;
+        if threshold_usd <= 0.0 {
+            return Ok(BorrowLimits::utilization(utilization));
+        }
+        let price = self.price_usd(client, token).await.filter(|p| *p > 0.0).ok_or(PlannerError::Unpriced(token))?;
+        let min_available = self.token_registry.to_raw(self.chain_id, token, threshold_usd / price)?;
+        Ok(BorrowLimits { utilization, min_available })
+    }
+
+    // Cheapest flashloan of `wanted` of `token` within its borrow limits; capped at what the
+    // deepest eligible source can lend if no source covers it. Served from the liquidity cache
+    // when it has the token, else quoted live (filling the cache).
+    async fn flashloan<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        token: Address,
+        wanted: U256,
+        matrix: Option<usize>,
+    ) -> Result<FlashloanQuote, PlannerError> {
+        let limits = self.borrow_limits(client.clone(), token, matrix).await?;
+        let cached = self.liquidity.as_ref().and_then(|cache| cache.get(token, wanted, &limits));
+        let loan = match (cached, &self.liquidity) {
+            (Some(loan), _) => Some(loan),
+            (None, Some(cache)) => {
+                cache.refresh_token(client, &self.flashloan_sources::<M>(), token, wanted).await;
+                cache.get(token, wanted, &limits)
+            }
+            (None, None) => select_flashloan(&self.flashloan_sources::<M>(), client, token, wanted, &limits).await,
+        };
+        let loan = loan.ok_or(PlannerError::NoFlashloanLiquidity(token))?;
+        if loan.amount < wanted {
+            debug!("[ExecutionPlanner] Loan capped at {} of {} by {}", loan.amount, wanted, loan.source);
+        }
+        Ok(loan)
+    }
+
+    /// Plan a `trade_size` (in quote-token units) round trip: flash-loan the quote token, buy
+    /// the asset on `buy_dex` and sell it back on `sell_dex`.
+    pub async fn plan_opportunity<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        opp: &ArbitrageOpportunity,
+        trade_size: f64,
+    ) -> Result<ArbitragePlan, PlannerError> {
+        let quote_token = self.token(&self.settings.execution_quote_token)?;
+        let asset = self.token(&opp.asset)?;
+        let legs = [
+            RouteLeg { routers: vec![self.router(&opp.buy_dex)?], path: vec![quote_token, asset] },
+            RouteLeg { routers: vec![self.router(&opp.sell_dex)?], path: vec![asset, quote_token] },
+        ];
+        let wanted = self.token_registry.to_raw(self.chain_id, quote_token, trade_size)?;
+        let loan = self.flashloan(client.clone(), quote_token, wanted, opp.matrix).await?;
+        self.plan_route(client, &loan, &legs).await
+    }
+
+    /// Plan a liquidation: flash-loan the debt, swap it into the collateral token and back on
+    /// whichever routers quote best. The executor contract only performs swaps, so the plan is
+    /// rejected unless the round trip repays the loan.
+    pub async fn plan_liquidation<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        event: &LiquidationEvent,
+    ) -> Result<ArbitragePlan, PlannerError> {
+        let debt_token = event.debt_token.ok_or_else(|| PlannerError::MissingToken(event.account.clone(), "debt"))?;
+        let collateral_token = event
+            .collateral_token
+            .ok_or_else(|| PlannerError::MissingToken(event.account.clone(), "collateral"))?;
+        let routers = self.routers_by_priority();
+        let legs = [
+            RouteLeg { routers: routers.clone(), path: vec![debt_token, collateral_token] },
+            RouteLeg { routers, path: vec![collateral_token, debt_token] },
+        ];
+        let wanted = self.token_registry.to_raw(self.chain_id, debt_token, event.debt)?;
+        let loan = self.flashloan(client.clone(), debt_token, wanted, None).await?;
+        self.plan_route(client, &loan, &legs).await
+    }
+
+    /// Quote `legs` in order, starting from the borrowed amount, and build the call. Each leg's
+    /// minimum output is its quote less `max_slippage`.
+    pub async fn plan_route<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        loan: &FlashloanQuote,
+        legs: &[RouteLeg],
+    ) -> Result<ArbitragePlan, PlannerError> {
+        let (loan_token, loan_amount) = (loan.token, loan.amount);
+        let mut plan = ArbitragePlan {
+            flashloan_provider: loan.lender,
+            loan_token,
+            loan_amount,
+            routers: Vec::new(),
+            swap_paths: Vec::new(),
+            amounts_in: Vec::new(),
+            amounts_out_min: Vec::new(),
+            flashloan_fee: loan.fee,
+            flashloan_callback: loan.callback,
+            expected_profit: U256::zero(),
+        };
+        let mut amount_in = loan_amount;
+        let mut quoted = loan_amount;
+        for leg in legs {
+            let mut best: Option<(Address, U256)> = None;
+            for router in &leg.routers {
+                if let Some(out) = quote(client.clone(), *router, amount_in, &leg.path).await
+                    && best.is_none_or(|(_, best_out)| out > best_out)
+                {
+                    best = Some((*router, out));
+                }
+            }
+            let (router, out) = best.ok_or_else(|| PlannerError::NoQuote(leg.path.clone()))?;
+            let min_out = apply_slippage(out, self.settings.max_slippage);
+            plan.routers.push(router);
+            plan.swap_paths.push(leg.path.clone());
+            plan.amounts_in.push(amount_in);
+            plan.amounts_out_min.push(min_out);
+            quoted = out;
+            amount_in = min_out;
+        }
+        let repayment = loan_amount + loan.fee;
+        if quoted <= repayment {
+            return Err(PlannerError::Unprofitable { loan: repayment, returned: quoted });
+        }
+        plan.expected_profit = quoted - repayment;
+        debug!("[ExecutionPlanner] Planned {} legs, loan {} of {:?}, expected profit {}", legs.len(), loan_amount, loan_token, plan.expected_profit);
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_registry::BSC_MAINNET_CHAIN_ID;
+    use ethers::abi::Token;
+
+    fn bsc() -> ChainConfig {
+        ChainConfig { chain_id: BSC_MAINNET_CHAIN_ID, name: "BSC".to_string(), ..Default::default() }
+    }
+
+    fn amounts_out(amounts: &[u64]) -> Bytes {
+        let tokens = amounts.iter().map(|a| Token::Uint(U256::from(*a))).collect();
+        Bytes::from(ethers::abi::encode(&[Token::Array(tokens)]))
+    }
+
+    #[test]
+    fn test_apply_slippage() {
+        assert_eq!(apply_slippage(U256::from(10_000u64), 0.5), U256::from(9_950u64));
+        assert_eq!(apply_slippage(U256::from(10_000u64), 0.0), U256::from(10_000u64));
+        assert_eq!(apply_slippage(U256::from(999u64), 1.0), U256::from(989u64));
+    }
+
+    #[tokio::test]
+    async fn test_plan_route_chains_minimum_outputs() {
+        let settings = Settings {
+            max_slippage: 0.5,
+            router_pancakeswap: format!("{:?}", Address::from_low_u64_be(1)),
+            router_biswap: format!("{:?}", Address::from_low_u64_be(2)),
+            ..Default::default()
+        };
+        let planner = ExecutionPlanner::new(Arc::new(settings), Arc::new(TokenRegistry::new()), &bsc());
+        let (quote_token, asset) = (Address::from_low_u64_be(10), Address::from_low_u64_be(11));
+        let legs = [
+            RouteLeg { routers: vec![planner.router("PancakeSwap").unwrap()], path: vec![quote_token, asset] },
+            RouteLeg { routers: vec![planner.router("biswap").unwrap()], path: vec![asset, quote_token] },
+        ];
+        assert!(matches!(planner.router("Uniswap"), Err(PlannerError::UnknownDex(_))));
+
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        // Responses are popped LIFO: second leg's quote first
+        mock.push::<Bytes, _>(amounts_out(&[19_900, 10_100])).unwrap();
+        mock.push::<Bytes, _>(amounts_out(&[10_000, 20_000])).unwrap();
+        // Aave-style 9 bps premium: 9 owed on a 10_000 loan
+        let loan = FlashloanQuote::new(
+            "Aave V3",
+            Address::from_low_u64_be(99),
+            quote_token,
+            U256::from(10_000u64),
+            U256::from(1_000_000u64),
+            (U256::from(9u64), U256::from(10_000u64)),
+            FlashloanCallback::AaveExecuteOperation,
+        );
+        let plan = planner.plan_route(client.clone(), &loan, &legs).await.unwrap();
+        assert_eq!(plan.routers, vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)]);
+        assert_eq!(plan.amounts_in, vec![U256::from(10_000u64), U256::from(19_900u64)]);
+        assert_eq!(plan.amounts_out_min, vec![U256::from(19_900u64), U256::from(10_049u64)]);
+        assert_eq!(plan.flashloan_provider, Address::from_low_u64_be(99));
+        assert_eq!(plan.flashloan_fee, U256::from(9u64));
+        assert_eq!(plan.expected_profit, U256::from(91u64));
+        assert_eq!(plan.min_profit(), U256::from(40u64));
+
+        mock.push::<Bytes, _>(amounts_out(&[19_900, 9_990])).unwrap();
+        mock.push::<Bytes, _>(amounts_out(&[10_000, 20_000])).unwrap();
+        let result = planner.plan_route(client, &loan, &legs).await;
+        assert!(matches!(result, Err(PlannerError::Unprofitable { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_plan_opportunity_buys_and_sells_on_its_dexes() {
+        let settings = Settings {
+            max_slippage: 0.5,
+            execution_quote_token: "BUSD".to_string(),
+            router_pancakeswap: format!("{:?}", Address::from_low_u64_be(1)),
+            router_biswap: format!("{:?}", Address::from_low_u64_be(2)),
+            max_liquidity_utilization_stable: 100.0,
+            ..Default::default()
+        };
+        let registry = Arc::new(TokenRegistry::new());
+        let (busd, cake) = (Address::from_low_u64_be(0xb0), Address::from_low_u64_be(0xca));
+        registry.register(BSC_MAINNET_CHAIN_ID, "BUSD", busd, Some(6));
+        registry.register(BSC_MAINNET_CHAIN_ID, "CAKE", cake, Some(18));
+        let cache = Arc::new(LiquidityCache::new(std::time::Duration::from_secs(60), std::time::Duration::from_secs(60)));
+        let lender = Address::from_low_u64_be(99);
+        cache.insert(FlashloanQuote::new(
+            "Aave V3",
+            lender,
+            busd,
+            U256::zero(),
+            U256::from(1_000_000_000_000u64),
+            (U256::from(9u64), U256::from(10_000u64)),
+            FlashloanCallback::AaveExecuteOperation,
+        ));
+        let planner = ExecutionPlanner::new(Arc::new(settings), registry, &bsc()).with_liquidity_cache(cache);
+        let opp = ArbitrageOpportunity {
+            buy_dex: "Biswap".to_string(),
+            asset: "CAKE".to_string(),
+            sell_dex: "PancakeSwap".to_string(),
+            buy_price: 2.0,
+            sell_price: 2.02,
+            profit_pct: 1.0,
+            matrix: None,
+        };
+
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        // Responses are popped LIFO: the sell quote first
+        mock.push::<Bytes, _>(amounts_out(&[19_900_000_000, 10_100_000])).unwrap();
+        mock.push::<Bytes, _>(amounts_out(&[10_000_000, 20_000_000_000])).unwrap();
+        let plan = planner.plan_opportunity(client.clone(), &opp, 10.0).await.unwrap();
+        // Buy CAKE with the BUSD loan on Biswap, sell it back on PancakeSwap
+        assert_eq!((plan.flashloan_provider, plan.loan_token, plan.loan_amount), (lender, busd, U256::from(10_000_000u64)));
+        assert_eq!(plan.routers, vec![Address::from_low_u64_be(2), Address::from_low_u64_be(1)]);
+        assert_eq!(plan.swap_paths, vec![vec![busd, cake], vec![cake, busd]]);
+        // Each minimum output is its quote less 0.5%, and the sell spends the buy's minimum
+        assert_eq!(plan.amounts_in, vec![U256::from(10_000_000u64), U256::from(19_900_000_000u64)]);
+        assert_eq!(plan.amounts_out_min, vec![U256::from(19_900_000_000u64), U256::from(10_049_500u64)]);
+        assert_eq!(plan.flashloan_fee, U256::from(9_000u64));
+        assert_eq!(plan.expected_profit, U256::from(91_000u64));
+        assert_eq!(plan.min_profit(), U256::from(40_500u64));
+
+        let unknown = ArbitrageOpportunity { sell_dex: "MDEX".to_string(), ..opp };
+        assert!(matches!(planner.plan_opportunity(client, &unknown, 10.0).await, Err(PlannerError::UnknownDex(_))));
+    }
+
+    #[tokio::test]
+    async fn test_borrow_limits_for_stable_token() {
+        let settings = Settings {
+            liquidity_threshold_minimum_usd: 10_000.0,
+            max_liquidity_utilization_stable: 100.0,
+            matrix1_max_liquidity_utilization: 60.0,
+            ..Default::default()
+        };
+        let registry = Arc::new(TokenRegistry::new());
+        let busd = Address::from_low_u64_be(0xb0);
+        registry.register(BSC_MAINNET_CHAIN_ID, "BUSD", busd, Some(18));
+        let planner = ExecutionPlanner::new(Arc::new(settings), registry, &bsc());
+        // Stablecoins are priced at 1.0 without a quote
+        let (provider, _mock) = Provider::mocked();
+        let limits = planner.borrow_limits(Arc::new(provider), busd, Some(1)).await.unwrap();
+        assert_eq!(limits.utilization, 0.6);
+        assert_eq!(limits.min_available, U256::from(10_000u64) * U256::exp10(18));
+    }
+
+    #[test]
+    fn test_native_price_from_matrix() {
+        let settings = Settings { ledger_mark_price_max_age_ms: 60_000, ..Default::default() };
+        let chain = ChainConfig { wrapped_native_token: "WBNB".to_string(), ..bsc() };
+        let planner = ExecutionPlanner::new(Arc::new(settings), Arc::new(TokenRegistry::new()), &chain);
+        assert_eq!(planner.native_price_usd(), None);
+
+        let matrix = Arc::new(std::sync::Mutex::new(Matrix2D::new(vec!["PancakeSwap".into()], vec!["WBNB".into()])));
+        let planner = planner.with_matrix(matrix.clone());
+        // Never updated: no fresh price
+        assert_eq!(planner.native_price_usd(), None);
+        matrix.lock().unwrap().update_price("PancakeSwap", "WBNB", 600.0);
+        assert_eq!(planner.native_price_usd(), Some(600.0));
+        // A stale price is still the last known one
+        matrix.lock().unwrap().prices[0][0].timestamp = 1;
+        assert_eq!(planner.native_price_usd(), None);
+        assert_eq!(planner.last_native_price_usd(), Some(600.0));
+    }
+}
@@ -0,0 +1,149 @@
+// Failover JSON-RPC transport: picks an HTTP endpoint per request via ProviderRotation and,
+// on transport errors or rate-limit responses, marks it failed and retries on the next one.
+// With a ProviderHealth attached, selection prefers the best-scoring endpoint and every request
+// feeds its latency and outcome back into the health statistics.
+
+use crate::provider_health::ProviderHealth;
+use crate::providers_round_robin::SharedRotation;
+use async_trait::async_trait;
+use ethers::providers::{Http, HttpClientError, JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use url::Url;
+
+/// Default cooldown applied to an endpoint after a failed or rate-limited request.
+pub const DEFAULT_FAILURE_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum FailoverError {
+    #[error(transparent)]
+    Client(#[from] HttpClientError),
+    #[error("All RPC providers failed or are cooling down (last error: {0})")]
+    Exhausted(String),
+}
+
+impl From<FailoverError> for ProviderError {
+    fn from(src: FailoverError) -> Self {
+        match src {
+            FailoverError::Client(e) => e.into(),
+            e @ FailoverError::Exhausted(_) => ProviderError::CustomError(e.to_string()),
+        }
+    }
+}
+
+impl RpcError for FailoverError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            FailoverError::Client(e) => e.as_error_response(),
+            FailoverError::Exhausted(_) => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            FailoverError::Client(e) => e.as_serde_error(),
+            FailoverError::Exhausted(_) => None,
+        }
+    }
+}
+
+/// True for JSON-RPC errors that signal throttling or quota exhaustion rather than a
+/// problem with the request itself.
+pub fn is_rate_limit_error(err: &JsonRpcError) -> bool {
+    let message = err.message.to_lowercase();
+    err.code == 429
+        || err.code == -32005
+        || message.contains("rate limit")
+        || message.contains("too many requests")
+        || message.contains("capacity exceeded")
+        || message.contains("limit exceeded")
+}
+
+fn should_failover(err: &HttpClientError) -> bool {
+    match err {
+        // Connection failures, timeouts, non-JSON bodies (HTML error pages, plain 429s)
+        HttpClientError::ReqwestError(_) | HttpClientError::SerdeJson { .. } => true,
+        // Genuine JSON-RPC errors (e.g. reverts) are returned as-is
+        HttpClientError::JsonRpcError(e) => is_rate_limit_error(e),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FailoverHttp {
+    rotation: SharedRotation,
+    clients: HashMap<String, Http>,
+    failure_cooldown: Duration,
+    health: Option<Arc<ProviderHealth>>,
+}
+
+impl FailoverHttp {
+    /// Build one HTTP client per endpoint in `rotation`.
+    pub fn new(rotation: SharedRotation, failure_cooldown: Duration) -> Result<Self, url::ParseError> {
+        let mut clients = HashMap::new();
+        {
+            let rotation = rotation.lock().unwrap();
+            for entry in rotation.entries() {
+                clients.insert(entry.name.clone(), Http::new(Url::parse(&entry.url)?));
+            }
+        }
+        Ok(Self { rotation, clients, failure_cooldown, health: None })
+    }
+
+    /// Select endpoints by health score and record real traffic into `health`.
+    pub fn with_health(mut self, health: Arc<ProviderHealth>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    pub fn rotation(&self) -> SharedRotation {
+        self.rotation.clone()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverHttp {
+    type Error = FailoverError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, FailoverError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut last_error: Option<String> = None;
+        for _ in 0..self.clients.len() {
+            let next = {
+                let mut rotation = self.rotation.lock().unwrap();
+                match &self.health {
+                    Some(health) => rotation.next_best_provider(|entry| health.score(&entry.name)),
+                    None => rotation.next_provider(),
+                }
+                .map(|entry| entry.name.clone())
+            };
+            let Some(name) = next else { break };
+            let Some(client) = self.clients.get(&name) else { continue };
+            let started = Instant::now();
+            let result = client.request(method, &params).await;
+            if let Some(health) = &self.health {
+                match &result {
+                    Err(e) if should_failover(e) => health.record_failure(&name),
+                    _ => health.record_success(&name, started.elapsed()),
+                }
+            }
+            match result {
+                Ok(result) => return Ok(result),
+                Err(e) if should_failover(&e) => {
+                    warn!("[FailoverHttp] {} failed for {}: {}; trying next provider", name, method, e);
+                    self.rotation.lock().unwrap().mark_provider_failure(&name, self.failure_cooldown);
+                    last_error = Some(format!("{}: {}", name, e));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(FailoverError::Exhausted(last_error.unwrap_or_else(|| "no provider available".to_string())))
+    }
+}
@@ -1,8 +1,15 @@
+use crate::chain_registry::FlashloanAddresses;
 use crate::config::Settings;
-use ethers::abi::Abi;
+use crate::token_amount::raw_fraction;
+use crate::token_registry::TokenCategory;
+use async_trait::async_trait;
+use ethers::abi::{Abi, Token};
 use ethers::middleware::Middleware;
 use ethers::prelude::*;
-use log::{error, warn};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::id;
+use log::{debug, error, warn};
+use futures::future::join_all;
 use once_cell::sync::Lazy;
 use serde_json;
 use std::sync::Arc;
@@ -40,6 +47,33 @@ impl FlashloanProvider {
     }
 }
 
+/// Flashloan providers from `flash_loan_providers`. Entries are "name:address", a bare
+/// address, or one of the names with an address setting (Aave, dYdX, Balancer).
+pub fn configured_providers(settings: &Settings) -> Vec<FlashloanProvider> {
+    let mut providers = Vec::new();
+    for entry in &settings.flash_loan_providers {
+        let entry = entry.trim();
+        if let Some(provider) = FlashloanProvider::from_entry(entry) {
+            providers.push(provider);
+            continue;
+        }
+        let address = match entry.to_lowercase().as_str() {
+            "aave" => &settings.aave_pool_address_provider,
+            "dydx" => &settings.dydx_solo_margin_address,
+            "balancer" => &settings.balancer_vault_address,
+            _ => {
+                warn!("No address configured for flashloan provider {}", entry);
+                continue;
+            }
+        };
+        match FlashloanProvider::from_config(entry, address) {
+            Some(provider) => providers.push(provider),
+            None => warn!("Invalid address for flashloan provider {}: {}", entry, address),
+        }
+    }
+    providers
+}
+
 /// Query the available liquidity for a given asset from a flashloan provider contract.
 pub async fn query_liquidity<M: Middleware + 'static>(
     provider: &FlashloanProvider,
@@ -71,45 +105,401 @@ pub async fn query_liquidity<M: Middleware + 'static>(
     }
 }
 
-/// Pure helper: choose best flashloan provider based on available liquidity and usage percentage.
-pub fn choose_best_provider(
-    liquidities: &[(Address, U256)],
-    usage_percent: f64,
-) -> Option<(Address, U256)> {
-    if liquidities.is_empty() {
-        warn!("No liquidity data provided to choose_best_provider");
-        return None;
+/// The callback a borrower must implement to receive a flashloan from a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashloanCallback {
+    AaveExecuteOperation,
+    BalancerReceiveFlashLoan,
+    UniswapV3Flash,
+    PancakeV3Flash,
+    /// V2 flash swap; repaid to the pair in either of its tokens
+    UniswapV2Call,
+    PancakeCall,
+}
+
+impl FlashloanCallback {
+    /// Signature of the function the lender calls on the borrower.
+    pub fn signature(&self) -> &'static str {
+        match self {
+            Self::AaveExecuteOperation => "executeOperation(address,uint256,uint256,address,bytes)",
+            Self::BalancerReceiveFlashLoan => "receiveFlashLoan(address[],uint256[],uint256[],bytes)",
+            Self::UniswapV3Flash => "uniswapV3FlashCallback(uint256,uint256,bytes)",
+            Self::PancakeV3Flash => "pancakeV3FlashCallback(uint256,uint256,bytes)",
+            Self::UniswapV2Call => "uniswapV2Call(address,uint256,uint256,bytes)",
+            Self::PancakeCall => "pancakeCall(address,uint256,uint256,bytes)",
+        }
+    }
+}
+
+/// What a source can lend of one token, and what borrowing `amount` of it costs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlashloanQuote {
+    pub source: String,
+    /// Contract to request the loan from (Aave pool, Balancer vault, V3 pool, V2 pair)
+    pub lender: Address,
+    pub token: Address,
+    pub amount: U256,
+    /// Total amount the lender holds of `token`
+    pub available: U256,
+    /// Fee owed on top of `amount`
+    pub fee: U256,
+    // Fee as a fraction of the borrowed amount
+    fee_numerator: U256,
+    fee_denominator: U256,
+    pub callback: FlashloanCallback,
+}
+
+impl FlashloanQuote {
+    /// The fee is `amount × fee_numerator / fee_denominator`, rounded up.
+    pub fn new(
+        source: &str,
+        lender: Address,
+        token: Address,
+        amount: U256,
+        available: U256,
+        (fee_numerator, fee_denominator): (U256, U256),
+        callback: FlashloanCallback,
+    ) -> Self {
+        let mut quote = Self {
+            source: source.to_string(),
+            lender,
+            token,
+            amount,
+            available,
+            fee: U256::zero(),
+            fee_numerator,
+            fee_denominator,
+            callback,
+        };
+        quote.fee = quote.fee_for(amount);
+        quote
+    }
+
+    /// Fee for borrowing `amount`, rounded up.
+    pub fn fee_for(&self, amount: U256) -> U256 {
+        if self.fee_numerator.is_zero() || self.fee_denominator.is_zero() {
+            return U256::zero();
+        }
+        // Taken in 512 bits so large amounts can't overflow; an unrepayable fee saturates
+        let denominator = U512::from(self.fee_denominator);
+        let fee = (amount.full_mul(self.fee_numerator) + denominator - 1) / denominator;
+        U256::try_from(fee).unwrap_or(U256::MAX)
+    }
+
+    /// The same quote for borrowing `amount` instead.
+    pub fn with_amount(mut self, amount: U256) -> Self {
+        self.amount = amount;
+        self.fee = self.fee_for(amount);
+        self
+    }
+}
+
+/// A protocol that lends tokens for the duration of one transaction.
+#[async_trait]
+pub trait FlashloanSource<M: Middleware + 'static>: Send + Sync {
+    fn name(&self) -> &str;
+    /// Quote borrowing `amount` of `token`; None if the source cannot lend it at all.
+    async fn quote(&self, client: Arc<M>, token: Address, amount: U256) -> Option<FlashloanQuote>;
+}
+
+// eth_call `signature` on `to` and return the raw result
+async fn call_raw<M: Middleware>(client: &M, to: Address, signature: &str, args: &[Token]) -> Option<Bytes> {
+    let mut data = id(signature).to_vec();
+    data.extend(ethers::abi::encode(args));
+    let tx: TypedTransaction = TransactionRequest::new().to(to).data(data).into();
+    match client.call(&tx, None).await {
+        Ok(result) => Some(result),
+        Err(e) => {
+            debug!("[Flashloan] {} on {:?} failed: {}", signature, to, e);
+            None
+        }
+    }
+}
+
+fn word(data: &[u8], index: usize) -> Option<U256> {
+    data.get(index * 32..(index + 1) * 32).map(U256::from_big_endian)
+}
+
+fn word_address(data: &[u8], index: usize) -> Option<Address> {
+    data.get(index * 32 + 12..(index + 1) * 32).map(Address::from_slice)
+}
+
+async fn call_uint<M: Middleware>(client: &M, to: Address, signature: &str, args: &[Token]) -> Option<U256> {
+    word(&call_raw(client, to, signature, args).await?, 0)
+}
+
+/// Zero addresses (unlisted reserve, missing pool) count as None.
+async fn call_address<M: Middleware>(client: &M, to: Address, signature: &str, args: &[Token]) -> Option<Address> {
+    word_address(&call_raw(client, to, signature, args).await?, 0).filter(|a| !a.is_zero())
+}
+
+/// ERC20 `balanceOf(holder)`; None if the call fails.
+pub async fn balance_of<M: Middleware>(client: &M, token: Address, holder: Address) -> Option<U256> {
+    call_uint(client, token, "balanceOf(address)", &[Token::Address(holder)]).await
+}
+
+/// Aave V3 `flashLoanSimple`: lends the reserve's idle liquidity (held by its aToken) for
+/// `FLASHLOAN_PREMIUM_TOTAL` basis points.
+#[derive(Debug, Clone)]
+pub struct AaveV3Source {
+    pub addresses_provider: Address,
+}
+
+#[async_trait]
+impl<M: Middleware + 'static> FlashloanSource<M> for AaveV3Source {
+    fn name(&self) -> &str {
+        "Aave V3"
+    }
+
+    async fn quote(&self, client: Arc<M>, token: Address, amount: U256) -> Option<FlashloanQuote> {
+        let pool = call_address(&*client, self.addresses_provider, "getPool()", &[]).await?;
+        // ReserveData is a static tuple; aTokenAddress is its ninth field
+        let reserve = call_raw(&*client, pool, "getReserveData(address)", &[Token::Address(token)]).await?;
+        let a_token = word_address(&reserve, 8).filter(|a| !a.is_zero())?;
+        let available = balance_of(&*client, token, a_token).await?;
+        let premium_bps = call_uint(&*client, pool, "FLASHLOAN_PREMIUM_TOTAL()", &[]).await?;
+        let fee = (premium_bps, U256::from(10_000u64));
+        Some(FlashloanQuote::new(FlashloanSource::<M>::name(self), pool, token, amount, available, fee, FlashloanCallback::AaveExecuteOperation))
+    }
+}
+
+/// Balancer Vault `flashLoan`: lends the vault's whole balance for the protocol fee
+/// collector's flash loan fee percentage (18 decimals).
+#[derive(Debug, Clone)]
+pub struct BalancerSource {
+    pub vault: Address,
+}
+
+#[async_trait]
+impl<M: Middleware + 'static> FlashloanSource<M> for BalancerSource {
+    fn name(&self) -> &str {
+        "Balancer"
+    }
+
+    async fn quote(&self, client: Arc<M>, token: Address, amount: U256) -> Option<FlashloanQuote> {
+        let available = balance_of(&*client, token, self.vault).await?;
+        let collector = call_address(&*client, self.vault, "getProtocolFeesCollector()", &[]).await?;
+        let fee_percentage = call_uint(&*client, collector, "getFlashLoanFeePercentage()", &[]).await?;
+        let fee = (fee_percentage, U256::exp10(18));
+        Some(FlashloanQuote::new(FlashloanSource::<M>::name(self), self.vault, token, amount, available, fee, FlashloanCallback::BalancerReceiveFlashLoan))
+    }
+}
+
+/// Uniswap V3-style pool `flash`: lends a pool's balance for the pool's swap fee. Pools of
+/// `token` against each pair token are looked up on the factory, one per fee tier, and the
+/// cheapest pool that covers the amount is used (else the deepest).
+#[derive(Debug, Clone)]
+pub struct V3PoolSource {
+    pub name: String,
+    pub factory: Address,
+    /// Fee tiers in hundredths of a basis point
+    pub fee_tiers: Vec<u32>,
+    pub pair_tokens: Vec<Address>,
+    pub callback: FlashloanCallback,
+}
+
+#[async_trait]
+impl<M: Middleware + 'static> FlashloanSource<M> for V3PoolSource {
+    fn name(&self) -> &str {
+        &self.name
     }
-    let mut best: Option<(Address, U256)> = None;
-    for (addr, liquidity) in liquidities {
-        let liq_f64 = liquidity.as_u128() as f64;
-        let usage_amount = U256::from((usage_percent * liq_f64) as u128);
-        if best.is_none() || usage_amount > best.as_ref().unwrap().1 {
-            best = Some((*addr, usage_amount));
+
+    async fn quote(&self, client: Arc<M>, token: Address, amount: U256) -> Option<FlashloanQuote> {
+        let mut quotes = Vec::new();
+        for pair_token in self.pair_tokens.iter().filter(|t| **t != token) {
+            for fee_tier in &self.fee_tiers {
+                let args = [Token::Address(token), Token::Address(*pair_token), Token::Uint((*fee_tier).into())];
+                let Some(pool) = call_address(&*client, self.factory, "getPool(address,address,uint24)", &args).await else {
+                    continue;
+                };
+                if let Some(available) = balance_of(&*client, token, pool).await {
+                    let fee = (U256::from(*fee_tier), U256::from(1_000_000u64));
+                    quotes.push(FlashloanQuote::new(&self.name, pool, token, amount, available, fee, self.callback));
+                }
+            }
         }
+        cheapest_quote(quotes, amount, &BorrowLimits::utilization(1.0))
     }
-    best
 }
 
-/// Select the best flashloan provider and amount (using liquidity_usage_percentage).
-pub async fn select_best_flashloan_provider<M: Middleware + 'static>(
+/// V2 flash swap: a pair lends its reserve of `token` (less one wei) and is repaid with the
+/// swap fee on top, `fee_bps` of the repayment.
+#[derive(Debug, Clone)]
+pub struct V2PairSource {
+    pub name: String,
+    pub factory: Address,
+    pub fee_bps: u64,
+    pub pair_tokens: Vec<Address>,
+    pub callback: FlashloanCallback,
+}
+
+#[async_trait]
+impl<M: Middleware + 'static> FlashloanSource<M> for V2PairSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn quote(&self, client: Arc<M>, token: Address, amount: U256) -> Option<FlashloanQuote> {
+        let mut quotes = Vec::new();
+        for pair_token in self.pair_tokens.iter().filter(|t| **t != token) {
+            let args = [Token::Address(token), Token::Address(*pair_token)];
+            let Some(pair) = call_address(&*client, self.factory, "getPair(address,address)", &args).await else {
+                continue;
+            };
+            if let Some(balance) = balance_of(&*client, token, pair).await {
+                // repayment × (1 - fee) must cover the loan: fee = loan × bps / (10000 - bps)
+                let fee = (U256::from(self.fee_bps), U256::from(10_000 - self.fee_bps));
+                quotes.push(FlashloanQuote::new(&self.name, pair, token, amount, balance.saturating_sub(U256::one()), fee, self.callback));
+            }
+        }
+        cheapest_quote(quotes, amount, &BorrowLimits::utilization(1.0))
+    }
+}
+
+/// How much of a lender's liquidity may be borrowed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BorrowLimits {
+    /// Fraction of a lender's availability that may be borrowed (0.45 = 45%)
+    pub utilization: f64,
+    /// Lenders holding less than this (raw token units) are not borrowed from at all
+    pub min_available: U256,
+}
+
+impl BorrowLimits {
+    pub fn utilization(utilization: f64) -> Self {
+        Self { utilization, min_available: U256::zero() }
+    }
+}
+
+/// Utilization cap (as a fraction) for a `category` token traded by `matrix`: the stricter of
+/// `max_liquidity_utilization_<category>` and `matrixN_max_liquidity_utilization`. Unset (zero)
+/// caps fall back to `liquidity_usage_percentage`.
+pub fn utilization_cap(settings: &Settings, category: TokenCategory, matrix: Option<usize>) -> f64 {
+    let category_cap = category.max_liquidity_utilization(settings);
+    let category_cap = if category_cap > 0.0 { category_cap } else { settings.liquidity_usage_percentage };
+    let cap = match matrix.and_then(|m| settings.matrix_max_liquidity_utilization(m)) {
+        Some(matrix_cap) => category_cap.min(matrix_cap),
+        None => category_cap,
+    };
+    (cap / 100.0).clamp(0.0, 1.0)
+}
+
+/// Cheapest quote able to lend `amount` within `limits`, ties going to the deeper lender. If
+/// none can, the deepest eligible lender is quoted for what it can lend.
+pub fn cheapest_quote(quotes: Vec<FlashloanQuote>, amount: U256, limits: &BorrowLimits) -> Option<FlashloanQuote> {
+    let utilization = limits.utilization.clamp(0.0, 1.0);
+    let usable = |q: &FlashloanQuote| raw_fraction(q.available, utilization).unwrap_or_default();
+    let quotes: Vec<FlashloanQuote> = quotes.into_iter().filter(|q| q.available >= limits.min_available).collect();
+    let covering = quotes
+        .iter()
+        .filter(|q| usable(q) >= amount)
+        .min_by(|a, b| a.fee.cmp(&b.fee).then(b.available.cmp(&a.available)));
+    if let Some(quote) = covering {
+        return Some(quote.clone());
+    }
+    let deepest = quotes.into_iter().max_by_key(|q| q.available)?;
+    let capped = usable(&deepest);
+    if capped.is_zero() {
+        return None;
+    }
+    Some(deepest.with_amount(capped))
+}
+
+/// Flashloan sources for `flash_loan_providers`. Names pick the protocol (Aave, Balancer,
+/// Uniswap, PancakeSwap) and its address on the chain (`addresses`); "name:address" entries
+/// override the address. Protocols without an address on the chain are skipped.
+/// `pair_tokens` are paired with the borrowed token to find V2 pairs and V3 pools.
+pub fn configured_sources<M: Middleware + 'static>(
     settings: &Settings,
-    client: Arc<M>,
-    asset: Address,
-) -> Option<(Address, U256)> {
-    let mut best: Option<(Address, U256)> = None;
+    addresses: &FlashloanAddresses,
+    pair_tokens: &[Address],
+) -> Vec<Box<dyn FlashloanSource<M>>> {
+    let mut sources: Vec<Box<dyn FlashloanSource<M>>> = Vec::new();
     for entry in &settings.flash_loan_providers {
-        if let Some(provider) = FlashloanProvider::from_entry(entry) {
-            if let Some(liquidity) = query_liquidity(&provider, asset, client.clone()).await {
-                let liq_f64 = liquidity.as_u128() as f64;
-                let usage = U256::from((settings.liquidity_usage_percentage * liq_f64) as u128);
-                if best.is_none() || usage > best.as_ref().unwrap().1 {
-                    best = Some((provider.address, usage));
+        let (name, address) = match entry.trim().split_once(':') {
+            Some((name, address)) => (name.trim(), Some(address.trim())),
+            None => (entry.trim(), None),
+        };
+        let parse = |chain_address: &Option<String>| -> Option<Address> {
+            let Some(address) = address.or(chain_address.as_deref()) else {
+                debug!("No address for flashloan provider {} on this chain", name);
+                return None;
+            };
+            match address.parse::<Address>() {
+                Ok(address) => Some(address),
+                Err(_) => {
+                    warn!("Invalid address for flashloan provider {}: {}", name, address);
+                    None
+                }
+            }
+        };
+        match name.to_lowercase().as_str() {
+            "aave" => {
+                if let Some(addresses_provider) = parse(&addresses.aave_pool_address_provider) {
+                    sources.push(Box::new(AaveV3Source { addresses_provider }));
+                }
+            }
+            "balancer" => {
+                if let Some(vault) = parse(&addresses.balancer_vault_address) {
+                    sources.push(Box::new(BalancerSource { vault }));
+                }
+            }
+            "uniswap" => {
+                if let Some(factory) = parse(&addresses.uniswap_v3_factory) {
+                    sources.push(Box::new(V3PoolSource {
+                        name: "Uniswap V3".to_string(),
+                        factory,
+                        fee_tiers: vec![100, 500, 3000, 10000],
+                        pair_tokens: pair_tokens.to_vec(),
+                        callback: FlashloanCallback::UniswapV3Flash,
+                    }));
+                }
+            }
+            "pancakeswap" => {
+                // An explicit address names the V3 factory; V2 flash swaps use the V2 factory
+                if let Some(factory) = parse(&addresses.pancakeswap_v3_factory) {
+                    sources.push(Box::new(V3PoolSource {
+                        name: "PancakeSwap V3".to_string(),
+                        factory,
+                        fee_tiers: vec![100, 500, 2500, 10000],
+                        pair_tokens: pair_tokens.to_vec(),
+                        callback: FlashloanCallback::PancakeV3Flash,
+                    }));
+                }
+                match addresses.pancakeswap_v2_factory.as_deref().map(|a| (a, a.parse::<Address>())) {
+                    Some((_, Ok(factory))) => sources.push(Box::new(V2PairSource {
+                        name: "PancakeSwap V2".to_string(),
+                        factory,
+                        fee_bps: 25,
+                        pair_tokens: pair_tokens.to_vec(),
+                        callback: FlashloanCallback::PancakeCall,
+                    })),
+                    Some((address, Err(_))) => warn!("Invalid pancakeswap_v2_factory: {}", address),
+                    None => {}
                 }
             }
+            _ => debug!("No flashloan adapter for provider {}", name),
         }
     }
-    best
+    sources
+}
+
+/// Quote `amount` of `token` from every source and pick the cheapest within `limits`.
+pub async fn select_flashloan<M: Middleware + 'static>(
+    sources: &[Box<dyn FlashloanSource<M>>],
+    client: Arc<M>,
+    token: Address,
+    amount: U256,
+    limits: &BorrowLimits,
+) -> Option<FlashloanQuote> {
+    let quotes = join_all(sources.iter().map(|source| source.quote(client.clone(), token, amount)))
+        .await
+        .into_iter()
+        .flatten()
+        .collect();
+    let best = cheapest_quote(quotes, amount, limits)?;
+    debug!("[Flashloan] {} lends {} of {:?} for a fee of {}", best.source, best.amount, token, best.fee);
+    Some(best)
 }
 
 #[cfg(test)]
@@ -122,25 +512,100 @@ mod tests {
     }
 
     #[test]
-    fn test_choose_best_provider_empty() {
-        assert!(choose_best_provider(&[], 0.45).is_none());
+    fn test_configured_providers_resolves_names() {
+        let settings = Settings {
+            flash_loan_providers: vec!["Aave".into(), "Uniswap".into(), "Custom:0x0000000000000000000000000000000000000007".into()],
+            aave_pool_address_provider: "0x6Ae43d3271ff6888e7Fc43Fd7321a503ff738951".into(),
+            ..Default::default()
+        };
+        let providers = configured_providers(&settings);
+        assert_eq!(providers.len(), 2);
+        assert_eq!(providers[0].name, "Aave");
+        assert_eq!(providers[0].address, "0x6Ae43d3271ff6888e7Fc43Fd7321a503ff738951".parse::<Address>().unwrap());
+        assert_eq!(providers[1].name, "Custom");
+        assert_eq!(providers[1].address, addr_from_u64(7));
+    }
+
+    fn quote(source: &str, available: u64, fee: (u64, u64)) -> FlashloanQuote {
+        let fee = (U256::from(fee.0), U256::from(fee.1));
+        FlashloanQuote::new(source, addr_from_u64(9), addr_from_u64(10), U256::zero(), available.into(), fee, FlashloanCallback::AaveExecuteOperation)
     }
 
     #[test]
-    fn test_choose_best_provider_single() {
-        let addr = addr_from_u64(1);
-        let res = choose_best_provider(&[(addr, U256::from(100u64))], 0.45).unwrap();
-        assert_eq!(res.0, addr);
-        assert_eq!(res.1, U256::from(45u64));
+    fn test_cheapest_quote_minimizes_fee() {
+        let quotes = [
+            quote("Balancer", 1_000, (0, 1)),
+            quote("Aave V3", 1_000_000, (9, 10_000)),
+            quote("PancakeSwap V2", 10_000_000, (25, 9_975)),
+        ];
+        let limits = BorrowLimits::utilization(0.45);
+        let amount = U256::from(10_000u64);
+        let best = cheapest_quote(quotes.iter().map(|q| q.clone().with_amount(amount)).collect(), amount, &limits).unwrap();
+        assert_eq!((best.source.as_str(), best.fee), ("Aave V3", U256::from(9u64)));
+
+        // Small enough for the fee-free vault
+        let amount = U256::from(100u64);
+        let best = cheapest_quote(quotes.iter().map(|q| q.clone().with_amount(amount)).collect(), amount, &limits).unwrap();
+        assert_eq!((best.source.as_str(), best.fee), ("Balancer", U256::zero()));
+
+        // Nobody covers it: the deepest lender, capped at 45% and charged for what it lends
+        let amount = U256::from(100_000_000u64);
+        let best = cheapest_quote(quotes.iter().map(|q| q.clone().with_amount(amount)).collect(), amount, &limits).unwrap();
+        assert_eq!(best.source, "PancakeSwap V2");
+        assert_eq!(best.amount, U256::from(4_500_000u64));
+        assert_eq!(best.fee, U256::from(11_279u64));
+        assert!(cheapest_quote(Vec::new(), amount, &limits).is_none());
+
+        // Lenders below the minimum are skipped even when they would be cheapest; the others'
+        // fees round up to the same 1 unit, so the deeper one wins
+        let limits = BorrowLimits { utilization: 0.45, min_available: U256::from(5_000u64) };
+        let amount = U256::from(100u64);
+        let best = cheapest_quote(quotes.iter().map(|q| q.clone().with_amount(amount)).collect(), amount, &limits).unwrap();
+        assert_eq!((best.source.as_str(), best.fee), ("PancakeSwap V2", U256::one()));
     }
 
     #[test]
-    fn test_choose_best_provider_multiple() {
-        let a1 = addr_from_u64(1);
-        let a2 = addr_from_u64(2);
-        let data = vec![(a1, U256::from(100u64)), (a2, U256::from(200u64))];
-        let res = choose_best_provider(&data, 0.45).unwrap();
-        assert_eq!(res.0, a2);
-        assert_eq!(res.1, U256::from(90u64));
+    fn test_utilization_cap_takes_stricter_limit() {
+        let settings = Settings {
+            liquidity_usage_percentage: 45.0,
+            max_liquidity_utilization_stable: 100.0,
+            max_liquidity_utilization_meme: 25.0,
+            max_liquidity_utilization_alt: 0.0,
+            matrix1_max_liquidity_utilization: 60.0,
+            matrix2_max_liquidity_utilization: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(utilization_cap(&settings, TokenCategory::Stable, None), 1.0);
+        assert_eq!(utilization_cap(&settings, TokenCategory::Stable, Some(1)), 0.6);
+        assert_eq!(utilization_cap(&settings, TokenCategory::Meme, Some(1)), 0.25);
+        assert_eq!(utilization_cap(&settings, TokenCategory::Meme, Some(2)), 0.1);
+        // Unset category cap: the global percentage
+        assert_eq!(utilization_cap(&settings, TokenCategory::Alt, Some(1)), 0.45);
+        // Matrix without a configured cap
+        assert_eq!(utilization_cap(&settings, TokenCategory::Stable, Some(7)), 1.0);
+    }
+
+    fn words(values: &[U256]) -> Bytes {
+        Bytes::from(ethers::abi::encode(&values.iter().map(|v| Token::Uint(*v)).collect::<Vec<_>>()))
+    }
+
+    #[tokio::test]
+    async fn test_aave_source_reads_reserve_and_premium() {
+        let (pool, a_token, asset) = (addr_from_u64(0xa1), addr_from_u64(0xa2), addr_from_u64(0xa3));
+        let mut reserve = vec![U256::zero(); 15];
+        reserve[8] = U256::from_big_endian(a_token.as_bytes());
+        let (provider, mock) = Provider::mocked();
+        // Responses are popped LIFO: getPool, getReserveData, balanceOf(aToken), premium
+        mock.push::<Bytes, _>(words(&[U256::from(5u64)])).unwrap();
+        mock.push::<Bytes, _>(words(&[U256::from(2_000_000u64)])).unwrap();
+        mock.push::<Bytes, _>(words(&reserve)).unwrap();
+        mock.push::<Bytes, _>(words(&[U256::from_big_endian(pool.as_bytes())])).unwrap();
+
+        let source = AaveV3Source { addresses_provider: addr_from_u64(0xa0) };
+        let quote = source.quote(Arc::new(provider), asset, U256::from(1_000_000u64)).await.unwrap();
+        assert_eq!(quote.lender, pool);
+        assert_eq!(quote.available, U256::from(2_000_000u64));
+        assert_eq!(quote.fee, U256::from(500u64));
+        assert_eq!(quote.callback.signature(), "executeOperation(address,uint256,uint256,address,bytes)");
     }
 }
@@ -0,0 +1,329 @@
+// Gas oracle: polls eth_feeHistory (EIP-1559 chains) or eth_gasPrice (legacy chains such as
+// BSC) and produces buffered, capped fee suggestions for transaction building and for the
+// gas-aware profit model.
+
+use crate::chain_registry::{ChainConfig, GasModel};
+use crate::config::Settings;
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::format_units;
+use log::{debug, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Blocks of fee history sampled for the priority fee estimate.
+const FEE_HISTORY_BLOCKS: u64 = 10;
+/// Reward percentile used as the priority fee estimate.
+const PRIORITY_FEE_PERCENTILE: f64 = 50.0;
+
+#[derive(Debug, Error)]
+pub enum GasOracleError {
+    #[error("Gas price query failed on chain {0}: {1}")]
+    Provider(u64, String),
+    #[error("Empty fee history on chain {0}")]
+    EmptyFeeHistory(u64),
+}
+
+/// Fee fields for a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeSuggestion {
+    Legacy { gas_price: U256 },
+    Eip1559 { max_fee_per_gas: U256, max_priority_fee_per_gas: U256 },
+}
+
+impl FeeSuggestion {
+    /// Worst-case price paid per unit of gas.
+    pub fn max_price_per_gas(&self) -> U256 {
+        match self {
+            FeeSuggestion::Legacy { gas_price } => *gas_price,
+            FeeSuggestion::Eip1559 { max_fee_per_gas, .. } => *max_fee_per_gas,
+        }
+    }
+
+    /// Worst-case cost of `gas_limit` gas in native token units (BNB, ETH).
+    pub fn cost_native(&self, gas_limit: u64) -> f64 {
+        let wei = self.max_price_per_gas().saturating_mul(U256::from(gas_limit));
+        format_units(wei, "ether").ok().and_then(|s| s.parse().ok()).unwrap_or(f64::MAX)
+    }
+
+    /// Worst-case cost of `gas_limit` gas in USD, given the native token price.
+    pub fn cost_usd(&self, gas_limit: u64, native_price_usd: f64) -> f64 {
+        self.cost_native(gas_limit) * native_price_usd
+    }
+
+    /// Set the fee fields on `tx`, converting it to the matching transaction type.
+    pub fn apply(&self, tx: &mut TypedTransaction) {
+        match *self {
+            FeeSuggestion::Legacy { gas_price } => {
+                if let TypedTransaction::Eip1559(inner) = tx {
+                    *tx = TypedTransaction::Legacy(TransactionRequest {
+                        from: inner.from,
+                        to: inner.to.clone(),
+                        gas: inner.gas,
+                        gas_price: None,
+                        value: inner.value,
+                        data: inner.data.clone(),
+                        nonce: inner.nonce,
+                        chain_id: inner.chain_id,
+                    });
+                }
+                tx.set_gas_price(gas_price);
+            }
+            FeeSuggestion::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => {
+                if let TypedTransaction::Legacy(inner) = tx {
+                    *tx = TypedTransaction::Eip1559(Eip1559TransactionRequest {
+                        from: inner.from,
+                        to: inner.to.clone(),
+                        gas: inner.gas,
+                        value: inner.value,
+                        data: inner.data.clone(),
+                        nonce: inner.nonce,
+                        chain_id: inner.chain_id,
+                        ..Default::default()
+                    });
+                }
+                if let TypedTransaction::Eip1559(inner) = tx {
+                    inner.max_fee_per_gas = Some(max_fee_per_gas);
+                    inner.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+                }
+            }
+        }
+    }
+}
+
+/// Buffer settings and one chain's fee caps.
+#[derive(Debug, Clone)]
+pub struct GasOracleConfig {
+    /// "dynamic" polls the chain; anything else uses the configured caps as fixed prices
+    pub dynamic: bool,
+    /// Percent added on top of the observed prices
+    pub buffer_percentage: f64,
+    /// The chain's `max_fee_per_gas`; U256::MAX if uncapped
+    pub max_fee_per_gas: U256,
+    /// The chain's `max_priority_fee_per_gas`; U256::MAX if uncapped
+    pub max_priority_fee_per_gas: U256,
+    pub update_interval: Duration,
+}
+
+impl GasOracleConfig {
+    pub fn from_settings(settings: &Settings, chain: &ChainConfig) -> Self {
+        let capped = match chain.gas_model {
+            GasModel::Legacy => chain.max_fee_per_gas.is_some(),
+            GasModel::Eip1559 => chain.max_fee_per_gas.is_some() && chain.max_priority_fee_per_gas.is_some(),
+        };
+        let mut dynamic = settings.gas_estimator.eq_ignore_ascii_case("dynamic");
+        if !dynamic && !capped {
+            warn!("[GasOracle] Chain {} has no fee caps to use as fixed prices; polling it instead", chain.name);
+            dynamic = true;
+        }
+        Self {
+            dynamic,
+            buffer_percentage: settings.gas_price_buffer as f64,
+            max_fee_per_gas: chain.max_fee_per_gas.map_or(U256::MAX, U256::from),
+            max_priority_fee_per_gas: chain.max_priority_fee_per_gas.map_or(U256::MAX, U256::from),
+            update_interval: Duration::from_millis(settings.gas_price_update_interval_ms.max(1)),
+        }
+    }
+
+    fn buffered(&self, value: U256) -> U256 {
+        let bps = (self.buffer_percentage.max(0.0) * 100.0) as u64;
+        value.saturating_mul(U256::from(10_000 + bps)) / U256::from(10_000u64)
+    }
+}
+
+/// Build a suggestion from raw chain observations, applying buffer and caps.
+/// `base_fee` and `priority_fee` are only used for EIP-1559 chains, `gas_price` for legacy ones.
+pub fn suggest_fees(
+    config: &GasOracleConfig,
+    gas_model: GasModel,
+    gas_price: U256,
+    base_fee: U256,
+    priority_fee: U256,
+) -> FeeSuggestion {
+    match gas_model {
+        GasModel::Legacy => FeeSuggestion::Legacy {
+            gas_price: config.buffered(gas_price).min(config.max_fee_per_gas),
+        },
+        GasModel::Eip1559 => {
+            let max_fee_per_gas = config
+                .buffered(base_fee.saturating_mul(U256::from(2)).saturating_add(priority_fee))
+                .min(config.max_fee_per_gas);
+            let max_priority_fee_per_gas = config
+                .buffered(priority_fee)
+                .min(config.max_priority_fee_per_gas)
+                .min(max_fee_per_gas);
+            FeeSuggestion::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas }
+        }
+    }
+}
+
+/// Fee oracle for one chain. `latest()` is cheap and returns the last polled suggestion.
+#[derive(Debug)]
+pub struct GasOracle {
+    chain_id: u64,
+    gas_model: GasModel,
+    config: GasOracleConfig,
+    latest: RwLock<Option<FeeSuggestion>>,
+    // Whether the last polled suggestion hit max_fee_per_gas
+    capped: AtomicBool,
+}
+
+impl GasOracle {
+    pub fn new(chain_id: u64, gas_model: GasModel, config: GasOracleConfig) -> Self {
+        Self { chain_id, gas_model, config, latest: RwLock::new(None), capped: AtomicBool::new(false) }
+    }
+
+    pub fn gas_model(&self) -> GasModel {
+        self.gas_model
+    }
+
+    /// Last suggestion from `refresh`, if any.
+    pub fn latest(&self) -> Option<FeeSuggestion> {
+        *self.latest.read().unwrap()
+    }
+
+    /// Query the chain and update the cached suggestion.
+    pub async fn refresh<M: Middleware>(&self, client: &M) -> Result<FeeSuggestion, GasOracleError> {
+        let suggestion = if !self.config.dynamic {
+            suggest_fees(
+                &self.config,
+                self.gas_model,
+                self.config.max_fee_per_gas,
+                self.config.max_fee_per_gas,
+                self.config.max_priority_fee_per_gas,
+            )
+        } else {
+            match self.gas_model {
+                GasModel::Legacy => {
+                    let gas_price = client
+                        .get_gas_price()
+                        .await
+                        .map_err(|e| GasOracleError::Provider(self.chain_id, e.to_string()))?;
+                    suggest_fees(&self.config, self.gas_model, gas_price, U256::zero(), U256::zero())
+                }
+                GasModel::Eip1559 => {
+                    let history = client
+                        .fee_history(FEE_HISTORY_BLOCKS, BlockNumber::Latest, &[PRIORITY_FEE_PERCENTILE])
+                        .await
+                        .map_err(|e| GasOracleError::Provider(self.chain_id, e.to_string()))?;
+                    // base_fee_per_gas includes the next block's base fee as its last element
+                    let base_fee = *history
+                        .base_fee_per_gas
+                        .last()
+                        .ok_or(GasOracleError::EmptyFeeHistory(self.chain_id))?;
+                    let mut rewards: Vec<U256> = history
+                        .reward
+                        .iter()
+                        .filter_map(|r| r.first().copied())
+                        .filter(|r| !r.is_zero())
+                        .collect();
+                    rewards.sort();
+                    let priority_fee = rewards
+                        .get(rewards.len() / 2)
+                        .copied()
+                        .unwrap_or(self.config.max_priority_fee_per_gas);
+                    suggest_fees(&self.config, self.gas_model, U256::zero(), base_fee, priority_fee)
+                }
+            }
+        };
+        // Log when the chain's fees cross the cap, not on every poll while they stay above it
+        let capped = self.config.dynamic && suggestion.max_price_per_gas() == self.config.max_fee_per_gas;
+        if self.capped.swap(capped, Ordering::Relaxed) != capped {
+            if capped {
+                warn!(
+                    "[GasOracle] Chain {} fee suggestion capped at max_fee_per_gas ({} wei)",
+                    self.chain_id, self.config.max_fee_per_gas
+                );
+            } else {
+                info!("[GasOracle] Chain {} fee suggestion back under max_fee_per_gas", self.chain_id);
+            }
+        }
+        debug!("[GasOracle] Chain {} suggestion: {:?}", self.chain_id, suggestion);
+        *self.latest.write().unwrap() = Some(suggestion);
+        Ok(suggestion)
+    }
+
+    /// Cached suggestion, refreshing first if nothing has been polled yet.
+    pub async fn current<M: Middleware>(&self, client: &M) -> Result<FeeSuggestion, GasOracleError> {
+        match self.latest() {
+            Some(suggestion) => Ok(suggestion),
+            None => self.refresh(client).await,
+        }
+    }
+
+    /// Poll every `gas_price_update_interval_ms`, forever.
+    pub async fn run<M: Middleware + 'static>(self: Arc<Self>, client: Arc<M>) {
+        let mut ticker = tokio::time::interval(self.config.update_interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.refresh(&*client).await {
+                warn!("[GasOracle] {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(n: u64) -> U256 {
+        U256::from(n) * U256::exp10(9)
+    }
+
+    fn config() -> GasOracleConfig {
+        GasOracleConfig {
+            dynamic: true,
+            buffer_percentage: 20.0,
+            max_fee_per_gas: gwei(100),
+            max_priority_fee_per_gas: gwei(2),
+            update_interval: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_legacy_suggestion_buffer_and_cap() {
+        let fees = suggest_fees(&config(), GasModel::Legacy, gwei(5), U256::zero(), U256::zero());
+        assert_eq!(fees, FeeSuggestion::Legacy { gas_price: gwei(6) });
+        let capped = suggest_fees(&config(), GasModel::Legacy, gwei(500), U256::zero(), U256::zero());
+        assert_eq!(capped.max_price_per_gas(), gwei(100));
+    }
+
+    #[test]
+    fn test_caps_come_from_the_chain() {
+        let settings = Settings { gas_estimator: "fixed".to_string(), ..Default::default() };
+        let bsc = ChainConfig { max_fee_per_gas: Some(2_000_000_000), ..Default::default() };
+        let config = GasOracleConfig::from_settings(&settings, &bsc);
+        assert!(!config.dynamic);
+        assert_eq!(config.max_fee_per_gas, gwei(2));
+        // Without caps there is no fixed price to use, and nothing to cap at
+        let config = GasOracleConfig::from_settings(&settings, &ChainConfig::default());
+        assert!(config.dynamic);
+        assert_eq!(config.max_fee_per_gas, U256::MAX);
+    }
+
+    #[test]
+    fn test_eip1559_suggestion() {
+        let fees = suggest_fees(&config(), GasModel::Eip1559, U256::zero(), gwei(10), gwei(1));
+        // (2 * 10 + 1) * 1.2 = 25.2 gwei; priority 1.2 gwei
+        assert_eq!(
+            fees,
+            FeeSuggestion::Eip1559 {
+                max_fee_per_gas: U256::from(25_200_000_000u64),
+                max_priority_fee_per_gas: U256::from(1_200_000_000u64),
+            }
+        );
+        assert!((fees.cost_native(100_000) - 0.00252).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_apply_converts_transaction_type() {
+        let mut tx: TypedTransaction = Eip1559TransactionRequest::new().nonce(7).into();
+        FeeSuggestion::Legacy { gas_price: gwei(3) }.apply(&mut tx);
+        assert!(matches!(tx, TypedTransaction::Legacy(_)));
+        assert_eq!(tx.gas_price(), Some(gwei(3)));
+        assert_eq!(tx.nonce(), Some(&U256::from(7)));
+    }
+}
@@ -0,0 +1,432 @@
+// Inventory and PnL ledger: balances per (chain, wallet, token) built from execution receipts
+// (profit paid to the profit wallet, gas paid by the executor wallet) and profit sweeps (excess
+// moved from executor wallets to the profit wallet), each carrying the USD cost basis it was
+// valued at when it arrived. Realized PnL is booked per trade and rolled up by protocol, matrix
+// and UTC day; unrealized PnL marks the inventory at current matrix prices against that basis.
+
+use crate::chain_registry::ChainRegistry;
+use crate::config::Settings;
+use crate::matrix2d::Matrix2D;
+use crate::receipt_processor::RealizedExecution;
+use crate::time_sync;
+use crate::token_amount::TokenAmount;
+use crate::token_registry::TokenRegistry;
+use chrono::Utc;
+use ethers::types::{Address, I256, U256};
+use log::warn;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Token key of the native coin in the ledger
+pub const NATIVE_TOKEN: Address = Address::zero();
+/// Label of the native coin on chains missing from the chain registry
+const UNKNOWN_NATIVE_SYMBOL: &str = "NATIVE";
+const NATIVE_DECIMALS: u8 = 18;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Position {
+    symbol: String,
+    decimals: u8,
+    // Negative when more was recorded leaving than arriving, e.g. gas from an unseeded wallet
+    raw: I256,
+    cost_basis_usd: f64,
+}
+
+impl Position {
+    fn new(symbol: &str, decimals: u8) -> Self {
+        Self { symbol: symbol.to_string(), decimals, raw: I256::zero(), cost_basis_usd: 0.0 }
+    }
+
+    fn amount(&self) -> f64 {
+        let magnitude = TokenAmount::new(self.raw.unsigned_abs(), self.decimals).to_f64();
+        if self.raw.is_negative() { -magnitude } else { magnitude }
+    }
+
+    fn credit(&mut self, raw: U256, cost_basis_usd: f64) {
+        self.raw = self.raw.saturating_add(I256::from_raw(raw.min(I256::MAX.into_raw())));
+        self.cost_basis_usd += cost_basis_usd;
+    }
+
+    /// Remove `raw` and its share of the cost basis, which is returned.
+    fn debit(&mut self, raw: U256) -> f64 {
+        let removed = match self.raw.is_positive() {
+            true => {
+                let held = self.raw.into_raw();
+                let share = if raw >= held {
+                    1.0
+                } else {
+                    TokenAmount::new(raw, self.decimals).to_f64() / TokenAmount::new(held, self.decimals).to_f64()
+                };
+                self.cost_basis_usd * share
+            }
+            false => 0.0,
+        };
+        self.cost_basis_usd -= removed;
+        self.raw = self.raw.saturating_sub(I256::from_raw(raw.min(I256::MAX.into_raw())));
+        removed
+    }
+}
+
+/// Realized PnL of one mined execution.
+#[derive(Debug, Clone, Serialize)]
+pub struct TradePnl {
+    pub tx_hash: String,
+    /// RFC 3339, UTC
+    pub timestamp: String,
+    pub chain_id: u64,
+    pub protocol: String,
+    pub matrix: Option<usize>,
+    pub wallet: String,
+    pub profit_usd: f64,
+    pub gas_usd: Option<f64>,
+    /// Profit less gas, or the gross profit if gas couldn't be valued
+    pub net_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionReport {
+    pub chain_id: u64,
+    pub wallet: String,
+    pub token: String,
+    pub symbol: String,
+    pub amount: f64,
+    pub cost_basis_usd: f64,
+    pub mark_price_usd: Option<f64>,
+    pub market_value_usd: Option<f64>,
+    pub unrealized_pnl_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerReport {
+    pub realized_pnl_usd: f64,
+    pub unrealized_pnl_usd: f64,
+    pub total_pnl_usd: f64,
+    pub realized_by_protocol: BTreeMap<String, f64>,
+    /// Keyed "matrixN", or "none" for trades without a matrix
+    pub realized_by_matrix: BTreeMap<String, f64>,
+    /// Keyed by UTC date (YYYY-MM-DD)
+    pub realized_by_day: BTreeMap<String, f64>,
+    pub positions: Vec<PositionReport>,
+    pub trades: usize,
+}
+
+#[derive(Default)]
+struct LedgerState {
+    positions: HashMap<(u64, Address, Address), Position>,
+    trades: Vec<TradePnl>,
+}
+
+pub struct Ledger {
+    state: RwLock<LedgerState>,
+    // Marks inventory for unrealized PnL
+    matrix: Option<Arc<Mutex<Matrix2D>>>,
+    mark_price_max_age_ms: u64,
+    // Names tokens booked by address (profit transfers)
+    registry: Option<Arc<TokenRegistry>>,
+    // Names each chain's native coin and the wrapped token it is marked at
+    chains: Option<Arc<ChainRegistry>>,
+}
+
+impl Ledger {
+    pub fn new(mark_price_max_age_ms: u64) -> Self {
+        Self { state: RwLock::new(LedgerState::default()), matrix: None, mark_price_max_age_ms, registry: None, chains: None }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(settings.ledger_mark_price_max_age_ms)
+    }
+
+    /// Mark inventory at `matrix` prices
+    pub fn with_matrix(mut self, matrix: Arc<Mutex<Matrix2D>>) -> Self {
+        self.matrix = Some(matrix);
+        self
+    }
+
+    /// Name tokens from `registry`; unregistered tokens are shown by address
+    pub fn with_registry(mut self, registry: Arc<TokenRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Name native coins and mark them at their wrapped token's price from `chains`
+    pub fn with_chains(mut self, chains: Arc<ChainRegistry>) -> Self {
+        self.chains = Some(chains);
+        self
+    }
+
+    // The native coin's symbol on `chain_id` and the matrix asset it falls back to for marking
+    fn native_symbols(&self, chain_id: u64) -> (String, Option<String>) {
+        match self.chains.as_ref().and_then(|chains| chains.get(chain_id)) {
+            Some(chain) => {
+                let wrapped = Some(chain.wrapped_native_token.clone()).filter(|s| !s.is_empty());
+                (chain.native_token.clone(), wrapped)
+            }
+            None => (UNKNOWN_NATIVE_SYMBOL.to_string(), None),
+        }
+    }
+
+    fn symbol(&self, chain_id: u64, token: Address) -> String {
+        if token == NATIVE_TOKEN {
+            return self.native_symbols(chain_id).0;
+        }
+        self.registry
+            .as_ref()
+            .and_then(|registry| registry.symbol_of(chain_id, token))
+            .unwrap_or_else(|| format!("{:?}", token))
+    }
+
+    /// Record the opening balance of a position not yet in the ledger, at `price_usd` as its
+    /// cost basis so that holdings from before the ledger started carry no PnL. Positions
+    /// already in the ledger are left alone. The native coin is always named after its chain.
+    pub fn open_position(&self, chain_id: u64, wallet: Address, token: Address, symbol: &str, balance: TokenAmount, price_usd: Option<f64>) {
+        let symbol = if token == NATIVE_TOKEN { self.symbol(chain_id, token) } else { symbol.to_string() };
+        let mut state = self.state.write().unwrap();
+        state.positions.entry((chain_id, wallet, token)).or_insert_with(|| {
+            let mut position = Position::new(&symbol, balance.decimals());
+            position.credit(balance.raw(), price_usd.and_then(|p| balance.usd(p)).unwrap_or(0.0));
+            position
+        });
+    }
+
+    /// Book a mined execution sent from `wallet`: its profit transfers are credited to
+    /// `profit_wallet` at the USD value they were realized at, and its gas is debited from
+    /// `wallet`'s native balance.
+    pub fn record_trade(
+        &self,
+        chain_id: u64,
+        wallet: Address,
+        profit_wallet: Address,
+        protocol: &str,
+        matrix: Option<usize>,
+        realized: &RealizedExecution,
+    ) {
+        let mut state = self.state.write().unwrap();
+        for profit in &realized.profits {
+            let Some(amount) = profit.amount else {
+                warn!("[Ledger] Unknown decimals for {:?}; not booking {} raw", profit.token, profit.raw);
+                continue;
+            };
+            let symbol = self.symbol(chain_id, profit.token);
+            state
+                .positions
+                .entry((chain_id, profit_wallet, profit.token))
+                .or_insert_with(|| Position::new(&symbol, amount.decimals()))
+                .credit(amount.raw(), profit.usd.unwrap_or(0.0));
+        }
+        let gas = realized.gas_used.saturating_mul(realized.effective_gas_price);
+        let native = self.symbol(chain_id, NATIVE_TOKEN);
+        state
+            .positions
+            .entry((chain_id, wallet, NATIVE_TOKEN))
+            .or_insert_with(|| Position::new(&native, NATIVE_DECIMALS))
+            .debit(gas);
+        state.trades.push(TradePnl {
+            tx_hash: format!("0x{:x}", realized.tx_hash),
+            timestamp: Utc::now().to_rfc3339(),
+            chain_id,
+            protocol: protocol.to_string(),
+            matrix,
+            wallet: format!("{:?}", wallet),
+            profit_usd: realized.profit_usd,
+            gas_usd: realized.gas_cost_usd,
+            net_usd: realized.net_profit_usd.unwrap_or(realized.profit_usd),
+        });
+    }
+
+    /// Book a mined sweep of `amount` of `token` (NATIVE_TOKEN for the native coin) from
+    /// `from` to `to`, whose gas cost `gas_cost` wei. The cost basis moves with the amount.
+    pub fn record_sweep(&self, chain_id: u64, from: Address, to: Address, token: Address, amount: TokenAmount, gas_cost: U256) {
+        let symbol = &self.symbol(chain_id, token);
+        let native = self.symbol(chain_id, NATIVE_TOKEN);
+        let mut state = self.state.write().unwrap();
+        let basis = state
+            .positions
+            .entry((chain_id, from, token))
+            .or_insert_with(|| Position::new(symbol, amount.decimals()))
+            .debit(amount.raw());
+        state
+            .positions
+            .entry((chain_id, to, token))
+            .or_insert_with(|| Position::new(symbol, amount.decimals()))
+            .credit(amount.raw(), basis);
+        state
+            .positions
+            .entry((chain_id, from, NATIVE_TOKEN))
+            .or_insert_with(|| Position::new(&native, NATIVE_DECIMALS))
+            .debit(gas_cost);
+    }
+
+    pub fn trades(&self) -> Vec<TradePnl> {
+        self.state.read().unwrap().trades.clone()
+    }
+
+    pub fn realized_pnl_usd(&self) -> f64 {
+        self.state.read().unwrap().trades.iter().map(|t| t.net_usd).sum()
+    }
+
+    // Current matrix price of `position`; the native coin falls back to its chain's wrapped token
+    fn mark_price(&self, matrix: &Matrix2D, chain_id: u64, token: Address, position: &Position, now_ms: u64) -> Option<f64> {
+        matrix.mark_price(&position.symbol, self.mark_price_max_age_ms, now_ms).or_else(|| {
+            if token != NATIVE_TOKEN {
+                return None;
+            }
+            let wrapped = self.native_symbols(chain_id).1?;
+            matrix.mark_price(&wrapped, self.mark_price_max_age_ms, now_ms)
+        })
+    }
+
+    pub fn report(&self) -> LedgerReport {
+        let matrix = self.matrix.as_ref().map(|m| m.lock().unwrap().clone());
+        let now_ms = time_sync::now_millis();
+        let state = self.state.read().unwrap();
+
+        let mut positions: Vec<PositionReport> = state
+            .positions
+            .iter()
+            .map(|((chain_id, wallet, token), position)| {
+                let amount = position.amount();
+                let mark_price_usd = matrix.as_ref().and_then(|m| self.mark_price(m, *chain_id, *token, position, now_ms));
+                let market_value_usd = mark_price_usd.map(|price| amount * price);
+                // Short positions only come from incomplete history; their value isn't PnL
+                let unrealized_pnl_usd = market_value_usd.filter(|_| amount > 0.0).map(|value| value - position.cost_basis_usd);
+                PositionReport {
+                    chain_id: *chain_id,
+                    wallet: format!("{:?}", wallet),
+                    token: format!("{:?}", token),
+                    symbol: position.symbol.clone(),
+                    amount,
+                    cost_basis_usd: position.cost_basis_usd,
+                    mark_price_usd,
+                    market_value_usd,
+                    unrealized_pnl_usd,
+                }
+            })
+            .collect();
+        positions.sort_by(|a, b| (a.chain_id, &a.wallet, &a.symbol).cmp(&(b.chain_id, &b.wallet, &b.symbol)));
+
+        let mut realized_by_protocol = BTreeMap::new();
+        let mut realized_by_matrix = BTreeMap::new();
+        let mut realized_by_day = BTreeMap::new();
+        for trade in &state.trades {
+            *realized_by_protocol.entry(trade.protocol.clone()).or_insert(0.0) += trade.net_usd;
+            let matrix = trade.matrix.map(|m| format!("matrix{}", m)).unwrap_or_else(|| "none".to_string());
+            *realized_by_matrix.entry(matrix).or_insert(0.0) += trade.net_usd;
+            let day = trade.timestamp.get(..10).unwrap_or(&trade.timestamp);
+            *realized_by_day.entry(day.to_string()).or_insert(0.0) += trade.net_usd;
+        }
+        let realized_pnl_usd: f64 = state.trades.iter().map(|t| t.net_usd).sum();
+        let unrealized_pnl_usd: f64 = positions.iter().filter_map(|p| p.unrealized_pnl_usd).sum();
+        LedgerReport {
+            realized_pnl_usd,
+            unrealized_pnl_usd,
+            total_pnl_usd: realized_pnl_usd + unrealized_pnl_usd,
+            realized_by_protocol,
+            realized_by_matrix,
+            realized_by_day,
+            positions,
+            trades: state.trades.len(),
+        }
+    }
+
+    /// Realized plus unrealized PnL in USD.
+    pub fn total_pnl_usd(&self) -> f64 {
+        self.report().total_pnl_usd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt_processor::TokenProfit;
+    use crate::chain_registry::ChainConfig;
+    use ethers::types::TxHash;
+
+    fn chains() -> Arc<ChainRegistry> {
+        let chain = |chain_id: u64, native: &str, wrapped: &str| ChainConfig {
+            chain_id,
+            native_token: native.to_string(),
+            wrapped_native_token: wrapped.to_string(),
+            ..Default::default()
+        };
+        Arc::new(ChainRegistry::new(vec![chain(56, "BNB", "WBNB"), chain(1, "ETH", "WETH")]))
+    }
+
+    fn realized(busd: Address, profit: &str, profit_usd: f64, gas_usd: Option<f64>) -> RealizedExecution {
+        let amount = TokenAmount::parse(profit, 18).unwrap();
+        RealizedExecution {
+            tx_hash: TxHash::random(),
+            success: true,
+            gas_used: U256::from(500_000u64),
+            effective_gas_price: U256::from(3_000_000_000u64),
+            gas_cost_native: 0.0015,
+            gas_cost_usd: gas_usd,
+            reported_profit: Some(amount.raw()),
+            profits: vec![TokenProfit { token: busd, raw: amount.raw(), amount: Some(amount), usd: Some(profit_usd) }],
+            profit_usd,
+            net_profit_usd: gas_usd.map(|gas| profit_usd - gas),
+        }
+    }
+
+    #[test]
+    fn test_realized_pnl_rollups_and_balances() {
+        let (busd, executor, profit_wallet) = (Address::from_low_u64_be(1), Address::from_low_u64_be(10), Address::from_low_u64_be(20));
+        let registry = Arc::new(TokenRegistry::new());
+        registry.register(56, "BUSD", busd, Some(18));
+        let ledger = Ledger::new(60_000).with_registry(registry).with_chains(chains());
+        ledger.open_position(56, executor, NATIVE_TOKEN, "BNB", TokenAmount::parse("1", 18).unwrap(), Some(600.0));
+
+        ledger.record_trade(56, executor, profit_wallet, "Venus", Some(2), &realized(busd, "25", 25.0, Some(0.9)));
+        ledger.record_trade(56, executor, profit_wallet, "Aave", None, &realized(busd, "10", 10.0, None));
+
+        let report = ledger.report();
+        assert_eq!(report.trades, 2);
+        assert!((report.realized_pnl_usd - 34.1).abs() < 1e-9);
+        assert!((report.realized_by_protocol["Venus"] - 24.1).abs() < 1e-9);
+        assert_eq!(report.realized_by_matrix["none"], 10.0);
+        assert!(report.realized_by_matrix.contains_key("matrix2"));
+        assert_eq!(report.realized_by_day.len(), 1);
+
+        let busd_held = report.positions.iter().find(|p| p.symbol == "BUSD").unwrap();
+        assert_eq!((busd_held.wallet.clone(), busd_held.amount, busd_held.cost_basis_usd), (format!("{:?}", profit_wallet), 35.0, 35.0));
+        // Two trades of 500k gas at 3 gwei from the 1 BNB opening balance
+        let bnb = report.positions.iter().find(|p| p.symbol == "BNB").unwrap();
+        assert!((bnb.amount - 0.997).abs() < 1e-12);
+        assert!((bnb.cost_basis_usd - 598.2).abs() < 1e-9);
+        // No matrix: nothing is marked
+        assert_eq!((report.unrealized_pnl_usd, report.total_pnl_usd), (0.0, report.realized_pnl_usd));
+    }
+
+    #[test]
+    fn test_sweeps_move_basis_and_matrix_marks_inventory() {
+        let mut matrix = Matrix2D::new(vec!["PancakeSwap".into(), "Biswap".into(), "ApeSwap".into()], vec!["WBNB".into(), "WETH".into(), "BUSD".into()]);
+        matrix.update_price("PancakeSwap", "WBNB", 610.0);
+        matrix.update_price("Biswap", "WBNB", 620.0);
+        matrix.update_price("ApeSwap", "WBNB", 900.0);
+        matrix.update_price("PancakeSwap", "BUSD", 1.0);
+        matrix.update_price("PancakeSwap", "WETH", 3_000.0);
+        let now = time_sync::now_millis();
+        assert_eq!(matrix.mark_price("WBNB", 60_000, now), Some(620.0));
+        assert_eq!(matrix.mark_price("WBNB", 60_000, now + 120_000), None);
+        assert_eq!(matrix.mark_price("ETH", 60_000, now), None);
+
+        let ledger = Ledger::new(60_000).with_matrix(Arc::new(Mutex::new(matrix))).with_chains(chains());
+        let (executor, profit_wallet) = (Address::from_low_u64_be(10), Address::from_low_u64_be(20));
+        ledger.open_position(56, executor, NATIVE_TOKEN, "BNB", TokenAmount::parse("2", 18).unwrap(), Some(600.0));
+        let half = TokenAmount::parse("1", 18).unwrap();
+        ledger.record_sweep(56, executor, profit_wallet, NATIVE_TOKEN, half, U256::zero());
+        // Ether on chain 1 is named ETH whatever the caller passed, and marked at WETH
+        ledger.open_position(1, executor, NATIVE_TOKEN, "BNB", TokenAmount::parse("1", 18).unwrap(), Some(3_000.0));
+
+        let report = ledger.report();
+        let eth = report.positions.iter().find(|p| p.chain_id == 1).unwrap();
+        assert_eq!((eth.symbol.as_str(), eth.mark_price_usd), ("ETH", Some(3_000.0)));
+        let swept = report.positions.iter().find(|p| p.chain_id == 56 && p.wallet == format!("{:?}", profit_wallet)).unwrap();
+        assert_eq!((swept.amount, swept.cost_basis_usd, swept.mark_price_usd), (1.0, 600.0, Some(620.0)));
+        assert_eq!(swept.unrealized_pnl_usd, Some(20.0));
+        // Both halves gained $20 marked at the WBNB median
+        assert_eq!(report.realized_pnl_usd, 0.0);
+        assert_eq!(report.unrealized_pnl_usd, 40.0);
+        assert_eq!(ledger.total_pnl_usd(), 40.0);
+    }
+}
@@ -0,0 +1,283 @@
+// Lending market reads for the liquidation helpers: an account's debt and collateral per market,
+// valued at the protocol's own oracle, and the liquidation that repays its largest debt. Covers
+// Compound v2-style comptrollers (Compound, Venus) and Aave V3 pools.
+
+use crate::liquidation_monitor::LiquidationEvent;
+use crate::token_amount::TokenAmount;
+use ethers::abi::Abi;
+use ethers::prelude::*;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
+static COMPTROLLER_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&[
+        "function getAccountLiquidity(address account) view returns (uint256, uint256, uint256)",
+        "function getAssetsIn(address account) view returns (address[])",
+        "function oracle() view returns (address)",
+    ])
+    .expect("ABI parse error")
+});
+
+static CTOKEN_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&[
+        "function getAccountSnapshot(address account) view returns (uint256, uint256, uint256, uint256)",
+        "function underlying() view returns (address)",
+    ])
+    .expect("ABI parse error")
+});
+
+static COMPOUND_ORACLE_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&["function getUnderlyingPrice(address cToken) view returns (uint256)"]).expect("ABI parse error")
+});
+
+static AAVE_PROVIDER_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&[
+        "function getPool() view returns (address)",
+        "function getPriceOracle() view returns (address)",
+        "function getPoolDataProvider() view returns (address)",
+    ])
+    .expect("ABI parse error")
+});
+
+static AAVE_POOL_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&[
+        "function getUserAccountData(address user) view returns (uint256, uint256, uint256, uint256, uint256, uint256)",
+        "function getReservesList() view returns (address[])",
+        "function getUserConfiguration(address user) view returns (uint256)",
+    ])
+    .expect("ABI parse error")
+});
+
+static AAVE_DATA_PROVIDER_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&[
+        "function getUserReserveData(address asset, address user) view returns (uint256, uint256, uint256, uint256, uint256, uint256, uint256, uint40, bool)",
+    ])
+    .expect("ABI parse error")
+});
+
+static AAVE_ORACLE_ABI: Lazy<Abi> = Lazy::new(|| {
+    ethers::abi::parse_abi(&["function getAssetPrice(address asset) view returns (uint256)"]).expect("ABI parse error")
+});
+
+static ERC20_ABI: Lazy<Abi> = Lazy::new(|| {
+    serde_json::from_str(include_str!("abi/ERC20.json")).expect("ABI parse error")
+});
+
+// Aave V3 oracles quote in USD with 8 decimals
+const AAVE_PRICE_DECIMALS: u8 = 8;
+
+/// An account's balances in one market, in whole tokens of the underlying.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketPosition {
+    pub token: Address,
+    pub debt: f64,
+    pub collateral: f64,
+    /// Price of one whole token at the protocol's oracle
+    pub price_usd: f64,
+}
+
+/// Liquidation of `account`: repay its largest debt and seize its largest collateral held in
+/// another token, by oracle value. None unless it has both.
+pub fn liquidation_event(protocol: &str, account: Address, positions: &[MarketPosition]) -> Option<LiquidationEvent> {
+    let debt = positions
+        .iter()
+        .filter(|p| p.debt > 0.0)
+        .max_by(|a, b| (a.debt * a.price_usd).total_cmp(&(b.debt * b.price_usd)))?;
+    let collateral = positions
+        .iter()
+        .filter(|p| p.collateral > 0.0 && p.token != debt.token)
+        .max_by(|a, b| (a.collateral * a.price_usd).total_cmp(&(b.collateral * b.price_usd)))?;
+    Some(LiquidationEvent {
+        protocol: protocol.to_string(),
+        account: format!("{:?}", account),
+        debt: debt.debt,
+        collateral: collateral.collateral,
+        debt_token: Some(debt.token),
+        collateral_token: Some(collateral.token),
+        observed_at_ms: crate::time_sync::now_millis(),
+    })
+}
+
+async fn decimals<M: Middleware + 'static>(client: Arc<M>, token: Address) -> Result<u8, ContractError<M>> {
+    Contract::new(token, ERC20_ABI.clone(), client).method::<_, u8>("decimals", ())?.call().await
+}
+
+/// Positions of `account` in a Compound v2-style comptroller, or None while it has no
+/// shortfall. Markets without `underlying()` hold the native coin and are reported as
+/// `wrapped_native`.
+pub async fn compound_positions<M: Middleware + 'static>(
+    client: Arc<M>,
+    comptroller: Address,
+    account: Address,
+    wrapped_native: Address,
+) -> Result<Option<Vec<MarketPosition>>, ContractError<M>> {
+    let comptroller = Contract::new(comptroller, COMPTROLLER_ABI.clone(), client.clone());
+    let (_, _, shortfall): (U256, U256, U256) = comptroller.method("getAccountLiquidity", account)?.call().await?;
+    if shortfall.is_zero() {
+        return Ok(None);
+    }
+    let markets: Vec<Address> = comptroller.method("getAssetsIn", account)?.call().await?;
+    let oracle: Address = comptroller.method("oracle", ())?.call().await?;
+    let oracle = Contract::new(oracle, COMPOUND_ORACLE_ABI.clone(), client.clone());
+    let mut positions = Vec::with_capacity(markets.len());
+    for market in markets {
+        let ctoken = Contract::new(market, CTOKEN_ABI.clone(), client.clone());
+        let (_, ctoken_balance, borrowed, exchange_rate): (U256, U256, U256, U256) =
+            ctoken.method("getAccountSnapshot", account)?.call().await?;
+        let (token, token_decimals) = match ctoken.method::<_, Address>("underlying", ())?.call().await {
+            Ok(token) => (token, decimals(client.clone(), token).await?),
+            Err(_) => (wrapped_native, 18),
+        };
+        // Scaled by 1e(36 - underlying decimals)
+        let price: U256 = oracle.method("getUnderlyingPrice", market)?.call().await?;
+        let supplied = ctoken_balance.full_mul(exchange_rate) / U512::exp10(18);
+        positions.push(MarketPosition {
+            token,
+            debt: TokenAmount::new(borrowed, token_decimals).to_f64(),
+            collateral: TokenAmount::new(U256::try_from(supplied).unwrap_or(U256::MAX), token_decimals).to_f64(),
+            price_usd: TokenAmount::new(price, 36u8.saturating_sub(token_decimals)).to_f64(),
+        });
+    }
+    Ok(Some(positions))
+}
+
+/// Positions of `account` in the Aave V3 pool behind `addresses_provider`, or None while its
+/// health factor is at least 1. Only reserves it borrows or uses as collateral are read.
+pub async fn aave_positions<M: Middleware + 'static>(
+    client: Arc<M>,
+    addresses_provider: Address,
+    account: Address,
+) -> Result<Option<Vec<MarketPosition>>, ContractError<M>> {
+    let provider = Contract::new(addresses_provider, AAVE_PROVIDER_ABI.clone(), client.clone());
+    let pool: Address = provider.method("getPool", ())?.call().await?;
+    let pool = Contract::new(pool, AAVE_POOL_ABI.clone(), client.clone());
+    let (_, _, _, _, _, health_factor): (U256, U256, U256, U256, U256, U256) =
+        pool.method("getUserAccountData", account)?.call().await?;
+    if health_factor >= U256::exp10(18) {
+        return Ok(None);
+    }
+    let reserves: Vec<Address> = pool.method("getReservesList", ())?.call().await?;
+    // Two bits per reserve (so at most 128): borrowing, then used as collateral
+    let config: U256 = pool.method("getUserConfiguration", account)?.call().await?;
+    let data_provider: Address = provider.method("getPoolDataProvider", ())?.call().await?;
+    let data_provider = Contract::new(data_provider, AAVE_DATA_PROVIDER_ABI.clone(), client.clone());
+    let oracle: Address = provider.method("getPriceOracle", ())?.call().await?;
+    let oracle = Contract::new(oracle, AAVE_ORACLE_ABI.clone(), client.clone());
+    let mut positions = Vec::new();
+    for (index, reserve) in reserves.into_iter().enumerate().take(128) {
+        let (borrowing, collateral) = (config.bit(2 * index), config.bit(2 * index + 1));
+        if !borrowing && !collateral {
+            continue;
+        }
+        let (supplied, stable_debt, variable_debt, _, _, _, _, _, _): (U256, U256, U256, U256, U256, U256, U256, u64, bool) =
+            data_provider.method("getUserReserveData", (reserve, account))?.call().await?;
+        let token_decimals = decimals(client.clone(), reserve).await?;
+        let price: U256 = oracle.method("getAssetPrice", reserve)?.call().await?;
+        positions.push(MarketPosition {
+            token: reserve,
+            debt: TokenAmount::new(stable_debt.saturating_add(variable_debt), token_decimals).to_f64(),
+            collateral: if collateral { TokenAmount::new(supplied, token_decimals).to_f64() } else { 0.0 },
+            price_usd: TokenAmount::new(price, AAVE_PRICE_DECIMALS).to_f64(),
+        });
+    }
+    Ok(Some(positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_registry::ChainConfig;
+    use crate::config::Settings;
+    use crate::execution_planner::ExecutionPlanner;
+    use crate::flashloan::{FlashloanCallback, FlashloanQuote};
+    use crate::liquidity_cache::LiquidityCache;
+    use crate::token_registry::{TokenRegistry, BSC_MAINNET_CHAIN_ID};
+    use ethers::abi::Token;
+    use std::time::Duration;
+
+    fn position(token: u64, debt: f64, collateral: f64, price_usd: f64) -> MarketPosition {
+        MarketPosition { token: Address::from_low_u64_be(token), debt, collateral, price_usd }
+    }
+
+    fn encode(tokens: &[Token]) -> Bytes {
+        Bytes::from(ethers::abi::encode(tokens))
+    }
+
+    fn uint(value: U256) -> Token {
+        Token::Uint(value)
+    }
+
+    #[test]
+    fn test_liquidation_event_picks_largest_positions() {
+        let positions = [
+            position(1, 100.0, 0.0, 1.0),
+            position(2, 1.0, 0.5, 600.0),
+            position(3, 0.0, 5.0, 2_000.0),
+        ];
+        let event = liquidation_event("Venus", Address::from_low_u64_be(9), &positions).unwrap();
+        // 1 token 2 ($600) outweighs 100 token 1 ($100)
+        assert_eq!(event.debt_token, Some(Address::from_low_u64_be(2)));
+        assert_eq!(event.debt, 1.0);
+        assert_eq!(event.collateral_token, Some(Address::from_low_u64_be(3)));
+        assert_eq!(event.collateral, 5.0);
+
+        // Collateral only in the debt token: nothing to swap into
+        assert!(liquidation_event("Venus", Address::from_low_u64_be(9), &[position(2, 1.0, 3.0, 600.0)]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_venus_positions_plan_liquidation() {
+        let e18 = U256::exp10(18);
+        let (account, comptroller, oracle) = (Address::from_low_u64_be(9), Address::from_low_u64_be(0xc0), Address::from_low_u64_be(0x0c));
+        let (v_busd, v_eth) = (Address::from_low_u64_be(0xa1), Address::from_low_u64_be(0xa2));
+        let (busd, eth, wbnb) = (Address::from_low_u64_be(0xb0), Address::from_low_u64_be(0xe0), Address::from_low_u64_be(0xbb));
+
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let responses = [
+            // getAccountLiquidity: (error, liquidity, shortfall)
+            encode(&[uint(U256::zero()), uint(U256::zero()), uint(e18)]),
+            encode(&[Token::Array(vec![Token::Address(v_busd), Token::Address(v_eth)])]),
+            encode(&[Token::Address(oracle)]),
+            // vBUSD: 500 BUSD borrowed at $1
+            encode(&[uint(U256::zero()), uint(U256::zero()), uint(e18 * 500), uint(e18)]),
+            encode(&[Token::Address(busd)]),
+            encode(&[uint(U256::from(18))]),
+            encode(&[uint(e18)]),
+            // vETH: 500 vETH of 0.02 ETH each supplied, ETH at $2000
+            encode(&[uint(U256::zero()), uint(U256::from(500u64) * U256::exp10(8)), uint(U256::zero()), uint(U256::from(2u64) * U256::exp10(26))]),
+            encode(&[Token::Address(eth)]),
+            encode(&[uint(U256::from(18))]),
+            encode(&[uint(e18 * 2_000)]),
+        ];
+        // Responses are popped LIFO
+        for response in responses.into_iter().rev() {
+            mock.push::<Bytes, _>(response).unwrap();
+        }
+        let positions = compound_positions(client.clone(), comptroller, account, wbnb).await.unwrap().unwrap();
+        let event = liquidation_event("Venus", account, &positions).unwrap();
+        assert_eq!((event.debt_token, event.collateral_token), (Some(busd), Some(eth)));
+        assert_eq!((event.debt, event.collateral), (500.0, 10.0));
+
+        // The event plans a BUSD -> ETH -> BUSD round trip on a BUSD flashloan
+        let settings = Settings {
+            dexes: vec!["PancakeSwap".to_string()],
+            router_pancakeswap: format!("{:?}", Address::from_low_u64_be(1)),
+            max_liquidity_utilization_stable: 100.0,
+            ..Default::default()
+        };
+        let registry = Arc::new(TokenRegistry::new());
+        registry.register(BSC_MAINNET_CHAIN_ID, "BUSD", busd, Some(18));
+        let cache = Arc::new(LiquidityCache::new(Duration::from_secs(60), Duration::from_secs(60)));
+        let lender = Address::from_low_u64_be(99);
+        cache.insert(FlashloanQuote::new("Balancer", lender, busd, U256::zero(), e18 * 1_000_000, (U256::zero(), U256::one()), FlashloanCallback::BalancerReceiveFlashLoan));
+        let chain = ChainConfig { chain_id: BSC_MAINNET_CHAIN_ID, ..Default::default() };
+        let planner = ExecutionPlanner::new(Arc::new(settings), registry, &chain).with_liquidity_cache(cache);
+        mock.push::<Bytes, _>(encode(&[Token::Array(vec![uint(e18 / 4), uint(e18 * 501)])])).unwrap();
+        mock.push::<Bytes, _>(encode(&[Token::Array(vec![uint(e18 * 500), uint(e18 / 4)])])).unwrap();
+        let plan = planner.plan_liquidation(client, &event).await.unwrap();
+        assert_eq!((plan.flashloan_provider, plan.loan_token, plan.loan_amount), (lender, busd, e18 * 500));
+        assert_eq!(plan.swap_paths, vec![vec![busd, eth], vec![eth, busd]]);
+        assert_eq!(plan.expected_profit, e18);
+    }
+}
@@ -12,8 +12,32 @@ pub mod arbitrage_executor_address;
 pub mod execute_arbitrage;
 pub mod liquidation_monitor;
 pub mod liquidation_monitor_real;
+pub mod lending_markets;
 pub mod execution_log;
 pub mod ai_controller;
 pub mod shared_state;
 pub mod optimizer_ai;
 pub mod events;
+pub mod time_sync;
+pub mod token_registry;
+pub mod failover_transport;
+pub mod quorum;
+pub mod provider_health;
+pub mod quota_store;
+pub mod chain_registry;
+pub mod nonce_manager;
+pub mod gas_oracle;
+pub mod secrets;
+pub mod remote_signer;
+pub mod wallet_pool;
+pub mod simulation;
+pub mod execution_planner;
+pub mod tx_tracker;
+pub mod bundles;
+pub mod pre_execution;
+pub mod receipt_processor;
+pub mod liquidity_cache;
+pub mod token_amount;
+pub mod risk_manager;
+pub mod profit_sweeper;
+pub mod ledger;
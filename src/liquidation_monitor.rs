@@ -1,6 +1,7 @@
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use crate::shared_state::SharedState;
+use ethers::types::Address;
 
 #[derive(Debug, Clone)]
 pub struct LiquidationEvent {
@@ -8,6 +9,12 @@ pub struct LiquidationEvent {
     pub account: String,
     pub debt: f64,
     pub collateral: f64,
+    /// Borrowed token to repay, when the protocol helper resolved it
+    pub debt_token: Option<Address>,
+    /// Collateral token seized, when the protocol helper resolved it
+    pub collateral_token: Option<Address>,
+    /// When the helper observed the position (SNTP-corrected unix millis)
+    pub observed_at_ms: u64,
 }
 
 use tokio::sync::mpsc;
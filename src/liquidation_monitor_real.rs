@@ -4,21 +4,32 @@ use std::sync::Arc;
 
 
 
-use ethers::types::Address;
+use ethers::types::{Address, U256};
 
-use log::{info, error};
-
-use std::env;
+use log::{info, warn, error};
 
 
 use crate::liquidation_monitor::{LiquidationEvent, LiquidationExecutor};
-use crate::arbitrage_executor_address::ARBITRAGE_EXECUTOR_MAINNET;
-use crate::execute_arbitrage::execute_arbitrage_onchain;
+use crate::lending_markets::{aave_positions, compound_positions, liquidation_event};
+use crate::execute_arbitrage::{execute_arbitrage_onchain, ExecutionOptions};
 use tokio::sync::mpsc;
 use tokio;
 
 
-use crate::execution_log::{ExecutionLog, ExecutionRecord};
+use crate::execution_log::{ExecutionLog, ExecutionRecord, TxStatus};
+use crate::tx_tracker::{TxOutcome, TxTracker};
+use crate::receipt_processor::ReceiptProcessor;
+use crate::risk_manager::{RiskManager, TradeOutcome};
+use crate::ledger::Ledger;
+use crate::bundles::BundleSubmitter;
+use crate::pre_execution::{CheckPipeline, ExecutionContext};
+use crate::execution_planner::ExecutionPlanner;
+use crate::simulation::ProfitCheck;
+use crate::nonce_manager::NonceManager;
+use crate::gas_oracle::GasOracle;
+use crate::wallet_pool::{WalletLease, WalletPool};
+use crate::providers::{QuorumProvider, SignerHttpProvider};
+use crate::time_sync::TimeSync;
 
 
 #[derive(Clone)]
@@ -26,32 +37,125 @@ pub struct RealArbitrageExecutor {
     pub abi_path: String,
     pub profit_wallet: String,
     pub execution_log: Arc<ExecutionLog>,
+    // Turns events into executeArbitrage arguments (flashloan, routers, paths, amounts)
+    pub planner: Arc<ExecutionPlanner>,
+    // Executor wallets; each submission leases one for its nonce and pending slot
+    pub wallet_pool: Arc<WalletPool>,
+    pub nonce_manager: Arc<NonceManager>,
+    pub gas_oracle: Arc<GasOracle>,
+    // Follows submissions to confirmation and caps pending transactions; the wallet lease is
+    // held until it reports the transaction final
+    pub tracker: Arc<TxTracker>,
+    // Private bundle submission (`bundle_submission_enabled`); public mempool when None
+    pub bundles: Option<Arc<BundleSubmitter>>,
+    // Pre-execution checks run right before sending (`pre_execution_checks`)
+    pub checks: Option<Arc<CheckPipeline>>,
+    // Realized profit and gas from receipts of tracked transactions
+    pub receipts: Option<Arc<ReceiptProcessor>>,
+    // Loss limits and circuit breaker, consulted before every execution
+    pub risk: Option<Arc<RiskManager>>,
+    // Inventory and realized PnL from receipts
+    pub ledger: Option<Arc<Ledger>>,
+    // Rejects events whose timestamps the corrected clock can't vouch for
+    pub time_sync: Option<Arc<TimeSync>>,
+    // Quorum provider for critical reads; the wallet's own client when None
+    pub quorum: Option<Arc<QuorumProvider>>,
 }
 
 impl RealArbitrageExecutor {
-    pub fn new(abi_path: String, profit_wallet: String, execution_log: Arc<ExecutionLog>) -> Self {
-        Self { abi_path, profit_wallet, execution_log }
+    pub fn new(
+        abi_path: String,
+        profit_wallet: String,
+        planner: Arc<ExecutionPlanner>,
+        wallet_pool: Arc<WalletPool>,
+        nonce_manager: Arc<NonceManager>,
+        gas_oracle: Arc<GasOracle>,
+        tracker: Arc<TxTracker>,
+    ) -> Self {
+        // Submissions are logged where the tracker writes their final status
+        let execution_log = tracker.execution_log().clone();
+        Self { abi_path, profit_wallet, execution_log, planner, wallet_pool, nonce_manager, gas_oracle, tracker, bundles: None, checks: None, receipts: None, risk: None, ledger: None, time_sync: None, quorum: None }
+    }
+
+    /// Submit through bundle relays instead of the public mempool
+    pub fn with_bundles(mut self, bundles: Arc<BundleSubmitter>) -> Self {
+        self.bundles = Some(bundles);
+        self
+    }
+
+    /// Run `checks` before every send
+    pub fn with_checks(mut self, checks: Arc<CheckPipeline>) -> Self {
+        self.checks = Some(checks);
+        self
+    }
+
+    /// Fill in realized profit and gas from receipts once tracked transactions are mined
+    pub fn with_receipts(mut self, receipts: Arc<ReceiptProcessor>) -> Self {
+        self.receipts = Some(receipts);
+        self
+    }
+
+    /// Check every execution against `risk` and report its outcome back
+    pub fn with_risk_manager(mut self, risk: Arc<RiskManager>) -> Self {
+        self.risk = Some(risk);
+        self
+    }
+
+    /// Book realized executions in `ledger` (needs `with_receipts`)
+    pub fn with_ledger(mut self, ledger: Arc<Ledger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Validate event timestamps against the SNTP-corrected clock before executing
+    pub fn with_time_sync(mut self, time_sync: Arc<TimeSync>) -> Self {
+        self.time_sync = Some(time_sync);
+        self
+    }
+
+    /// Read quotes, prices, nonces and receipts through `quorum` instead of the wallet's client
+    pub fn with_quorum(mut self, quorum: Arc<QuorumProvider>) -> Self {
+        self.quorum = Some(quorum);
+        self
+    }
+
+    // USD value of a raw amount of `token` at `price_usd`
+    fn usd(&self, token: Address, raw: U256, price_usd: Option<f64>) -> Option<f64> {
+        self.planner.token_registry().amount(self.planner.chain_id(), token, raw).ok()?.usd(price_usd?)
+    }
+
+    fn log_failure(&self, event: &LiquidationEvent, error: String) {
+        self.execution_log.log(ExecutionRecord {
+            timestamp: chrono::Utc::now(),
+            protocol: event.protocol.clone(),
+            account: event.account.clone(),
+            debt: event.debt,
+            collateral: event.collateral,
+            success: false,
+            profit: 0.0,
+            gas_used: None,
+            gas_cost_native: None,
+            tx_hash: None,
+            error: Some(error),
+            status: TxStatus::Failed,
+            transfer: None,
+        });
     }
 }
 
 #[async_trait]
 impl LiquidationExecutor for RealArbitrageExecutor {
     async fn execute(&self, event: &LiquidationEvent) {
+        let started = std::time::Instant::now();
         let dry_run = std::env::var("DRY_RUN").unwrap_or_else(|_| "true".to_string()) == "true";
         info!("[RealArbitrageExecutor] {} liquidation for {} on {} (dry_run={})", if dry_run {"Simulating"} else {"Executing"}, event.account, event.protocol, dry_run);
-
-        // Example: Map event fields to contract call params (these should be replaced with real logic)
-        let contract_address = ARBITRAGE_EXECUTOR_MAINNET.parse().expect("Invalid contract address");
-        let abi_path = &self.abi_path;
-        let flashloan_client = "0x0000000000000000000000000000000000000000".parse().unwrap(); // TODO
-        let loan_token = "0x0000000000000000000000000000000000000000".parse().unwrap(); // TODO
-        let loan_amount = ethers::types::U256::from((event.debt * 1e18) as u128); // Example
-        let routers = vec![]; // TODO
-        let swap_paths = vec![]; // TODO
-        let amounts_in = vec![]; // TODO
-        let amounts_out_min = vec![]; // TODO
-        let private_key = env::var("PRIVATE_KEY").expect("PRIVATE_KEY not set");
-        let rpc_url = env::var("BSC_RPC_URL").unwrap_or_else(|_| "https://bsc-dataseed.binance.org/".to_string());
+        if let Some(time_sync) = &self.time_sync
+            && let Err(e) = time_sync.validate_timestamp(event.observed_at_ms)
+        {
+            warn!("[RealArbitrageExecutor] Rejecting liquidation for {}: {}", event.account, e);
+            self.log_failure(event, format!("{}", e));
+            return;
+        }
 
         if dry_run {
             info!("[DRY_RUN] Would call execute_arbitrage_onchain with: account={} debt={} collateral={}", event.account, event.debt, event.collateral);
@@ -64,25 +168,140 @@ impl LiquidationExecutor for RealArbitrageExecutor {
                 success: true,
                 profit: 0.0,
                 gas_used: None,
+                gas_cost_native: None,
                 tx_hash: None,
                 error: None,
+                status: TxStatus::Simulated,
+                transfer: None,
             });
             return;
         }
-        match execute_arbitrage_onchain(
-            contract_address,
-            abi_path,
-            flashloan_client,
-            loan_token,
-            loan_amount,
-            routers,
-            swap_paths,
-            amounts_in,
-            amounts_out_min,
-            &private_key,
-            &rpc_url,
-        ).await {
-            Ok(tx_hash) => {
+        if let Some(risk) = &self.risk
+            && let Err(violation) = risk.check_ready()
+        {
+            warn!("[RealArbitrageExecutor] Skipping liquidation for {}: {}", event.account, violation);
+            self.log_failure(event, format!("{}", violation));
+            return;
+        }
+        // Held until the transaction is final so at most max_pending_transactions are in flight
+        let _slot = self.tracker.reserve().await;
+        let chain_id = self.planner.chain_id();
+        let lease = match self.wallet_pool.acquire(chain_id, None) {
+            Ok(lease) => lease,
+            Err(e) => {
+                error!("[RealArbitrageExecutor] {}", e);
+                self.log_failure(event, format!("{}", e));
+                return;
+            }
+        };
+        let Some(client) = lease.client(chain_id) else {
+            self.log_failure(event, format!("Wallet {} has no client for chain {}", lease.wallet().label, chain_id));
+            return;
+        };
+        match self.quorum.clone() {
+            Some(reads) => self.submit(event, started, &lease, client, reads).await,
+            None => self.submit(event, started, &lease, client.clone(), client).await,
+        }
+    }
+}
+
+impl RealArbitrageExecutor {
+    // Plan, check, simulate and send the liquidation from `lease`. `client` signs and sends;
+    // critical reads (quotes, prices, nonces, receipts) go through `reads`.
+    async fn submit<R: ethers::providers::Middleware + 'static>(
+        &self,
+        event: &LiquidationEvent,
+        started: std::time::Instant,
+        lease: &WalletLease,
+        client: Arc<SignerHttpProvider>,
+        reads: Arc<R>,
+    ) {
+        let chain_id = self.planner.chain_id();
+        let abi_path = &self.abi_path;
+        let contract_address = match self.planner.executor() {
+            Ok(address) => address,
+            Err(e) => {
+                error!("[RealArbitrageExecutor] {}", e);
+                self.log_failure(event, format!("{}", e));
+                return;
+            }
+        };
+        let plan = match self.planner.plan_liquidation(reads.clone(), event).await {
+            Ok(plan) => plan,
+            Err(e) => {
+                error!("[RealArbitrageExecutor] Cannot plan liquidation for {}: {}", event.account, e);
+                self.log_failure(event, format!("{}", e));
+                return;
+            }
+        };
+        let fees = match self.gas_oracle.current(&*client).await {
+            Ok(fees) => fees,
+            Err(e) => {
+                error!("[RealArbitrageExecutor] No gas price available: {}", e);
+                self.log_failure(event, format!("{}", e));
+                return;
+            }
+        };
+        // The loan token is priced once, for the risk limits and the pre-execution checks
+        let loan_price_usd = self.planner.price_usd(reads.clone(), plan.loan_token).await;
+        let notional_usd = self.usd(plan.loan_token, plan.loan_amount, loan_price_usd);
+        // Reserves the notional; every path from here on records an outcome to release it
+        if let Some(risk) = &self.risk
+            && let Err(violation) = risk.check_trade(plan.loan_token, notional_usd)
+        {
+            warn!("[RealArbitrageExecutor] Risk check rejected liquidation for {}: {}", event.account, violation);
+            self.log_failure(event, format!("{}", violation));
+            return;
+        }
+        // The contract pays profit out to the profit wallet; simulate against its balance
+        let profit_check = self.profit_wallet.parse::<Address>().ok().map(|recipient| ProfitCheck {
+            token: plan.loan_token,
+            recipient,
+            min_profit: plan.min_profit(),
+        });
+        let sender = lease.address();
+        let nonce = match self.nonce_manager.next_nonce(&*reads, chain_id, sender).await {
+            Ok(nonce) => nonce,
+            Err(e) => {
+                error!("[RealArbitrageExecutor] Cannot allocate nonce: {}", e);
+                self.log_failure(event, format!("{}", e));
+                if let Some(risk) = &self.risk {
+                    risk.record(plan.loan_token, notional_usd, TradeOutcome::Rejected);
+                }
+                return;
+            }
+        };
+        let mut tokens = vec![plan.loan_token];
+        for token in plan.swap_paths.iter().flatten() {
+            if !tokens.contains(token) {
+                tokens.push(*token);
+            }
+        }
+        let context = ExecutionContext {
+            chain_id,
+            observed_at_ms: event.observed_at_ms,
+            assembly_started: Some(started),
+            tokens,
+            expected_profit_usd: self.usd(plan.loan_token, plan.expected_profit, loan_price_usd),
+            exposure_usd: notional_usd,
+            native_price_usd: self.planner.native_price_usd(),
+            sender_balance: lease.wallet().balance(chain_id),
+            max_gas_cost: None,
+            simulation: None,
+        };
+        let options = ExecutionOptions {
+            nonce,
+            fees,
+            profit_check,
+            bundles: self.bundles.clone(),
+            checks: self.checks.clone(),
+            context,
+        };
+        let loan_token = plan.loan_token;
+        let result = execute_arbitrage_onchain(contract_address, abi_path, &plan, client.clone(), &options).await;
+        match result {
+            Ok(submitted) => {
+                let tx_hash = submitted.tx_hash;
                 info!("[RealArbitrageExecutor] Submitted liquidation tx: 0x{:x}", tx_hash);
                 self.execution_log.log(ExecutionRecord {
                     timestamp: chrono::Utc::now(),
@@ -90,27 +309,56 @@ impl LiquidationExecutor for RealArbitrageExecutor {
                     account: event.account.clone(),
                     debt: event.debt,
                     collateral: event.collateral,
-                    success: true,
+                    success: false,
                     profit: 0.0,
                     gas_used: None,
+                    gas_cost_native: None,
                     tx_hash: Some(format!("0x{:x}", tx_hash)),
                     error: None,
+                    status: TxStatus::Pending,
+                    transfer: None,
                 });
+                // The lease (and with it the wallet's pending count) is held until the tracker
+                // reports the transaction final
+                let outcome = self.tracker.track(client.clone(), submitted.tx, tx_hash).await;
+                info!("[RealArbitrageExecutor] Liquidation tx 0x{:x} finished: {:?}", tx_hash, outcome);
+                let realized = match (&self.receipts, &outcome) {
+                    (Some(receipts), TxOutcome::Confirmed { tx_hash, .. } | TxOutcome::Reverted { tx_hash, .. }) => {
+                        receipts.record(reads.clone(), *tx_hash).await
+                    }
+                    _ => None,
+                };
+                if let (Some(ledger), Some(realized), Ok(profit_wallet)) =
+                    (&self.ledger, &realized, self.profit_wallet.parse::<Address>())
+                {
+                    ledger.record_trade(chain_id, sender, profit_wallet, &event.protocol, None, realized);
+                }
+                if let Some(risk) = &self.risk {
+                    let pnl_usd = realized.map(|r| r.net_profit_usd.unwrap_or(r.profit_usd)).unwrap_or(0.0);
+                    let trade = match outcome {
+                        TxOutcome::Confirmed { .. } => TradeOutcome::Executed { pnl_usd },
+                        TxOutcome::Reverted { .. } => TradeOutcome::Reverted { pnl_usd },
+                        TxOutcome::Cancelled { .. } | TxOutcome::Dropped => TradeOutcome::Failed,
+                    };
+                    risk.record(loan_token, notional_usd, trade);
+                }
             }
             Err(e) => {
                 error!("[RealArbitrageExecutor] Error executing liquidation: {}", e);
-                self.execution_log.log(ExecutionRecord {
-                    timestamp: chrono::Utc::now(),
-                    protocol: event.protocol.clone(),
-                    account: event.account.clone(),
-                    debt: event.debt,
-                    collateral: event.collateral,
-                    success: false,
-                    profit: 0.0,
-                    gas_used: None,
-                    tx_hash: None,
-                    error: Some(format!("{}", e)),
-                });
+                if e.is_nonce_error() {
+                    // The node disagrees with our counter; take its view
+                    if let Err(resync_err) = self.nonce_manager.resync(&*reads, chain_id, sender).await {
+                        error!("[RealArbitrageExecutor] Nonce resync failed: {}", resync_err);
+                    }
+                } else {
+                    // Nothing went out with this nonce; hand it out again
+                    self.nonce_manager.release(chain_id, sender, nonce).await;
+                }
+                self.log_failure(event, e.to_string());
+                // Nothing was mined: a rejection, not a failed transaction
+                if let Some(risk) = &self.risk {
+                    risk.record(loan_token, notional_usd, TradeOutcome::Rejected);
+                }
             }
         }
     }
@@ -119,6 +367,9 @@ impl LiquidationExecutor for RealArbitrageExecutor {
 pub struct VenusHelper {
     pub sender: mpsc::Sender<LiquidationEvent>,
     pub client: Arc<crate::providers::SignerHttpProvider>,
+    // Venus comptroller and the chain's wrapped native token (vBNB's underlying)
+    pub comptroller: Address,
+    pub wrapped_native: Address,
 }
 
 impl VenusHelper {
@@ -141,15 +392,14 @@ impl VenusHelper {
             match fetch_venus_users_from_graph().await {
                 Ok(users) => {
                     for user in users {
-                        // Placeholder: simulate random opportunity
-                        if rand::random::<u8>() % 20 == 0 {
-                            let event = LiquidationEvent {
-                                protocol: "Venus".to_string(),
-                                account: format!("{:?}", user),
-                                debt: 100.0,
-                                collateral: 200.0,
-                            };
-                            let _ = self.sender.send(event).await;
+                        match compound_positions(self.client.clone(), self.comptroller, user, self.wrapped_native).await {
+                            Ok(Some(positions)) => {
+                                if let Some(event) = liquidation_event("Venus", user, &positions) {
+                                    let _ = self.sender.send(event).await;
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => log::debug!("[VenusHelper] Reading positions of {:?} failed: {}", user, e),
                         }
                     }
                 },
@@ -164,6 +414,8 @@ impl VenusHelper {
 pub struct AaveHelper {
     pub sender: mpsc::Sender<LiquidationEvent>,
     pub client: Arc<crate::providers::SignerHttpProvider>,
+    // Aave V3 PoolAddressesProvider
+    pub addresses_provider: Address,
 }
 
 impl AaveHelper {
@@ -186,15 +438,14 @@ impl AaveHelper {
             match fetch_aave_users_from_graph().await {
                 Ok(users) => {
                     for user in users {
-                        // Placeholder: simulate random opportunity
-                        if rand::random::<u8>() % 20 == 0 {
-                            let event = LiquidationEvent {
-                                protocol: "Aave".to_string(),
-                                account: format!("{:?}", user),
-                                debt: 100.0,
-                                collateral: 200.0,
-                            };
-                            let _ = self.sender.send(event).await;
+                        match aave_positions(self.client.clone(), self.addresses_provider, user).await {
+                            Ok(Some(positions)) => {
+                                if let Some(event) = liquidation_event("Aave", user, &positions) {
+                                    let _ = self.sender.send(event).await;
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => log::debug!("[AaveHelper] Reading positions of {:?} failed: {}", user, e),
                         }
                     }
                 },
@@ -212,6 +463,9 @@ impl AaveHelper {
 pub struct CompoundHelper {
     pub sender: mpsc::Sender<LiquidationEvent>,
     pub client: Arc<crate::providers::SignerHttpProvider>,
+    // Compound comptroller and the chain's wrapped native token (cETH's underlying)
+    pub comptroller: Address,
+    pub wrapped_native: Address,
 }
 
 impl CompoundHelper {
@@ -234,15 +488,14 @@ impl CompoundHelper {
             match fetch_compound_users_from_graph().await {
                 Ok(users) => {
                     for user in users {
-                        // Placeholder: simulate random opportunity
-                        if rand::random::<u8>() % 20 == 0 {
-                            let event = LiquidationEvent {
-                                protocol: "Compound".to_string(),
-                                account: format!("{:?}", user),
-                                debt: 100.0,
-                                collateral: 200.0,
-                            };
-                            let _ = self.sender.send(event).await;
+                        match compound_positions(self.client.clone(), self.comptroller, user, self.wrapped_native).await {
+                            Ok(Some(positions)) => {
+                                if let Some(event) = liquidation_event("Compound", user, &positions) {
+                                    let _ = self.sender.send(event).await;
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => log::debug!("[CompoundHelper] Reading positions of {:?} failed: {}", user, e),
                         }
                     }
                 },
@@ -0,0 +1,188 @@
+// Flashloan liquidity cache: quotes of every (source, token) pair in use are refreshed in the
+// background every `refresh_liquidity_interval_ms` and expire after `liquidity_cache_ttl_ms`,
+// so the execution planner can pick a flashloan source without a network round trip. A token
+// is "in use" once it has been looked up; the largest amount wanted of it is what gets quoted.
+
+use crate::config::Settings;
+use crate::flashloan::{cheapest_quote, BorrowLimits, FlashloanQuote, FlashloanSource};
+use ethers::prelude::*;
+use futures::future::join_all;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+struct CachedQuote {
+    quote: FlashloanQuote,
+    fetched_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct LiquidityCache {
+    ttl: Duration,
+    refresh_interval: Duration,
+    // Keyed by (source name, token)
+    quotes: RwLock<HashMap<(String, Address), CachedQuote>>,
+    // Tokens in use and the largest amount looked up of each
+    wanted: RwLock<HashMap<Address, U256>>,
+}
+
+impl LiquidityCache {
+    pub fn new(ttl: Duration, refresh_interval: Duration) -> Self {
+        Self {
+            ttl,
+            refresh_interval,
+            quotes: RwLock::new(HashMap::new()),
+            wanted: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            Duration::from_millis(settings.liquidity_cache_ttl_ms),
+            Duration::from_millis(settings.refresh_liquidity_interval_ms),
+        )
+    }
+
+    /// Cheapest cached, unexpired source for borrowing `amount` of `token` within `limits`.
+    /// Never touches the network; the token is tracked for background refresh from now on.
+    pub fn get(&self, token: Address, amount: U256, limits: &BorrowLimits) -> Option<FlashloanQuote> {
+        self.track(token, amount);
+        let now = Instant::now();
+        let quotes: Vec<FlashloanQuote> = self
+            .quotes
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|((_, t), cached)| *t == token && now.duration_since(cached.fetched_at) < self.ttl)
+            .map(|(_, cached)| cached.quote.clone().with_amount(amount))
+            .collect();
+        cheapest_quote(quotes, amount, limits)
+    }
+
+    /// Track `token` for refresh, quoting at least `amount`.
+    pub fn track(&self, token: Address, amount: U256) {
+        let mut wanted = self.wanted.write().unwrap();
+        let entry = wanted.entry(token).or_default();
+        *entry = (*entry).max(amount);
+    }
+
+    pub fn insert(&self, quote: FlashloanQuote) {
+        let key = (quote.source.clone(), quote.token);
+        self.quotes.write().unwrap().insert(key, CachedQuote { quote, fetched_at: Instant::now() });
+    }
+
+    /// Number of unexpired quotes.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.quotes.read().unwrap().values().filter(|c| now.duration_since(c.fetched_at) < self.ttl).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Quote `amount` of `token` from every source and cache the results.
+    pub async fn refresh_token<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        sources: &[Box<dyn FlashloanSource<M>>],
+        token: Address,
+        amount: U256,
+    ) {
+        let quotes = join_all(sources.iter().map(|source| source.quote(client.clone(), token, amount))).await;
+        for (source, quote) in sources.iter().zip(quotes) {
+            match quote {
+                Some(quote) => self.insert(quote),
+                None => debug!("[LiquidityCache] {} has no quote for {:?}", source.name(), token),
+            }
+        }
+    }
+
+    /// Refresh every tracked token and drop expired quotes.
+    pub async fn refresh<M: Middleware + 'static>(&self, client: Arc<M>, sources: &[Box<dyn FlashloanSource<M>>]) {
+        let wanted: Vec<(Address, U256)> = self.wanted.read().unwrap().iter().map(|(t, a)| (*t, *a)).collect();
+        for (token, amount) in wanted {
+            self.refresh_token(client.clone(), sources, token, amount).await;
+        }
+        let now = Instant::now();
+        self.quotes.write().unwrap().retain(|_, cached| now.duration_since(cached.fetched_at) < self.ttl);
+    }
+
+    /// Refresh every `refresh_liquidity_interval_ms`, forever.
+    pub async fn run<M: Middleware + 'static>(self: Arc<Self>, client: Arc<M>, sources: Vec<Box<dyn FlashloanSource<M>>>) {
+        if sources.is_empty() {
+            warn!("[LiquidityCache] No flashloan sources configured; nothing to refresh");
+            return;
+        }
+        let mut ticker = tokio::time::interval(self.refresh_interval.max(Duration::from_millis(1)));
+        loop {
+            ticker.tick().await;
+            self.refresh(client.clone(), &sources).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashloan::FlashloanCallback;
+    use async_trait::async_trait;
+
+    // Lends `available` of any token for `fee_bps`
+    struct FixedSource {
+        name: &'static str,
+        available: u64,
+        fee_bps: u64,
+    }
+
+    #[async_trait]
+    impl FlashloanSource<Provider<MockProvider>> for FixedSource {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn quote(&self, _client: Arc<Provider<MockProvider>>, token: Address, amount: U256) -> Option<FlashloanQuote> {
+            Some(FlashloanQuote::new(
+                self.name,
+                Address::zero(),
+                token,
+                amount,
+                self.available.into(),
+                (self.fee_bps.into(), 10_000u64.into()),
+                FlashloanCallback::AaveExecuteOperation,
+            ))
+        }
+    }
+
+    fn source(name: &'static str, available: u64, fee_bps: u64) -> Box<dyn FlashloanSource<Provider<MockProvider>>> {
+        Box::new(FixedSource { name, available, fee_bps })
+    }
+
+    #[tokio::test]
+    async fn test_lookup_serves_refreshed_quotes_until_expiry() {
+        let cache = LiquidityCache::new(Duration::from_millis(50), Duration::from_secs(1));
+        let limits = BorrowLimits::utilization(0.5);
+        let token = Address::from_low_u64_be(1);
+        let amount = U256::from(10_000u64);
+        assert!(cache.get(token, amount, &limits).is_none());
+
+        // The miss tracked the token; a refresh quotes it from every source
+        let (provider, _mock) = Provider::mocked();
+        let sources = vec![source("Balancer", 15_000, 0), source("Aave V3", 1_000_000, 9)];
+        cache.refresh(Arc::new(provider), &sources).await;
+        assert_eq!(cache.len(), 2);
+
+        // Balancer can only lend 7_500 at 50% usage, so Aave wins despite its fee
+        let best = cache.get(token, amount, &limits).unwrap();
+        assert_eq!((best.source.as_str(), best.fee), ("Aave V3", U256::from(9u64)));
+        let best = cache.get(token, U256::from(5_000u64), &limits).unwrap();
+        assert_eq!((best.source.as_str(), best.fee), ("Balancer", U256::zero()));
+        assert!(cache.get(Address::from_low_u64_be(2), amount, &limits).is_none());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(cache.get(token, amount, &limits).is_none());
+        assert!(cache.is_empty());
+    }
+}
@@ -16,23 +16,31 @@ use fusion::liquidation_monitor::LiquidationMonitor;
 use fusion::liquidation_monitor_real::{RealArbitrageExecutor, VenusHelper};
 use tokio::sync::mpsc;
 // use fusion::optimizer_ai::OptimizerAI;
+use fusion::bundles::BundleSubmitter;
+use fusion::events::{WebSocketEvent, WebSocketEventSender};
+use fusion::execution_log::ExecutionLog;
+use fusion::execution_planner::ExecutionPlanner;
+use fusion::ledger::Ledger;
+use fusion::liquidity_cache::LiquidityCache;
+use fusion::pre_execution::CheckPipeline;
+use fusion::profit_sweeper::{ProfitSweeper, SweepConfig};
+use fusion::receipt_processor::ReceiptProcessor;
+use fusion::risk_manager::RiskManager;
 use fusion::shared_state::SharedState;
+use fusion::time_sync::TimeSync;
+use fusion::token_registry::TokenRegistry;
+use fusion::tx_tracker::TxTracker;
 use fusion::api;
 use actix_cors::Cors;
-use std::sync::Arc;
+
+// Foundry artifact of src/ArbitrageExecutor.sol, for the executeArbitrage ABI
+const EXECUTOR_ABI_PATH: &str = "out/ArbitrageExecutor.sol/ArbitrageExecutor.json";
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Load environment variables and logging
     dotenv().ok();
-    env_logger::init();
-    // DEBUG: Print current working directory and env vars
-    println!("[DEBUG] CWD: {}", std::env::current_dir().unwrap().display());
-    println!("[DEBUG] PRIVATE_KEY={:?}", std::env::var("PRIVATE_KEY"));
-    println!("[DEBUG] PROFIT_WALLET={:?}", std::env::var("PROFIT_WALLET"));
-    println!("[DEBUG] INFURA_API_KEY={:?}", std::env::var("INFURA_API_KEY"));
-    println!("[DEBUG] ALCHEMY_API_KEY={:?}", std::env::var("ALCHEMY_API_KEY"));
-    println!("[DEBUG] NODEREAL_API_KEY={:?}", std::env::var("NODEREAL_API_KEY"));
+    fusion::secrets::init_redacting_logger();
     log::info!("Starting Fusion backend API...");
     log::info!("WebSocket endpoint: ws://localhost:8080/ws/matrix2d");
     log::info!("Health check endpoint: http://localhost:8080/health");
@@ -44,9 +52,18 @@ async fn main() -> std::io::Result<()> {
         .expect("Failed to build config")
         .try_deserialize()
         .expect("Failed to deserialize settings");
+    // Keys and API keys are scrubbed from every log line from here on
+    fusion::secrets::register_settings_secrets(&settings);
 
     log::info!("Application routes configured");
 
+    // Start SNTP clock synchronization (corrected clock is used for price timestamps)
+    let time_sync = Arc::new(TimeSync::new(&settings));
+    if let Err(e) = time_sync.sync_once().await {
+        log::error!("Initial clock synchronization failed: {}", e);
+    }
+    tokio::spawn(time_sync.clone().run());
+
     // Initialize shared state
     let shared = Arc::new(Mutex::new(SharedState::default()));
 
@@ -56,9 +73,149 @@ async fn main() -> std::io::Result<()> {
             .await
             .expect("ProviderManager initialization failed"),
     );
+    provider_manager.spawn_health_probes(std::time::Duration::from_millis(
+        settings.provider_health_probe_interval_ms,
+    ));
+    provider_manager.spawn_quota_persistence(std::time::Duration::from_millis(
+        settings.quota_persist_interval_ms,
+    ));
+    provider_manager.spawn_nonce_gap_monitor(std::time::Duration::from_millis(
+        settings.nonce_gap_check_interval_ms,
+    ));
+    provider_manager.spawn_gas_oracles();
+    provider_manager.spawn_wallet_balance_monitor(std::time::Duration::from_millis(
+        settings.wallet_balance_refresh_interval_ms,
+    ));
+
+    // Chain the DEX matrix, executor and sweeper run on
+    let execution_chain = provider_manager
+        .registry
+        .resolve(&settings.execution_chain)
+        .cloned()
+        .expect("execution_chain is not in [[chains]]");
+
+    // Token registry (symbol <-> address, decimals, categories) built from the token_* settings
+    // and the [[chains]] token tables
+    let token_registry = Arc::new(TokenRegistry::from_settings(&settings));
+    for (chain_id, chain) in &provider_manager.chains {
+        token_registry.load_all_metadata(*chain_id, chain.http_provider.clone()).await;
+    }
+
+    // Price matrix: rows = DEXes, columns = the execution chain's token symbols
+    let assets = token_registry.tokens(execution_chain.chain_id).into_iter().map(|t| t.symbol).collect();
+    let matrix2d = Arc::new(std::sync::Mutex::new(Matrix2D::new(settings.dexes.clone(), assets)));
+
+    // Execution planner on the execution chain, picking flashloan sources from a liquidity
+    // cache refreshed in the background (through the quorum provider when there is one) and
+    // valuing gas at the matrix's wrapped native price
+    let execution_provider = provider_manager
+        .require_chain(execution_chain.chain_id)
+        .expect("execution_chain is not connected");
+    let liquidity_cache = Arc::new(LiquidityCache::from_settings(&settings));
+    let planner = Arc::new(
+        ExecutionPlanner::new(Arc::new(settings.clone()), token_registry.clone(), &execution_chain)
+            .with_liquidity_cache(liquidity_cache.clone())
+            .with_matrix(matrix2d.clone()),
+    );
+    match &execution_provider.quorum_provider {
+        Some(quorum) => tokio::spawn(liquidity_cache.clone().run(quorum.clone(), planner.flashloan_sources())),
+        None => tokio::spawn(
+            liquidity_cache.clone().run(execution_provider.http_provider.clone(), planner.flashloan_sources()),
+        ),
+    };
 
     // Initialize broadcast channel for WebSocket events
     let (event_tx, _) = tokio::sync::broadcast::channel::<WebSocketEvent>(100);
+
+    // Loss limits and circuit breaker; breaker trips are broadcast as alerts
+    let risk = Arc::new(
+        RiskManager::from_settings(&settings).with_alerts(Arc::new(WebSocketEventSender::new(event_tx.clone()))),
+    );
+
+    // Inventory and PnL, marked at matrix prices
+    let ledger = Arc::new(
+        Ledger::from_settings(&settings)
+            .with_matrix(matrix2d.clone())
+            .with_registry(token_registry.clone())
+            .with_chains(provider_manager.registry.clone()),
+    );
+
+    // Liquidation executor on the execution chain
+    let execution_log = Arc::new(ExecutionLog::new());
+    let tracker = Arc::new(TxTracker::from_settings(&settings, execution_log.clone()));
+    let profit_wallet = settings.profit_wallet.clone().unwrap_or_default();
+    let mut executor = RealArbitrageExecutor::new(
+        EXECUTOR_ABI_PATH.to_string(),
+        profit_wallet.clone(),
+        planner.clone(),
+        provider_manager.wallet_pool.clone(),
+        provider_manager.nonce_manager.clone(),
+        execution_provider.gas_oracle.clone(),
+        tracker.clone(),
+    )
+    .with_risk_manager(risk.clone())
+    .with_ledger(ledger.clone())
+    .with_time_sync(time_sync.clone())
+    .with_checks(Arc::new(CheckPipeline::from_settings(&settings, &token_registry)));
+    if let Some(quorum) = &execution_provider.quorum_provider {
+        executor = executor.with_quorum(quorum.clone());
+    }
+    match (planner.executor(), profit_wallet.parse()) {
+        (Ok(executor_address), Ok(profit_wallet)) => {
+            executor = executor.with_receipts(Arc::new(ReceiptProcessor::new(
+                executor_address,
+                profit_wallet,
+                planner.clone(),
+                execution_log.clone(),
+            )));
+        }
+        (Err(e), _) => log::error!("{}; liquidations will not be executed", e),
+        (_, Err(_)) => log::warn!("profit_wallet is not set to a valid address; realized profit is not tracked"),
+    }
+    if settings.bundle_submission_enabled {
+        match BundleSubmitter::from_settings(&settings) {
+            Ok(bundles) => executor = executor.with_bundles(Arc::new(bundles)),
+            Err(e) => log::error!("Bundle submission disabled: {}", e),
+        }
+    }
+
+    // Scheduled sweep of executor-wallet excess to profit_wallet; also books the wallets'
+    // opening balances in the ledger
+    if settings.profit_sweep_enabled {
+        match SweepConfig::from_settings(&settings, &token_registry, &execution_chain) {
+            Ok(config) => {
+                let mut sweeper = ProfitSweeper::new(
+                    config,
+                    provider_manager.wallet_pool.clone(),
+                    planner.clone(),
+                    provider_manager.nonce_manager.clone(),
+                    execution_provider.gas_oracle.clone(),
+                    execution_log.clone(),
+                )
+                .with_tracker(tracker.clone())
+                .with_ledger(ledger.clone());
+                if let Some(quorum) = &execution_provider.quorum_provider {
+                    sweeper = sweeper.with_quorum(quorum.clone());
+                }
+                tokio::spawn(Arc::new(sweeper).run());
+            }
+            Err(e) => log::error!("Profit sweep disabled: {}", e),
+        }
+    }
+
+    // Detected liquidation opportunities are handed to the executor
+    let (liquidation_tx, liquidation_rx) = mpsc::channel(100);
+    tokio::spawn(LiquidationMonitor::new_with_rx_and_executor(liquidation_rx, executor).run(shared.clone()));
+    match (execution_chain.lending.venus(), token_registry.resolve_symbol(execution_chain.chain_id, &execution_chain.wrapped_native_token)) {
+        (Some(comptroller), Some(wrapped_native)) => {
+            tokio::spawn(
+                VenusHelper { sender: liquidation_tx, client: execution_provider.http_provider.clone(), comptroller, wrapped_native }
+                    .spawn_detection(shared.clone()),
+            );
+        }
+        _ => log::warn!("No Venus comptroller or wrapped native token on {}; Venus liquidations are not watched", execution_chain.name),
+    }
+
     let event_tx = web::Data::new(event_tx);
 
     // Start HTTP/WebSocket server
@@ -66,9 +223,12 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(Cors::permissive())
             .app_data(web::Data::new(Arc::new(settings.clone())))
-            .app_data(web::Data::new(Arc::new(provider_manager.clone())))
-            .app_data(web::Data::new(Arc::new(matrix2d.clone())))
-            .app_data(web::Data::new(Arc::new(shared.clone())))
+            .app_data(web::Data::new(provider_manager.clone()))
+            .app_data(web::Data::new(matrix2d.clone()))
+            .app_data(web::Data::new(shared.clone()))
+            .app_data(web::Data::new(token_registry.clone()))
+            .app_data(web::Data::new(risk.clone()))
+            .app_data(web::Data::new(ledger.clone()))
             .app_data(event_tx.clone())
             .service(web::resource("/ws/matrix2d").to(fusion::api_ws::ws_matrix2d_handler))
             .service(web::resource("/health").to(api::health_check))
@@ -77,6 +237,13 @@ async fn main() -> std::io::Result<()> {
             .service(api::post_transfer)
             .service(api::get_wallet_status)
             .service(api::post_connect_wallet)
+            .service(api::get_quorum_metrics)
+            .service(api::get_provider_health)
+            .service(api::get_wallets)
+            .service(api::get_risk_status)
+            .service(api::post_kill_switch)
+            .service(api::get_profit)
+            .service(api::get_ledger)
     })
     .bind((
         std::env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
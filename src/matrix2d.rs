@@ -1,5 +1,5 @@
 use serde::{Serialize, Deserialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::time_sync;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Matrix2D {
@@ -20,12 +20,13 @@ impl Matrix2D {
         Self { dexes, assets, prices }
     }
 
+    /// Update a cell, stamping it with the SNTP-corrected clock (`time_sync::now_millis`).
     pub fn update_price(&mut self, dex: &str, asset: &str, price: f64) {
         if let (Some(dex_idx), Some(asset_idx)) = (
             self.dexes.iter().position(|d| d == dex),
             self.assets.iter().position(|a| a == asset),
         ) {
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+            let now = time_sync::now_millis();
             self.prices[dex_idx][asset_idx] = PriceCell { price, timestamp: now };
         }
     }
@@ -35,4 +36,34 @@ impl Matrix2D {
         let asset_idx = self.assets.iter().position(|a| a == asset)?;
         self.prices.get(dex_idx)?.get(asset_idx)
     }
+
+    /// Median price of `asset` across DEXes, counting only cells updated within `max_age_ms`
+    /// of `now_ms`. None if no DEX has a fresh price.
+    pub fn mark_price(&self, asset: &str, max_age_ms: u64, now_ms: u64) -> Option<f64> {
+        let asset_idx = self.assets.iter().position(|a| a == asset)?;
+        let mut fresh: Vec<f64> = self
+            .prices
+            .iter()
+            .filter_map(|row| row.get(asset_idx))
+            .filter(|cell| cell.price > 0.0 && cell.price.is_finite() && now_ms.saturating_sub(cell.timestamp) <= max_age_ms)
+            .map(|cell| cell.price)
+            .collect();
+        if fresh.is_empty() {
+            return None;
+        }
+        fresh.sort_by(f64::total_cmp);
+        let mid = fresh.len() / 2;
+        Some(if fresh.len().is_multiple_of(2) { (fresh[mid - 1] + fresh[mid]) / 2.0 } else { fresh[mid] })
+    }
+
+    /// The most recently updated price of `asset` on any DEX, however old.
+    pub fn last_price(&self, asset: &str) -> Option<f64> {
+        let asset_idx = self.assets.iter().position(|a| a == asset)?;
+        self.prices
+            .iter()
+            .filter_map(|row| row.get(asset_idx))
+            .filter(|cell| cell.price > 0.0 && cell.price.is_finite())
+            .max_by_key(|cell| cell.timestamp)
+            .map(|cell| cell.price)
+    }
 }
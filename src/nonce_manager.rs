@@ -0,0 +1,282 @@
+// Per-chain, per-wallet nonce manager for concurrent transaction submission.
+// Nonces are handed out locally from a counter seeded by eth_getTransactionCount("pending").
+// Nonces of transactions that were never sent are released for reuse, the counter is resynced
+// from the node when a send fails with a nonce error, and gaps left by dropped transactions are
+// filled with zero-value self-transfers so later nonces are not stuck behind them.
+
+use ethers::prelude::*;
+use log::{info, warn};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Error)]
+pub enum NonceError {
+    #[error("Failed to fetch transaction count for {address:?} on chain {chain_id}: {message}")]
+    TransactionCount { chain_id: u64, address: Address, message: String },
+    #[error("Failed to send gap-filling transaction for nonce {nonce}: {message}")]
+    GapFill { nonce: U256, message: String },
+}
+
+#[derive(Debug, Default)]
+struct WalletNonces {
+    next: U256,
+    // Nonces handed out and not yet seen mined, with the time they were issued
+    issued: BTreeMap<U256, Instant>,
+    // Released nonces below `next`, handed out again before `next`
+    released: BTreeSet<U256>,
+}
+
+/// Whether a send error means the node disagrees with our nonce ("nonce too low"/"too high").
+pub fn is_nonce_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("nonce too low") || message.contains("nonce too high") || message.contains("invalid nonce")
+}
+
+#[derive(Debug)]
+pub struct NonceManager {
+    wallets: Mutex<HashMap<(u64, Address), WalletNonces>>,
+    // How long an issued nonce may be missing from the node's pending pool before it counts as a gap
+    gap_grace: Duration,
+}
+
+async fn transaction_count<M: Middleware>(
+    client: &M,
+    chain_id: u64,
+    address: Address,
+    block: BlockNumber,
+) -> Result<U256, NonceError> {
+    client
+        .get_transaction_count(address, Some(block.into()))
+        .await
+        .map_err(|e| NonceError::TransactionCount { chain_id, address, message: e.to_string() })
+}
+
+impl NonceManager {
+    pub fn new(gap_grace: Duration) -> Self {
+        Self { wallets: Mutex::new(HashMap::new()), gap_grace }
+    }
+
+    /// Hand out the next nonce for `address` on `chain_id`, seeding the counter from the
+    /// node's pending transaction count on first use.
+    pub async fn next_nonce<M: Middleware>(
+        &self,
+        client: &M,
+        chain_id: u64,
+        address: Address,
+    ) -> Result<U256, NonceError> {
+        let mut wallets = self.wallets.lock().await;
+        let wallet = match wallets.entry((chain_id, address)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let pending = transaction_count(client, chain_id, address, BlockNumber::Pending).await?;
+                info!("[NonceManager] Initialized {:?} on chain {} at nonce {}", address, chain_id, pending);
+                entry.insert(WalletNonces { next: pending, ..Default::default() })
+            }
+        };
+        let nonce = match wallet.released.pop_first() {
+            Some(nonce) => nonce,
+            None => {
+                let nonce = wallet.next;
+                wallet.next += U256::one();
+                nonce
+            }
+        };
+        wallet.issued.insert(nonce, Instant::now());
+        Ok(nonce)
+    }
+
+    /// Give back a nonce whose transaction was never sent (failed simulation, rejected check,
+    /// send refused by the node) so it is handed out again instead of leaving a gap.
+    pub async fn release(&self, chain_id: u64, address: Address, nonce: U256) {
+        let mut wallets = self.wallets.lock().await;
+        let Some(wallet) = wallets.get_mut(&(chain_id, address)) else { return };
+        if wallet.issued.remove(&nonce).is_none() {
+            return;
+        }
+        if nonce + 1 != wallet.next {
+            wallet.released.insert(nonce);
+            return;
+        }
+        // Released from the top: wind the counter back past every released nonce below it
+        wallet.next = nonce;
+        while let Some(&last) = wallet.released.last()
+            && last + 1 == wallet.next
+        {
+            wallet.released.pop_last();
+            wallet.next = last;
+        }
+    }
+
+    /// Reset the local counter to the node's pending transaction count after a send failed
+    /// with a nonce error. Returns the new next nonce.
+    pub async fn resync<M: Middleware>(&self, client: &M, chain_id: u64, address: Address) -> Result<U256, NonceError> {
+        let pending = transaction_count(client, chain_id, address, BlockNumber::Pending).await?;
+        let mut wallets = self.wallets.lock().await;
+        let wallet = wallets.entry((chain_id, address)).or_default();
+        if wallet.next != pending {
+            warn!(
+                "[NonceManager] Resynced {:?} on chain {}: local next nonce {} -> {}",
+                address, chain_id, wallet.next, pending
+            );
+        }
+        wallet.next = pending;
+        wallet.issued.retain(|nonce, _| *nonce < pending);
+        wallet.released.clear();
+        Ok(pending)
+    }
+
+    /// The lowest issued nonce the node does not know about although higher nonces were
+    /// issued and it has been outstanding longer than the gap grace period.
+    pub async fn detect_gap<M: Middleware>(
+        &self,
+        client: &M,
+        chain_id: u64,
+        address: Address,
+    ) -> Result<Option<U256>, NonceError> {
+        let mined = transaction_count(client, chain_id, address, BlockNumber::Latest).await?;
+        let pending = transaction_count(client, chain_id, address, BlockNumber::Pending).await?;
+        let mut wallets = self.wallets.lock().await;
+        let Some(wallet) = wallets.get_mut(&(chain_id, address)) else {
+            return Ok(None);
+        };
+        wallet.issued.retain(|nonce, _| *nonce >= mined);
+        if pending >= wallet.next {
+            return Ok(None);
+        }
+        // The node's pending count stops at the first nonce it has no transaction for
+        match wallet.issued.get(&pending) {
+            Some(issued_at) if issued_at.elapsed() < self.gap_grace => Ok(None),
+            _ => Ok(Some(pending)),
+        }
+    }
+
+    /// Fill nonce gaps for `address` with zero-value self-transfers, one at a time until the
+    /// node's pending count reaches the local counter. Returns the filler transaction hashes.
+    pub async fn fill_gaps<M: Middleware>(
+        &self,
+        client: &M,
+        chain_id: u64,
+        address: Address,
+    ) -> Result<Vec<TxHash>, NonceError> {
+        let mut filled = Vec::new();
+        let mut filled_nonces = Vec::new();
+        while let Some(nonce) = self.detect_gap(client, chain_id, address).await? {
+            // The node hasn't picked up our filler yet; try again on the next check
+            if filled_nonces.contains(&nonce) {
+                break;
+            }
+            warn!("[NonceManager] Nonce gap at {} for {:?} on chain {}; filling", nonce, address, chain_id);
+            let tx = TransactionRequest::new().from(address).to(address).value(0).nonce(nonce);
+            let pending_tx = client
+                .send_transaction(tx, None)
+                .await
+                .map_err(|e| NonceError::GapFill { nonce, message: e.to_string() })?;
+            filled.push(pending_tx.tx_hash());
+            filled_nonces.push(nonce);
+            if let Some(wallet) = self.wallets.lock().await.get_mut(&(chain_id, address)) {
+                wallet.released.remove(&nonce);
+                wallet.issued.insert(nonce, Instant::now());
+            }
+        }
+        Ok(filled)
+    }
+
+    /// Check for and fill nonce gaps every `interval`, forever.
+    pub async fn run_gap_monitor<M: Middleware + 'static>(
+        self: Arc<Self>,
+        client: Arc<M>,
+        chain_id: u64,
+        address: Address,
+        interval: Duration,
+    ) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.fill_gaps(&*client, chain_id, address).await {
+                Ok(filled) if !filled.is_empty() => {
+                    info!("[NonceManager] Filled {} nonce gap(s) on chain {}", filled.len(), chain_id)
+                }
+                Ok(_) => {}
+                Err(e) => warn!("[NonceManager] {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wallet() -> Address {
+        Address::from_low_u64_be(0xabc)
+    }
+
+    #[tokio::test]
+    async fn test_sequential_nonces_and_resync() {
+        let (provider, mock) = Provider::mocked();
+        let manager = NonceManager::new(Duration::from_secs(60));
+        mock.push(U256::from(5)).unwrap();
+        for expected in 5..8 {
+            assert_eq!(manager.next_nonce(&provider, 56, wallet()).await.unwrap(), U256::from(expected));
+        }
+        // A different chain keeps its own counter
+        mock.push(U256::from(0)).unwrap();
+        assert_eq!(manager.next_nonce(&provider, 97, wallet()).await.unwrap(), U256::zero());
+
+        mock.push(U256::from(6)).unwrap();
+        assert_eq!(manager.resync(&provider, 56, wallet()).await.unwrap(), U256::from(6));
+        assert_eq!(manager.next_nonce(&provider, 56, wallet()).await.unwrap(), U256::from(6));
+    }
+
+    #[tokio::test]
+    async fn test_released_nonces_are_reused() {
+        let (provider, mock) = Provider::mocked();
+        let manager = NonceManager::new(Duration::from_secs(60));
+        mock.push(U256::from(5)).unwrap();
+        for _ in 0..3 {
+            manager.next_nonce(&provider, 56, wallet()).await.unwrap();
+        }
+        // 6 is released while 7 is in flight: it is handed out next
+        manager.release(56, wallet(), U256::from(6)).await;
+        assert_eq!(manager.next_nonce(&provider, 56, wallet()).await.unwrap(), U256::from(6));
+        assert_eq!(manager.next_nonce(&provider, 56, wallet()).await.unwrap(), U256::from(8));
+
+        // Releasing 8, 7 and 6 winds the counter back to 6; unknown nonces are ignored
+        manager.release(56, wallet(), U256::from(7)).await;
+        manager.release(56, wallet(), U256::from(6)).await;
+        manager.release(56, wallet(), U256::from(42)).await;
+        manager.release(56, wallet(), U256::from(8)).await;
+        assert_eq!(manager.next_nonce(&provider, 56, wallet()).await.unwrap(), U256::from(6));
+        assert_eq!(manager.next_nonce(&provider, 56, wallet()).await.unwrap(), U256::from(7));
+    }
+
+    #[test]
+    fn test_is_nonce_error() {
+        assert!(is_nonce_error("(code: -32000, message: nonce too low, data: None)"));
+        assert!(is_nonce_error("Nonce too high"));
+        assert!(!is_nonce_error("insufficient funds for gas * price + value"));
+    }
+
+    #[tokio::test]
+    async fn test_detect_gap_after_grace_period() {
+        let (provider, mock) = Provider::mocked();
+        let manager = NonceManager::new(Duration::ZERO);
+        mock.push(U256::from(5)).unwrap();
+        for _ in 0..3 {
+            manager.next_nonce(&provider, 56, wallet()).await.unwrap();
+        }
+        // Nonce 5 was dropped: nothing mined past 5 and the pending pool stops at 5
+        mock.push(U256::from(5)).unwrap(); // pending (responses are popped LIFO)
+        mock.push(U256::from(5)).unwrap(); // latest
+        assert_eq!(manager.detect_gap(&provider, 56, wallet()).await.unwrap(), Some(U256::from(5)));
+
+        // All issued nonces pending: no gap
+        mock.push(U256::from(8)).unwrap();
+        mock.push(U256::from(5)).unwrap();
+        assert_eq!(manager.detect_gap(&provider, 56, wallet()).await.unwrap(), None);
+    }
+}
@@ -6,6 +6,12 @@ pub struct OptimizerAI {
     // Add config fields as needed
 }
 
+impl Default for OptimizerAI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OptimizerAI {
     pub fn new() -> Self {
         Self { /* Add config fields as needed */ }
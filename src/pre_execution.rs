@@ -0,0 +1,380 @@
+// Pre-execution checks: an ordered pipeline of `PreExecutionCheck`s run right before a
+// transaction is sent (after simulation). The first failing check rejects the execution and
+// its name is recorded. Built-in checks are selected and ordered by `pre_execution_checks`;
+// the whole pipeline is skipped when `transaction_pre_validation` is off.
+
+use crate::config::Settings;
+use crate::simulation::SimulationOutcome;
+use crate::time_sync;
+use crate::token_registry::TokenRegistry;
+use ethers::types::{Address, U256};
+use ethers::utils::format_ether;
+use log::warn;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// What the checks know about an execution about to be sent. Filled in by the executor;
+/// `simulation` and `max_gas_cost` by `execute_arbitrage_onchain`.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionContext {
+    pub chain_id: u64,
+    /// When the prices or event behind the execution were observed (ms since epoch)
+    pub observed_at_ms: u64,
+    /// When the executor started assembling the transaction
+    pub assembly_started: Option<Instant>,
+    /// Every token the route touches
+    pub tokens: Vec<Address>,
+    /// Expected profit before gas; None if it can't be valued in USD
+    pub expected_profit_usd: Option<f64>,
+    /// Loan size; None if it can't be valued in USD
+    pub exposure_usd: Option<f64>,
+    pub native_price_usd: Option<f64>,
+    /// Last known native balance of the sending wallet
+    pub sender_balance: Option<U256>,
+    /// Gas limit × max price per gas, in wei
+    pub max_gas_cost: Option<U256>,
+    /// None if the transaction was not simulated
+    pub simulation: Option<SimulationOutcome>,
+}
+
+impl ExecutionContext {
+    fn gas_cost_usd(&self) -> Option<f64> {
+        let wei = self.max_gas_cost?;
+        let native: f64 = format_ether(wei).parse().ok()?;
+        Some(native * self.native_price_usd?)
+    }
+}
+
+pub trait PreExecutionCheck: Send + Sync {
+    /// Name used in `pre_execution_checks` and in rejection records
+    fn name(&self) -> &'static str;
+    /// `Err` with a human-readable reason rejects the execution
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String>;
+}
+
+/// Prices/events older than `transaction_max_age_ms` (by the SNTP-corrected clock) are stale.
+pub struct FreshnessCheck {
+    pub max_age: Duration,
+}
+
+impl PreExecutionCheck for FreshnessCheck {
+    fn name(&self) -> &'static str {
+        "freshness"
+    }
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String> {
+        let age = time_sync::now_millis().saturating_sub(ctx.observed_at_ms);
+        if age > self.max_age.as_millis() as u64 {
+            return Err(format!("observed {} ms ago (max {} ms)", age, self.max_age.as_millis()));
+        }
+        Ok(())
+    }
+}
+
+/// Assembly (planning, quoting, simulation) must finish within `transaction_assembly_max_time_ms`.
+pub struct DeadlineCheck {
+    pub max_assembly_time: Duration,
+}
+
+impl PreExecutionCheck for DeadlineCheck {
+    fn name(&self) -> &'static str {
+        "deadline"
+    }
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String> {
+        let Some(started) = ctx.assembly_started else {
+            return Ok(());
+        };
+        let elapsed = started.elapsed();
+        if elapsed > self.max_assembly_time {
+            return Err(format!("assembly took {:?} (max {:?})", elapsed, self.max_assembly_time));
+        }
+        Ok(())
+    }
+}
+
+/// The transaction must have been simulated successfully (reverts already abort the send).
+pub struct SimulationCheck;
+
+impl PreExecutionCheck for SimulationCheck {
+    fn name(&self) -> &'static str {
+        "simulation"
+    }
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String> {
+        match &ctx.simulation {
+            Some(_) => Ok(()),
+            None => Err("transaction was not simulated".to_string()),
+        }
+    }
+}
+
+/// Expected profit less the worst-case gas cost must reach `min_profit_usd`. Gas that can't be
+/// valued (no native price) rejects rather than counting as free.
+pub struct MinNetProfitCheck {
+    pub min_profit_usd: f64,
+}
+
+impl PreExecutionCheck for MinNetProfitCheck {
+    fn name(&self) -> &'static str {
+        "min_net_profit"
+    }
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String> {
+        let profit = ctx.expected_profit_usd.ok_or("profit cannot be valued in USD")?;
+        let gas = match ctx.max_gas_cost {
+            Some(_) => ctx.gas_cost_usd().ok_or("gas cost cannot be valued in USD")?,
+            None => 0.0,
+        };
+        let net = profit - gas;
+        if net < self.min_profit_usd {
+            return Err(format!("net profit ${:.2} below ${:.2}", net, self.min_profit_usd));
+        }
+        Ok(())
+    }
+}
+
+/// The sending wallet must be able to pay for the gas. Unknown balances pass.
+pub struct BalanceCheck;
+
+impl PreExecutionCheck for BalanceCheck {
+    fn name(&self) -> &'static str {
+        "balance"
+    }
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String> {
+        if let (Some(balance), Some(cost)) = (ctx.sender_balance, ctx.max_gas_cost)
+            && balance < cost
+        {
+            return Err(format!("wallet balance {} wei below max gas cost {} wei", balance, cost));
+        }
+        Ok(())
+    }
+}
+
+/// Every token on the route must be allowlisted on the chain it executes on.
+pub struct TokenAllowlistCheck {
+    /// Allowlisted token addresses per chain ID
+    pub allowed: HashMap<u64, HashSet<Address>>,
+}
+
+impl TokenAllowlistCheck {
+    /// Tokens from `token_allowlist` on every chain that registers them, or every registered
+    /// token if the list is empty.
+    pub fn from_settings(settings: &Settings, registry: &TokenRegistry) -> Self {
+        let mut allowed: HashMap<u64, HashSet<Address>> = HashMap::new();
+        for chain_id in registry.chain_ids() {
+            let tokens: HashSet<Address> = if settings.token_allowlist.is_empty() {
+                registry.tokens(chain_id).into_iter().map(|t| t.address).collect()
+            } else {
+                settings.token_allowlist.iter().filter_map(|symbol| registry.resolve_symbol(chain_id, symbol)).collect()
+            };
+            allowed.insert(chain_id, tokens);
+        }
+        for symbol in &settings.token_allowlist {
+            if !allowed.keys().any(|chain_id| registry.resolve_symbol(*chain_id, symbol).is_some()) {
+                warn!("[PreExecution] Allowlisted token {} is not registered", symbol);
+            }
+        }
+        Self { allowed }
+    }
+}
+
+impl PreExecutionCheck for TokenAllowlistCheck {
+    fn name(&self) -> &'static str {
+        "token_allowlist"
+    }
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String> {
+        let allowed = self.allowed.get(&ctx.chain_id);
+        match ctx.tokens.iter().find(|token| !allowed.is_some_and(|allowed| allowed.contains(token))) {
+            Some(token) => Err(format!("token {:?} is not allowlisted on chain {}", token, ctx.chain_id)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A single execution may not borrow more than `max_exposure_usd`.
+pub struct MaxExposureCheck {
+    pub max_exposure_usd: f64,
+}
+
+impl PreExecutionCheck for MaxExposureCheck {
+    fn name(&self) -> &'static str {
+        "max_exposure"
+    }
+    fn check(&self, ctx: &ExecutionContext) -> Result<(), String> {
+        let exposure = ctx.exposure_usd.ok_or("exposure cannot be valued in USD")?;
+        if exposure > self.max_exposure_usd {
+            return Err(format!("exposure ${:.2} above ${:.2}", exposure, self.max_exposure_usd));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Pre-execution check {check} failed: {reason}")]
+pub struct CheckRejection {
+    pub check: &'static str,
+    pub reason: String,
+}
+
+/// Ordered list of checks; the first failure rejects.
+#[derive(Default)]
+pub struct CheckPipeline {
+    checks: Vec<Box<dyn PreExecutionCheck>>,
+    // Rejections per check name since startup
+    rejections: Mutex<HashMap<&'static str, u64>>,
+}
+
+impl std::fmt::Debug for CheckPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CheckPipeline").field("checks", &self.names()).finish()
+    }
+}
+
+impl CheckPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a check; checks run in the order they were added.
+    pub fn with_check(mut self, check: Box<dyn PreExecutionCheck>) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// The built-in checks named in `pre_execution_checks`, in that order. Empty when
+    /// `transaction_pre_validation` is off.
+    pub fn from_settings(settings: &Settings, registry: &TokenRegistry) -> Self {
+        let mut pipeline = Self::new();
+        if !settings.transaction_pre_validation {
+            return pipeline;
+        }
+        for name in &settings.pre_execution_checks {
+            let check: Box<dyn PreExecutionCheck> = match name.as_str() {
+                "freshness" => Box::new(FreshnessCheck { max_age: Duration::from_millis(settings.transaction_max_age_ms) }),
+                "deadline" => Box::new(DeadlineCheck {
+                    max_assembly_time: Duration::from_millis(settings.transaction_assembly_max_time_ms),
+                }),
+                "simulation" => Box::new(SimulationCheck),
+                "min_net_profit" => Box::new(MinNetProfitCheck { min_profit_usd: settings.min_profit_usd }),
+                "balance" => Box::new(BalanceCheck),
+                "token_allowlist" => Box::new(TokenAllowlistCheck::from_settings(settings, registry)),
+                "max_exposure" => Box::new(MaxExposureCheck { max_exposure_usd: settings.max_exposure_usd }),
+                other => {
+                    warn!("[PreExecution] Unknown pre-execution check {}", other);
+                    continue;
+                }
+            };
+            pipeline = pipeline.with_check(check);
+        }
+        pipeline
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.checks.iter().map(|c| c.name()).collect()
+    }
+
+    /// Run every check in order, stopping at the first rejection.
+    pub fn run(&self, ctx: &ExecutionContext) -> Result<(), CheckRejection> {
+        for check in &self.checks {
+            if let Err(reason) = check.check(ctx) {
+                *self.rejections.lock().unwrap().entry(check.name()).or_insert(0) += 1;
+                warn!("[PreExecution] Rejected by {}: {}", check.name(), reason);
+                return Err(CheckRejection { check: check.name(), reason });
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejection counts per check name.
+    pub fn rejections(&self) -> HashMap<&'static str, u64> {
+        self.rejections.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_registry::BSC_MAINNET_CHAIN_ID;
+
+    fn now_ms() -> u64 {
+        crate::time_sync::now_millis()
+    }
+
+    fn passing_context() -> ExecutionContext {
+        ExecutionContext {
+            chain_id: 56,
+            observed_at_ms: now_ms(),
+            assembly_started: Some(Instant::now()),
+            tokens: vec![Address::from_low_u64_be(1)],
+            expected_profit_usd: Some(50.0),
+            exposure_usd: Some(10_000.0),
+            native_price_usd: Some(600.0),
+            sender_balance: Some(U256::exp10(18)),
+            max_gas_cost: Some(U256::exp10(15)), // 0.001 BNB = $0.60
+            simulation: Some(SimulationOutcome { gas_estimate: U256::from(300_000), return_data: Default::default(), profit: None }),
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            transaction_pre_validation: true,
+            pre_execution_checks: ["freshness", "deadline", "simulation", "min_net_profit", "balance", "max_exposure", "bogus"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            transaction_max_age_ms: 60_000,
+            transaction_assembly_max_time_ms: 5_000,
+            min_profit_usd: 10.0,
+            max_exposure_usd: 50_000.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pipeline_order_and_rejection_names() {
+        let pipeline = CheckPipeline::from_settings(&settings(), &TokenRegistry::new());
+        assert_eq!(pipeline.names(), vec!["freshness", "deadline", "simulation", "min_net_profit", "balance", "max_exposure"]);
+        assert!(pipeline.run(&passing_context()).is_ok());
+
+        let stale = ExecutionContext { observed_at_ms: now_ms() - 120_000, ..passing_context() };
+        assert_eq!(pipeline.run(&stale).unwrap_err().check, "freshness");
+
+        // Profit of $10.50 less $0.60 gas misses the $10 minimum
+        let thin = ExecutionContext { expected_profit_usd: Some(10.5), ..passing_context() };
+        assert_eq!(pipeline.run(&thin).unwrap_err().check, "min_net_profit");
+        let unpriced_gas = ExecutionContext { native_price_usd: None, ..passing_context() };
+        assert_eq!(pipeline.run(&unpriced_gas).unwrap_err().check, "min_net_profit");
+
+        let broke = ExecutionContext { sender_balance: Some(U256::from(1)), ..passing_context() };
+        let rejection = pipeline.run(&broke).unwrap_err();
+        assert_eq!(rejection.check, "balance");
+        assert!(rejection.to_string().starts_with("Pre-execution check balance failed"));
+
+        let unsimulated = ExecutionContext { simulation: None, ..passing_context() };
+        assert_eq!(pipeline.run(&unsimulated).unwrap_err().check, "simulation");
+        assert_eq!(pipeline.rejections().get("min_net_profit"), Some(&2));
+
+        let disabled = Settings { transaction_pre_validation: false, ..settings() };
+        assert!(CheckPipeline::from_settings(&disabled, &TokenRegistry::new()).names().is_empty());
+    }
+
+    #[test]
+    fn test_token_allowlist() {
+        let registry = TokenRegistry::new();
+        let (busd, wbnb, other) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2), Address::from_low_u64_be(3));
+        registry.register(BSC_MAINNET_CHAIN_ID, "BUSD", busd, Some(18));
+        registry.register(BSC_MAINNET_CHAIN_ID, "WBNB", wbnb, Some(18));
+
+        let on_bsc = |tokens: Vec<Address>| ExecutionContext { chain_id: BSC_MAINNET_CHAIN_ID, tokens, ..Default::default() };
+
+        let everything = TokenAllowlistCheck::from_settings(&Settings::default(), &registry);
+        assert!(everything.check(&on_bsc(vec![busd, wbnb])).is_ok());
+        assert!(everything.check(&on_bsc(vec![busd, other])).is_err());
+        // Tokens are allowlisted per chain
+        assert!(everything.check(&ExecutionContext { chain_id: 1, tokens: vec![busd], ..Default::default() }).is_err());
+
+        let only_busd = Settings { token_allowlist: vec!["busd".to_string()], ..Default::default() };
+        let check = TokenAllowlistCheck::from_settings(&only_busd, &registry);
+        assert!(check.check(&on_bsc(vec![busd])).is_ok());
+        assert!(check.check(&on_bsc(vec![busd, wbnb])).is_err());
+    }
+}
@@ -0,0 +1,577 @@
+// Profit sweeper: every `profit_sweep_interval_ms` each executor wallet's native balance and its
+// balance of every `sweep_tokens` token is compared with the working balance it should keep for
+// gas and trading capital, and the excess is sent to `profit_wallet`. To limit gas, excess worth
+// less than `sweep_min_usd` is left to accumulate, a transfer whose gas would cost more than
+// `sweep_max_gas_pct` of its value is skipped, and at most `sweep_max_transfers_per_run`
+// transfers (largest first) go out per run. Every sweep is recorded in the ExecutionLog.
+
+use crate::chain_registry::ChainConfig;
+use crate::config::Settings;
+use crate::execution_log::{ExecutionLog, ExecutionRecord, TransferRecord, TxStatus};
+use crate::execution_planner::ExecutionPlanner;
+use crate::flashloan::balance_of;
+use crate::gas_oracle::{FeeSuggestion, GasOracle};
+use crate::ledger::{Ledger, NATIVE_TOKEN};
+use crate::nonce_manager::{is_nonce_error, NonceManager};
+use crate::providers::{QuorumProvider, SignerHttpProvider};
+use crate::token_amount::TokenAmount;
+use crate::token_registry::TokenRegistry;
+use crate::tx_tracker::{TxOutcome, TxTracker};
+use crate::wallet_pool::{PooledWallet, WalletPool};
+use ethers::abi::Token;
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::id;
+use futures::future::join_all;
+use log::{debug, error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+const NATIVE_DECIMALS: u8 = 18;
+const NATIVE_TRANSFER_GAS: u64 = 21_000;
+/// Gas limit for an ERC20 transfer; generous so fee-on-transfer tokens don't run out
+const TOKEN_TRANSFER_GAS: u64 = 100_000;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SweepError {
+    #[error("Invalid sweep_tokens entry '{0}': expected SYMBOL:working_balance")]
+    InvalidEntry(String),
+    #[error("Unknown sweep token {0}")]
+    UnknownToken(String),
+    #[error("profit_wallet is not set to a valid address: {0}")]
+    InvalidProfitWallet(String),
+}
+
+/// A token whose balance above `working_balance` (whole tokens) is swept from every wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepToken {
+    pub symbol: String,
+    pub address: Address,
+    pub working_balance: f64,
+}
+
+/// Parse "SYMBOL:working_balance" entries, resolving symbols (or addresses) on `chain_id` in
+/// `registry`.
+pub fn parse_sweep_tokens(entries: &[String], registry: &TokenRegistry, chain_id: u64) -> Result<Vec<SweepToken>, SweepError> {
+    entries
+        .iter()
+        .map(|entry| {
+            let invalid = || SweepError::InvalidEntry(entry.clone());
+            let (symbol, working) = entry.split_once(':').ok_or_else(invalid)?;
+            let working_balance: f64 = working.trim().parse().map_err(|_| invalid())?;
+            if !working_balance.is_finite() || working_balance < 0.0 {
+                return Err(invalid());
+            }
+            let symbol = symbol.trim();
+            let address = match symbol.parse::<Address>() {
+                Ok(address) => address,
+                Err(_) => registry
+                    .resolve_symbol(chain_id, symbol)
+                    .ok_or_else(|| SweepError::UnknownToken(symbol.to_string()))?,
+            };
+            let symbol = registry.symbol_of(chain_id, address).unwrap_or_else(|| symbol.to_string());
+            Ok(SweepToken { symbol, address, working_balance })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepConfig {
+    /// Chain the wallets are swept on (`execution_chain`)
+    pub chain_id: u64,
+    /// The chain's native coin, e.g. "BNB"
+    pub native_symbol: String,
+    pub profit_wallet: Address,
+    pub interval: Duration,
+    /// Native balance (whole units) kept in every wallet
+    pub native_working_balance: f64,
+    pub tokens: Vec<SweepToken>,
+    pub min_usd: f64,
+    pub max_gas_pct: f64,
+    pub max_transfers_per_run: usize,
+}
+
+impl SweepConfig {
+    pub fn from_settings(settings: &Settings, registry: &TokenRegistry, chain: &ChainConfig) -> Result<Self, SweepError> {
+        let wallet = settings.profit_wallet.clone().unwrap_or_default();
+        let profit_wallet = wallet
+            .trim()
+            .parse::<Address>()
+            .ok()
+            .filter(|a| !a.is_zero())
+            .ok_or(SweepError::InvalidProfitWallet(wallet))?;
+        Ok(Self {
+            chain_id: chain.chain_id,
+            native_symbol: chain.native_token.clone(),
+            profit_wallet,
+            interval: Duration::from_millis(settings.profit_sweep_interval_ms),
+            // Never sweep a wallet below the balance the pool needs to keep using it
+            native_working_balance: settings.sweep_native_working_balance.max(settings.wallet_min_balance),
+            tokens: parse_sweep_tokens(&settings.sweep_tokens, registry, chain.chain_id)?,
+            min_usd: settings.sweep_min_usd,
+            max_gas_pct: settings.sweep_max_gas_pct,
+            max_transfers_per_run: settings.sweep_max_transfers_per_run,
+        })
+    }
+}
+
+/// A wallet's balance of one sweep token and the price it was valued at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBalance {
+    pub token: SweepToken,
+    pub balance: TokenAmount,
+    pub price_usd: Option<f64>,
+}
+
+/// Balances of one executor wallet at the start of a sweep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletBalances {
+    pub wallet: Address,
+    pub label: String,
+    pub native: U256,
+    pub tokens: Vec<TokenBalance>,
+}
+
+/// One transfer of excess balance to the profit wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepTransfer {
+    pub wallet: Address,
+    pub wallet_label: String,
+    /// None for the native coin
+    pub token: Option<Address>,
+    pub symbol: String,
+    pub amount: TokenAmount,
+    pub usd: Option<f64>,
+}
+
+impl SweepTransfer {
+    fn gas_limit(&self) -> u64 {
+        if self.token.is_some() { TOKEN_TRANSFER_GAS } else { NATIVE_TRANSFER_GAS }
+    }
+}
+
+// Whether `usd` of excess is worth sweeping at a worst-case gas price of `gas_price` wei
+fn worth_sweeping(config: &SweepConfig, symbol: &str, usd: Option<f64>, gas_limit: u64, gas_price: U256, native_price: Option<f64>) -> bool {
+    let Some(usd) = usd else {
+        // Unpriced excess is only swept when there is no threshold to apply
+        if config.min_usd > 0.0 {
+            debug!("[ProfitSweeper] Cannot value {} excess; not sweeping it", symbol);
+        }
+        return config.min_usd <= 0.0;
+    };
+    if usd < config.min_usd {
+        debug!("[ProfitSweeper] {} excess ${:.2} is below sweep_min_usd", symbol, usd);
+        return false;
+    }
+    let gas_usd = native_price.and_then(|price| TokenAmount::new(gas_price.saturating_mul(gas_limit.into()), NATIVE_DECIMALS).usd(price));
+    if let Some(gas_usd) = gas_usd
+        && config.max_gas_pct > 0.0
+        && gas_usd > usd * config.max_gas_pct / 100.0
+    {
+        debug!("[ProfitSweeper] Gas ${:.4} is too much to sweep {} worth ${:.2}", gas_usd, symbol, usd);
+        return false;
+    }
+    true
+}
+
+/// The transfers for one sweep run: every token excess worth sweeping, then the native excess
+/// after the gas for those transfers and its own, largest first and capped at
+/// `max_transfers_per_run`.
+pub fn plan_sweeps(config: &SweepConfig, wallets: &[WalletBalances], gas_price: U256, native_price: Option<f64>) -> Vec<SweepTransfer> {
+    let mut transfers = Vec::new();
+    for wallet in wallets {
+        let transfer = |token: Option<Address>, symbol: &str, amount: TokenAmount, usd: Option<f64>| SweepTransfer {
+            wallet: wallet.wallet,
+            wallet_label: wallet.label.clone(),
+            token,
+            symbol: symbol.to_string(),
+            amount,
+            usd,
+        };
+        let mut gas_reserved = U256::zero();
+        for held in &wallet.tokens {
+            let decimals = held.balance.decimals();
+            let Ok(working) = TokenAmount::from_f64(held.token.working_balance, decimals) else { continue };
+            let Ok(excess) = held.balance.checked_sub(&working) else { continue };
+            if excess.is_zero() {
+                continue;
+            }
+            let usd = held.price_usd.and_then(|price| excess.usd(price));
+            if worth_sweeping(config, &held.token.symbol, usd, TOKEN_TRANSFER_GAS, gas_price, native_price) {
+                gas_reserved = gas_reserved.saturating_add(gas_price.saturating_mul(TOKEN_TRANSFER_GAS.into()));
+                transfers.push(transfer(Some(held.token.address), &held.token.symbol, excess, usd));
+            }
+        }
+
+        let Ok(working) = TokenAmount::from_f64(config.native_working_balance, NATIVE_DECIMALS) else { continue };
+        let own_gas = gas_price.saturating_mul(NATIVE_TRANSFER_GAS.into());
+        let keep = working.raw().saturating_add(gas_reserved).saturating_add(own_gas);
+        let Some(excess) = wallet.native.checked_sub(keep).filter(|e| !e.is_zero()) else { continue };
+        let excess = TokenAmount::new(excess, NATIVE_DECIMALS);
+        let usd = native_price.and_then(|price| excess.usd(price));
+        if worth_sweeping(config, &config.native_symbol, usd, NATIVE_TRANSFER_GAS, gas_price, native_price) {
+            transfers.push(transfer(None, &config.native_symbol, excess, usd));
+        }
+    }
+    transfers.sort_by(|a, b| b.usd.unwrap_or(0.0).total_cmp(&a.usd.unwrap_or(0.0)));
+    transfers.truncate(config.max_transfers_per_run);
+    transfers
+}
+
+// Unsigned transaction moving `transfer` from its wallet to the profit wallet
+fn transfer_tx(transfer: &SweepTransfer, config: &SweepConfig, nonce: U256, fees: &FeeSuggestion) -> TypedTransaction {
+    let to = config.profit_wallet;
+    let request = match transfer.token {
+        Some(token) => {
+            let mut data = id("transfer(address,uint256)").to_vec();
+            data.extend(ethers::abi::encode(&[Token::Address(to), Token::Uint(transfer.amount.raw())]));
+            TransactionRequest::new().to(token).data(data)
+        }
+        None => TransactionRequest::new().to(to).value(transfer.amount.raw()),
+    };
+    let mut tx: TypedTransaction = request
+        .from(transfer.wallet)
+        .nonce(nonce)
+        .gas(transfer.gas_limit())
+        .chain_id(config.chain_id)
+        .into();
+    fees.apply(&mut tx);
+    tx
+}
+
+pub struct ProfitSweeper {
+    config: SweepConfig,
+    wallet_pool: Arc<WalletPool>,
+    // Token registry and router quotes for USD valuation
+    planner: Arc<ExecutionPlanner>,
+    nonce_manager: Arc<NonceManager>,
+    gas_oracle: Arc<GasOracle>,
+    execution_log: Arc<ExecutionLog>,
+    // Follows sweeps to confirmation; without it they are fire-and-forget
+    tracker: Option<Arc<TxTracker>>,
+    // Books swept balances and the wallets' opening balances
+    ledger: Option<Arc<Ledger>>,
+    // Quorum provider for balance, price and nonce reads; the wallets' own client when None
+    quorum: Option<Arc<QuorumProvider>>,
+}
+
+impl ProfitSweeper {
+    pub fn new(
+        config: SweepConfig,
+        wallet_pool: Arc<WalletPool>,
+        planner: Arc<ExecutionPlanner>,
+        nonce_manager: Arc<NonceManager>,
+        gas_oracle: Arc<GasOracle>,
+        execution_log: Arc<ExecutionLog>,
+    ) -> Self {
+        Self { config, wallet_pool, planner, nonce_manager, gas_oracle, execution_log, tracker: None, ledger: None, quorum: None }
+    }
+
+    /// Track sweep transactions to confirmation (`transaction_*` settings)
+    pub fn with_tracker(mut self, tracker: Arc<TxTracker>) -> Self {
+        self.tracker = Some(tracker);
+        self
+    }
+
+    /// Book sweeps (once mined, if tracked) and the balances read for them in `ledger`
+    pub fn with_ledger(mut self, ledger: Arc<Ledger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Read balances, prices and nonces through `quorum` instead of the wallets' client
+    pub fn with_quorum(mut self, quorum: Arc<QuorumProvider>) -> Self {
+        self.quorum = Some(quorum);
+        self
+    }
+
+    pub fn config(&self) -> &SweepConfig {
+        &self.config
+    }
+
+    // Native and sweep-token balances of `wallet`, read through `reads`; None if the native
+    // balance can't be read
+    async fn wallet_balances<R: Middleware + 'static>(&self, wallet: &PooledWallet, reads: Arc<R>) -> Option<WalletBalances> {
+        let registry = self.planner.token_registry();
+        let native = match reads.get_balance(wallet.address(), None).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                warn!("[ProfitSweeper] Balance check for {} failed: {}", wallet.label, e);
+                return None;
+            }
+        };
+        wallet.record_balance(self.config.chain_id, native);
+        let mut tokens = Vec::new();
+        for token in &self.config.tokens {
+            let Some(raw) = balance_of(&*reads, token.address, wallet.address()).await else {
+                warn!("[ProfitSweeper] {} balance of {} unavailable", token.symbol, wallet.label);
+                continue;
+            };
+            if raw.is_zero() {
+                continue;
+            }
+            if registry.decimals(self.config.chain_id, token.address).is_err()
+                && let Err(e) = registry.fetch_metadata(self.config.chain_id, token.address, reads.clone()).await
+            {
+                warn!("[ProfitSweeper] {}", e);
+                continue;
+            }
+            let Ok(balance) = registry.amount(self.config.chain_id, token.address, raw) else { continue };
+            let price_usd = self.planner.price_usd(reads.clone(), token.address).await;
+            tokens.push(TokenBalance { token: token.clone(), balance, price_usd });
+        }
+        Some(WalletBalances { wallet: wallet.address(), label: wallet.label.clone(), native, tokens })
+    }
+
+    /// Sweep every wallet's excess once. Returns the transfers that were sent.
+    pub async fn sweep(&self) -> Vec<SweepTransfer> {
+        let wallets = self.wallet_pool.wallets();
+        let Some(client) = wallets.iter().find_map(|w| w.client(self.config.chain_id)) else {
+            warn!("[ProfitSweeper] No executor wallet has a client for chain {}", self.config.chain_id);
+            return Vec::new();
+        };
+        match self.quorum.clone() {
+            Some(reads) => self.sweep_with(client, reads).await,
+            None => self.sweep_with(client.clone(), client).await,
+        }
+    }
+
+    // Sweep with balance, price and nonce reads going through `reads`; `client` is any wallet's
+    // client, used for fee suggestions
+    async fn sweep_with<R: Middleware + 'static>(&self, client: Arc<SignerHttpProvider>, reads: Arc<R>) -> Vec<SweepTransfer> {
+        let wallets = self.wallet_pool.wallets();
+        let fees = match self.gas_oracle.current(&*client).await {
+            Ok(fees) => fees,
+            Err(e) => {
+                warn!("[ProfitSweeper] No gas price available; skipping sweep: {}", e);
+                return Vec::new();
+            }
+        };
+        // The native coin is valued at the wrapped native token's price
+        let native_price = match self.planner.wrapped_native() {
+            Some(wrapped) => self.planner.price_usd(reads.clone(), wrapped).await,
+            None => None,
+        };
+        let mut balances = Vec::new();
+        for wallet in wallets {
+            if wallet.pending() > 0 {
+                // Its balances are about to change; sweep it next time
+                debug!("[ProfitSweeper] {} has pending transactions; not sweeping it", wallet.label);
+                continue;
+            }
+            if wallet.client(self.config.chain_id).is_none() {
+                continue;
+            }
+            balances.extend(self.wallet_balances(wallet, reads.clone()).await);
+        }
+        if let Some(ledger) = &self.ledger {
+            for wallet in &balances {
+                let native = TokenAmount::new(wallet.native, NATIVE_DECIMALS);
+                ledger.open_position(self.config.chain_id, wallet.wallet, NATIVE_TOKEN, &self.config.native_symbol, native, native_price);
+                for held in &wallet.tokens {
+                    ledger.open_position(self.config.chain_id, wallet.wallet, held.token.address, &held.token.symbol, held.balance, held.price_usd);
+                }
+            }
+        }
+
+        let planned = plan_sweeps(&self.config, &balances, fees.max_price_per_gas(), native_price);
+        let mut sent = Vec::new();
+        let mut tracking = Vec::new();
+        for transfer in planned {
+            let Some(wallet) = wallets.iter().find(|w| w.address() == transfer.wallet) else { continue };
+            let Some(client) = wallet.client(self.config.chain_id) else { continue };
+            match self.send(client.clone(), &*reads, &transfer, &fees).await {
+                Ok((tx, tx_hash)) => {
+                    info!(
+                        "[ProfitSweeper] Swept {} {} from {} to {:?}: 0x{:x}",
+                        transfer.amount, transfer.symbol, transfer.wallet_label, self.config.profit_wallet, tx_hash
+                    );
+                    self.log(&transfer, Some(tx_hash), None);
+                    let gas_price = tx.gas_price().unwrap_or_default();
+                    match &self.tracker {
+                        Some(tracker) => {
+                            let swept = transfer.clone();
+                            tracking.push(async move { (tracker.track(client, tx, tx_hash).await, swept, gas_price) });
+                        }
+                        // Untracked: book it as sent, with gas at its limit
+                        None => self.book(&transfer, gas_price.saturating_mul(transfer.gas_limit().into())),
+                    }
+                    sent.push(transfer);
+                }
+                Err(e) => {
+                    error!("[ProfitSweeper] Sweeping {} {} from {} failed: {}", transfer.amount, transfer.symbol, transfer.wallet_label, e);
+                    // The node disagrees with our counter; take its view
+                    if is_nonce_error(&e)
+                        && let Err(resync_err) = self.nonce_manager.resync(&*reads, self.config.chain_id, transfer.wallet).await
+                    {
+                        error!("[ProfitSweeper] Nonce resync failed: {}", resync_err);
+                    }
+                    self.log(&transfer, None, Some(e));
+                }
+            }
+        }
+        // The tracker writes each final status back to its ExecutionLog record
+        for (outcome, transfer, gas_price) in join_all(tracking).await {
+            match outcome {
+                TxOutcome::Confirmed { gas_used, .. } => {
+                    let gas_used = gas_used.unwrap_or_else(|| transfer.gas_limit().into());
+                    self.book(&transfer, gas_used.saturating_mul(gas_price));
+                }
+                other => warn!("[ProfitSweeper] Sweep of {} {} from {} ended {:?}", transfer.amount, transfer.symbol, transfer.wallet_label, other),
+            }
+        }
+        sent
+    }
+
+    fn book(&self, transfer: &SweepTransfer, gas_cost: U256) {
+        if let Some(ledger) = &self.ledger {
+            let token = transfer.token.unwrap_or(NATIVE_TOKEN);
+            ledger.record_sweep(self.config.chain_id, transfer.wallet, self.config.profit_wallet, token, transfer.amount, gas_cost);
+        }
+    }
+
+    async fn send<M: Middleware + 'static, R: Middleware>(&self, client: Arc<M>, reads: &R, transfer: &SweepTransfer, fees: &FeeSuggestion) -> Result<(TypedTransaction, TxHash), String> {
+        let nonce = self
+            .nonce_manager
+            .next_nonce(reads, self.config.chain_id, transfer.wallet)
+            .await
+            .map_err(|e| e.to_string())?;
+        let tx = transfer_tx(transfer, &self.config, nonce, fees);
+        match client.send_transaction(tx.clone(), None).await {
+            Ok(pending) => Ok((tx, pending.tx_hash())),
+            Err(e) => {
+                let message = e.to_string();
+                // Nothing went out with this nonce; hand it out again
+                if !is_nonce_error(&message) {
+                    self.nonce_manager.release(self.config.chain_id, transfer.wallet, nonce).await;
+                }
+                Err(message)
+            }
+        }
+    }
+
+    fn log(&self, transfer: &SweepTransfer, tx_hash: Option<TxHash>, error: Option<String>) {
+        self.execution_log.log(ExecutionRecord {
+            timestamp: chrono::Utc::now(),
+            protocol: "sweep".to_string(),
+            account: format!("{:?}", transfer.wallet),
+            debt: 0.0,
+            collateral: 0.0,
+            success: false,
+            // Moving realized profit is not itself profit
+            profit: 0.0,
+            gas_used: None,
+            gas_cost_native: None,
+            tx_hash: tx_hash.map(|h| format!("0x{:x}", h)),
+            status: if error.is_some() { TxStatus::Failed } else { TxStatus::Pending },
+            error,
+            transfer: Some(TransferRecord {
+                token: transfer.symbol.clone(),
+                amount: transfer.amount.to_string(),
+                from: format!("{:?}", transfer.wallet),
+                to: format!("{:?}", self.config.profit_wallet),
+                usd: transfer.usd,
+            }),
+        });
+    }
+
+    /// Sweep every `profit_sweep_interval_ms`, forever.
+    pub async fn run(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(self.config.interval.max(Duration::from_millis(1)));
+        loop {
+            ticker.tick().await;
+            let sent = self.sweep().await;
+            if !sent.is_empty() {
+                let usd: f64 = sent.iter().filter_map(|t| t.usd).sum();
+                info!("[ProfitSweeper] Sent {} sweep transfers worth ${:.2}", sent.len(), usd);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_registry::BSC_MAINNET_CHAIN_ID;
+    use ethers::utils::parse_ether;
+
+    fn config(busd: Address, wbnb: Address) -> SweepConfig {
+        SweepConfig {
+            chain_id: BSC_MAINNET_CHAIN_ID,
+            native_symbol: "BNB".to_string(),
+            profit_wallet: Address::from_low_u64_be(0xbeef),
+            interval: Duration::from_secs(600),
+            native_working_balance: 0.5,
+            tokens: vec![
+                SweepToken { symbol: "BUSD".to_string(), address: busd, working_balance: 500.0 },
+                SweepToken { symbol: "WBNB".to_string(), address: wbnb, working_balance: 1.0 },
+            ],
+            min_usd: 50.0,
+            max_gas_pct: 2.0,
+            max_transfers_per_run: 10,
+        }
+    }
+
+    fn held(token: &SweepToken, amount: &str, price_usd: Option<f64>) -> TokenBalance {
+        TokenBalance { token: token.clone(), balance: TokenAmount::parse(amount, 18).unwrap(), price_usd }
+    }
+
+    #[test]
+    fn test_parse_sweep_tokens() {
+        let registry = TokenRegistry::new();
+        let busd = Address::from_low_u64_be(1);
+        registry.register(BSC_MAINNET_CHAIN_ID, "BUSD", busd, Some(18));
+        let entries = vec!["BUSD:500".to_string(), format!("{:?}:0.5", busd)];
+        let tokens = parse_sweep_tokens(&entries, &registry, BSC_MAINNET_CHAIN_ID).unwrap();
+        assert_eq!(tokens[0], SweepToken { symbol: "BUSD".to_string(), address: busd, working_balance: 500.0 });
+        assert_eq!((tokens[1].symbol.as_str(), tokens[1].working_balance), ("BUSD", 0.5));
+        assert_eq!(parse_sweep_tokens(&["BUSD".to_string()], &registry, BSC_MAINNET_CHAIN_ID), Err(SweepError::InvalidEntry("BUSD".to_string())));
+        assert_eq!(parse_sweep_tokens(&["BUSD:-1".to_string()], &registry, BSC_MAINNET_CHAIN_ID), Err(SweepError::InvalidEntry("BUSD:-1".to_string())));
+        assert_eq!(parse_sweep_tokens(&["DOGE:1".to_string()], &registry, BSC_MAINNET_CHAIN_ID), Err(SweepError::UnknownToken("DOGE".to_string())));
+    }
+
+    #[test]
+    fn test_plan_keeps_working_balances_and_thresholds() {
+        let (busd, wbnb) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let config = config(busd, wbnb);
+        let gas_price = U256::from(3_000_000_000u64); // 3 gwei
+        let wallets = vec![
+            WalletBalances {
+                wallet: Address::from_low_u64_be(10),
+                label: "default".to_string(),
+                native: parse_ether("2.5").unwrap(),
+                tokens: vec![held(&config.tokens[0], "1750.25", Some(1.0)), held(&config.tokens[1], "1.05", Some(600.0))],
+            },
+            WalletBalances {
+                wallet: Address::from_low_u64_be(11),
+                label: "matrix1".to_string(),
+                native: parse_ether("0.5").unwrap(),
+                tokens: vec![held(&config.tokens[0], "520", Some(1.0))],
+            },
+        ];
+        let transfers = plan_sweeps(&config, &wallets, gas_price, Some(600.0));
+        // WBNB excess of 0.05 ($30) and matrix1's $20 of BUSD wait for a later sweep; matrix1
+        // has no native excess at all
+        assert_eq!(transfers.len(), 2);
+        assert_eq!((transfers[0].symbol.as_str(), transfers[0].amount.to_string()), ("BUSD", "1250.25".to_string()));
+        assert_eq!((transfers[0].token, transfers[0].usd), (Some(busd), Some(1250.25)));
+        // 2.5 BNB less the 0.5 working balance and gas for both transfers (0.0003 + 0.000063)
+        assert_eq!((transfers[1].symbol.as_str(), transfers[1].amount.to_string()), ("BNB", "1.999637".to_string()));
+        assert_eq!(transfers[1].token, None);
+
+        let tx = transfer_tx(&transfers[0], &config, U256::from(7u64), &FeeSuggestion::Legacy { gas_price });
+        assert_eq!((tx.to_addr(), tx.nonce(), tx.value()), (Some(&busd), Some(&U256::from(7u64)), None));
+        assert_eq!(tx.data().unwrap()[..4], id("transfer(address,uint256)"));
+
+        // Batching: only the largest transfer per run
+        let capped = SweepConfig { max_transfers_per_run: 1, ..config.clone() };
+        assert_eq!(plan_sweeps(&capped, &wallets, gas_price, Some(600.0)).len(), 1);
+        // At 1000 gwei a token transfer costs $60 of gas, over 2% of $1250.25
+        let transfers = plan_sweeps(&config, &wallets, U256::from(1_000_000_000_000u64), Some(600.0));
+        assert_eq!(transfers.iter().map(|t| t.symbol.as_str()).collect::<Vec<_>>(), ["BNB"]);
+        // Without a native price only the BUSD excess can be valued
+        let transfers = plan_sweeps(&config, &wallets, gas_price, None);
+        assert_eq!(transfers.iter().map(|t| t.symbol.as_str()).collect::<Vec<_>>(), ["BUSD"]);
+    }
+}
@@ -0,0 +1,265 @@
+// Rolling per-endpoint health statistics (latency, error rate, head-block lag) collected from
+// periodic eth_blockNumber probes and from real traffic through the failover transport.
+// Lower scores are better; ProviderRotation::next_best_provider uses them for selection.
+
+use crate::providers_round_robin::SharedRotation;
+use ethers::providers::{Http, JsonRpcClient};
+use ethers::types::U64;
+use log::warn;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Number of recent requests the latency and error-rate averages are taken over.
+const SAMPLE_WINDOW: usize = 50;
+/// Score penalty (ms-equivalent) for a 100% error rate.
+const ERROR_RATE_PENALTY_MS: f64 = 2_000.0;
+/// Score penalty (ms-equivalent) per block behind the best known head.
+const BLOCK_LAG_PENALTY_MS: f64 = 250.0;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct EndpointStats {
+    latencies_ms: VecDeque<f64>,
+    outcomes: VecDeque<bool>,
+    head_block: Option<u64>,
+}
+
+impl EndpointStats {
+    fn push_outcome(&mut self, ok: bool) {
+        if self.outcomes.len() == SAMPLE_WINDOW {
+            self.outcomes.pop_front();
+        }
+        self.outcomes.push_back(ok);
+    }
+
+    fn avg_latency_ms(&self) -> Option<f64> {
+        if self.latencies_ms.is_empty() {
+            return None;
+        }
+        Some(self.latencies_ms.iter().sum::<f64>() / self.latencies_ms.len() as f64)
+    }
+
+    fn error_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        self.outcomes.iter().filter(|ok| !**ok).count() as f64 / self.outcomes.len() as f64
+    }
+}
+
+/// Health report for one endpoint, as served by `/api/providers/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderHealthReport {
+    pub name: String,
+    pub avg_latency_ms: Option<f64>,
+    pub error_rate: f64,
+    pub head_block: Option<u64>,
+    pub block_lag: Option<u64>,
+    pub samples: usize,
+    pub score: f64,
+}
+
+/// Health statistics for the endpoints of one chain.
+#[derive(Debug)]
+pub struct ProviderHealth {
+    endpoints: Vec<(String, Http)>,
+    stats: Mutex<HashMap<String, EndpointStats>>,
+    // Probes bypass the rotation, so each one is counted against its provider's quotas here
+    rotation: Option<SharedRotation>,
+}
+
+impl ProviderHealth {
+    /// `endpoints` are (name, url) pairs, probed by `probe_all`.
+    pub fn new(endpoints: &[(String, String)]) -> Result<Self, url::ParseError> {
+        let endpoints = endpoints
+            .iter()
+            .map(|(name, url)| Ok((name.clone(), Http::new(Url::parse(url)?))))
+            .collect::<Result<Vec<_>, url::ParseError>>()?;
+        let stats = endpoints.iter().map(|(name, _)| (name.clone(), EndpointStats::default())).collect();
+        Ok(Self { endpoints, stats: Mutex::new(stats), rotation: None })
+    }
+
+    /// Count each probe against the per-provider limits and quotas of `rotation`
+    pub fn with_rotation(mut self, rotation: SharedRotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    pub fn record_success(&self, name: &str, latency: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let entry = stats.entry(name.to_string()).or_default();
+        if entry.latencies_ms.len() == SAMPLE_WINDOW {
+            entry.latencies_ms.pop_front();
+        }
+        entry.latencies_ms.push_back(latency.as_secs_f64() * 1000.0);
+        entry.push_outcome(true);
+    }
+
+    pub fn record_failure(&self, name: &str) {
+        self.stats.lock().unwrap().entry(name.to_string()).or_default().push_outcome(false);
+    }
+
+    pub fn record_head_block(&self, name: &str, block: u64) {
+        self.stats.lock().unwrap().entry(name.to_string()).or_default().head_block = Some(block);
+    }
+
+    fn best_head(stats: &HashMap<String, EndpointStats>) -> Option<u64> {
+        stats.values().filter_map(|s| s.head_block).max()
+    }
+
+    fn score_of(stats: &EndpointStats, best_head: Option<u64>) -> f64 {
+        let lag = match (best_head, stats.head_block) {
+            (Some(best), Some(head)) => best.saturating_sub(head),
+            _ => 0,
+        };
+        stats.avg_latency_ms().unwrap_or(0.0)
+            + stats.error_rate() * ERROR_RATE_PENALTY_MS
+            + lag as f64 * BLOCK_LAG_PENALTY_MS
+    }
+
+    /// Selection score for `name` (lower is better). Endpoints without samples score 0 so
+    /// they get tried and measured.
+    pub fn score(&self, name: &str) -> f64 {
+        let stats = self.stats.lock().unwrap();
+        let best_head = Self::best_head(&stats);
+        stats.get(name).map(|s| Self::score_of(s, best_head)).unwrap_or(0.0)
+    }
+
+    /// Current statistics for every endpoint, best score first.
+    pub fn reports(&self) -> Vec<ProviderHealthReport> {
+        let stats = self.stats.lock().unwrap();
+        let best_head = Self::best_head(&stats);
+        let mut reports: Vec<ProviderHealthReport> = stats
+            .iter()
+            .map(|(name, s)| ProviderHealthReport {
+                name: name.clone(),
+                avg_latency_ms: s.avg_latency_ms(),
+                error_rate: s.error_rate(),
+                head_block: s.head_block,
+                block_lag: best_head.zip(s.head_block).map(|(best, head)| best.saturating_sub(head)),
+                samples: s.outcomes.len(),
+                score: Self::score_of(s, best_head),
+            })
+            .collect();
+        reports.sort_by(|a, b| a.score.total_cmp(&b.score));
+        reports
+    }
+
+    /// Probe every endpoint once with eth_blockNumber, recording latency and head block.
+    pub async fn probe_all(&self) {
+        for (name, client) in &self.endpoints {
+            if let Some(rotation) = &self.rotation {
+                rotation.lock().unwrap().record_request(name);
+            }
+            let started = Instant::now();
+            let result = tokio::time::timeout(PROBE_TIMEOUT, client.request::<_, U64>("eth_blockNumber", ())).await;
+            match result {
+                Ok(Ok(block)) => {
+                    self.record_success(name, started.elapsed());
+                    self.record_head_block(name, block.as_u64());
+                }
+                Ok(Err(e)) => {
+                    warn!("[ProviderHealth] Probe of {} failed: {}", name, e);
+                    self.record_failure(name);
+                }
+                Err(_) => {
+                    warn!("[ProviderHealth] Probe of {} timed out", name);
+                    self.record_failure(name);
+                }
+            }
+        }
+    }
+
+    /// Probe all endpoints every `interval`, forever.
+    pub async fn run_probes(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.probe_all().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health() -> ProviderHealth {
+        ProviderHealth::new(&[
+            ("Fast".to_string(), "http://fast".to_string()),
+            ("Slow".to_string(), "http://slow".to_string()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_score_prefers_fast_reliable_providers() {
+        let health = health();
+        health.record_success("Fast", Duration::from_millis(40));
+        health.record_success("Slow", Duration::from_millis(400));
+        assert!(health.score("Fast") < health.score("Slow"));
+
+        // Errors outweigh a latency advantage
+        for _ in 0..5 {
+            health.record_failure("Fast");
+        }
+        assert!(health.score("Fast") > health.score("Slow"));
+        assert_eq!(health.reports()[0].name, "Slow");
+    }
+
+    #[test]
+    fn test_block_lag_penalty() {
+        let health = health();
+        health.record_success("Fast", Duration::from_millis(40));
+        health.record_success("Slow", Duration::from_millis(100));
+        health.record_head_block("Fast", 1_000);
+        health.record_head_block("Slow", 1_003);
+        let reports = health.reports();
+        let fast = reports.iter().find(|r| r.name == "Fast").unwrap();
+        assert_eq!(fast.block_lag, Some(3));
+        assert!(health.score("Fast") > health.score("Slow"));
+    }
+
+    #[tokio::test]
+    async fn test_probes_count_against_quota() {
+        use crate::providers_round_robin::{ProviderEntry, ProviderRotation};
+        let entry = |name: &str| ProviderEntry {
+            name: name.to_string(),
+            url: "http://127.0.0.1:1".to_string(),
+            max_requests_per_minute: 100,
+            monthly_limit: None,
+            hourly_limit: None,
+            daily_limit: None,
+            requests_this_window: 0,
+            window_start: None,
+            requests_this_hour: 0,
+            hour_start: None,
+            requests_today: 0,
+            day_start: None,
+            last_used: None,
+            cooldown_until: None,
+            requests_this_month: 0,
+            month_start: None,
+            billing_reset_day: 1,
+            quota_warn_percentage: 80.0,
+            quota_hard_stop_percentage: 100.0,
+            quota_warned: false,
+            quota_exhausted_logged: false,
+        };
+        let rotation = Arc::new(Mutex::new(ProviderRotation::new(vec![entry("A"), entry("B")], Duration::from_secs(60))));
+        // Nothing listens on port 1: the probes fail, but they were still sent
+        let health = ProviderHealth::new(&[
+            ("A".to_string(), "http://127.0.0.1:1".to_string()),
+            ("B".to_string(), "http://127.0.0.1:1".to_string()),
+        ])
+        .unwrap()
+        .with_rotation(rotation.clone());
+        health.probe_all().await;
+        let rotation = rotation.lock().unwrap();
+        assert!(rotation.entries().all(|e| e.requests_today == 1 && e.requests_this_month == 1));
+        assert_eq!(health.reports()[0].error_rate, 1.0);
+    }
+}
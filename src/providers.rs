@@ -1,10 +1,21 @@
 // /home/nox/Fusion/src/providers.rs
 
+use crate::chain_registry::{ChainConfig, ChainRegistry};
 use crate::config::Settings; // Use crate:: to refer to config module in the same crate
-use crate::providers_round_robin::{ProviderEntry, ProviderRotation};
+use crate::failover_transport::{FailoverHttp, DEFAULT_FAILURE_COOLDOWN};
+use crate::gas_oracle::{GasOracle, GasOracleConfig};
+use crate::nonce_manager::NonceManager;
+use crate::provider_health::{ProviderHealth, ProviderHealthReport};
+use crate::providers_round_robin::{ProviderEntry, ProviderRotation, SharedRotation};
+use crate::quorum::{QuorumHttp, QuorumMetrics};
+use crate::quota_store::QuotaStore;
+use crate::remote_signer::ExecutorSigner;
+use crate::wallet_pool::{WalletPool, WalletReport};
 use ethers::providers::{Http, Middleware, Provider, ProviderError, Ws};
-use ethers::signers::{LocalWallet, Signer};
-use std::sync::Arc;
+use ethers::signers::Signer;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use log::{info, warn};
 use thiserror::Error;
 use url::Url; // For custom error types
 
@@ -14,18 +25,21 @@ pub enum ProviderManagerError {
     UrlParse(#[from] url::ParseError),
     #[error("Provider connection error: {0}")]
     ProviderConnection(#[from] ProviderError),
-    #[error("Invalid private key: {0}")]
+    #[error("Wallet unavailable: {0}")]
     WalletError(String), // ethers::signers::WalletError doesn't impl Error directly sometimes
     #[error("No valid providers configured or connectable")]
     NoValidProvider,
     #[error("Unsupported provider name: {0}")]
     UnsupportedProvider(String),
+    #[error("Chain {0} is not configured or not connected")]
+    UnknownChain(u64),
 }
 
 // Define types for clarity
-pub type HttpProvider = Provider<Http>;
+pub type HttpProvider = Provider<FailoverHttp>;
 pub type WsProvider = Provider<Ws>;
-pub type SignerHttpProvider = ethers::middleware::SignerMiddleware<HttpProvider, LocalWallet>;
+pub type QuorumProvider = Provider<QuorumHttp>;
+pub type SignerHttpProvider = ethers::middleware::SignerMiddleware<HttpProvider, ExecutorSigner>;
 // pub type SignerWsProvider = ethers::middleware::SignerMiddleware<WsProvider, LocalWallet>; // If needed later
 
 // A simple structure to hold provider connections for a specific chain (e.g., BSC Mainnet)
@@ -34,19 +48,33 @@ pub struct ChainProvider {
     pub http_provider: Arc<SignerHttpProvider>, // Use Arc for shared ownership
     // pub ws_provider: Option<Arc<WsProvider>>, // Add WebSocket later if needed directly here
     pub chain_id: u64, // Store chain ID for reference
+    // Registry entry this provider was built from (name, native token, explorer, gas model)
+    pub config: ChainConfig,
+    // Round-robin provider rotation, shared with this chain's failover transport
+    pub rotation: SharedRotation,
+    // Cross-checks critical reads (reserves, balances, nonces) across all verified providers.
+    // None when quorum mode is disabled or fewer providers than the threshold are connectable.
+    pub quorum_provider: Option<Arc<QuorumProvider>>,
+    // Rolling latency/error/lag statistics driving provider selection for this chain
+    pub health: Arc<ProviderHealth>,
+    // Buffered, capped fee suggestions for this chain's gas model
+    pub gas_oracle: Arc<GasOracle>,
 }
 
 pub struct ProviderManager {
-    pub bsc_provider: Option<ChainProvider>,
-    pub eth_provider: Option<ChainProvider>,
-    // Add testnet providers if needed
-    // pub bsc_testnet_provider: Option<ChainProvider>,
-    // pub eth_sepolia_provider: Option<ChainProvider>,
-    // Store the wallet derived from the private key
-    wallet: LocalWallet,
-    // New: round-robin provider rotation for BSC and ETH
-    pub bsc_rotation: Option<ProviderRotation>,
-    pub eth_rotation: Option<ProviderRotation>,
+    // Connected chains keyed by chain ID (every enabled `[[chains]]` entry that answered)
+    pub chains: HashMap<u64, ChainProvider>,
+    pub registry: Arc<ChainRegistry>,
+    // Executor signer: local key (keystore / PRIVATE_KEY) or remote signing service
+    wallet: ExecutorSigner,
+    // Agreement/disagreement counters for the quorum providers of all chains
+    pub quorum_metrics: Arc<QuorumMetrics>,
+    // Persistent quota counters; None if the store could not be opened
+    pub quota_store: Option<Arc<QuotaStore>>,
+    // Local nonce allocation per (chain, wallet), shared by all submitters
+    pub nonce_manager: Arc<NonceManager>,
+    // Executor wallets (the primary signer plus per-matrix wallets) transactions are assigned to
+    pub wallet_pool: Arc<WalletPool>,
 }
 
 impl ProviderManager {
@@ -64,262 +92,332 @@ impl ProviderManager {
 }
 
 impl ProviderManager {
-    /// Example: Get the next available BSC provider (for future use)
-    pub fn next_bsc_provider(&mut self) -> Option<&mut ProviderEntry> {
-        self.bsc_rotation.as_mut()?.next_provider()
+    /// Connected provider for `chain_id`
+    pub fn chain(&self, chain_id: u64) -> Option<&ChainProvider> {
+        self.chains.get(&chain_id)
     }
-    /// Example: Mark BSC provider as failed (for future use)
-    pub fn mark_bsc_provider_failure(&mut self, name: &str, cooldown: std::time::Duration) {
-        if let Some(rotation) = self.bsc_rotation.as_mut() {
-            rotation.mark_provider_failure(name, cooldown);
+    /// Connected provider for `chain_id`, or an error naming the missing chain
+    pub fn require_chain(&self, chain_id: u64) -> Result<&ChainProvider, ProviderManagerError> {
+        self.chain(chain_id).ok_or(ProviderManagerError::UnknownChain(chain_id))
+    }
+    /// Get the next available provider from the rotation used by the chain's transport
+    pub fn next_provider(&self, chain_id: u64) -> Option<ProviderEntry> {
+        self.chain(chain_id)?.rotation.lock().unwrap().next_provider().cloned()
+    }
+    /// Quorum provider for critical reads on `chain_id`, if quorum mode is available for it
+    pub fn quorum_provider(&self, chain_id: u64) -> Option<Arc<QuorumProvider>> {
+        self.chain(chain_id)?.quorum_provider.clone()
+    }
+    /// Health reports per chain ID, best-scoring provider first
+    pub fn provider_health_reports(&self) -> HashMap<u64, Vec<ProviderHealthReport>> {
+        self.chains.iter().map(|(chain_id, p)| (*chain_id, p.health.reports())).collect()
+    }
+    /// Start periodic eth_blockNumber probes for every connected chain
+    pub fn spawn_health_probes(&self, interval: std::time::Duration) {
+        for provider in self.chains.values() {
+            tokio::spawn(provider.health.clone().run_probes(interval));
         }
     }
-    pub async fn new(settings: Arc<Settings>) -> Result<Self, ProviderManagerError> {
-        // Create wallet from private key (robust parsing)
-        let raw_key = settings
-            .private_key
-            .as_ref()
-            .expect("PRIVATE_KEY must be set in environment or config")
-            .trim()
-            .to_string();
-        println!("[DEBUG] Raw PRIVATE_KEY: {:?}, length: {}", raw_key, raw_key.len());
-        // Try parsing as-is
-        let wallet = match raw_key.parse::<LocalWallet>() {
-            Ok(w) => Ok(w),
-            Err(e1) => {
-                // Try with 0x prefix
-                let with_0x = if raw_key.starts_with("0x") { raw_key.clone() } else { format!("0x{}", raw_key) };
-                match with_0x.parse::<LocalWallet>() {
-                    Ok(w) => Ok(w),
-                    Err(e2) => Err(ProviderManagerError::WalletError(format!(
-                        "Tried parsing PRIVATE_KEY as-is (error: {}), and with 0x prefix (error: {})",
-                        e1, e2
-                    )))
-                }
+    /// Periodically persist the quota counters of every rotation to the quota store
+    pub fn spawn_quota_persistence(&self, interval: std::time::Duration) {
+        let Some(store) = self.quota_store.clone() else { return };
+        let rotations = self
+            .chains
+            .iter()
+            .map(|(chain_id, p)| (chain_id.to_string(), p.rotation.clone()))
+            .collect();
+        tokio::spawn(store.run_persistence(rotations, interval));
+    }
+    /// Periodically detect and fill nonce gaps of every pool wallet on every connected chain
+    pub fn spawn_nonce_gap_monitor(&self, interval: std::time::Duration) {
+        for wallet in self.wallet_pool.wallets() {
+            for chain_id in self.chains.keys() {
+                let Some(client) = wallet.client(*chain_id) else { continue };
+                tokio::spawn(self.nonce_manager.clone().run_gap_monitor(
+                    client,
+                    *chain_id,
+                    wallet.address(),
+                    interval,
+                ));
             }
-        }?;
+        }
+    }
+    /// Periodically refresh the native balances of the pool wallets on every connected chain,
+    /// through the chain's quorum provider where there is one
+    pub fn spawn_wallet_balance_monitor(&self, interval: std::time::Duration) {
+        let chains = self.chains.iter().map(|(chain_id, p)| (*chain_id, p.quorum_provider.clone())).collect();
+        tokio::spawn(self.wallet_pool.clone().run_balance_monitor(chains, interval));
+    }
+    /// Pending transaction counts and balances of the executor wallets
+    pub fn wallet_reports(&self) -> Vec<WalletReport> {
+        self.wallet_pool.reports()
+    }
+    /// Start polling fee suggestions for every connected chain
+    pub fn spawn_gas_oracles(&self) {
+        for provider in self.chains.values() {
+            tokio::spawn(provider.gas_oracle.clone().run(provider.http_provider.clone()));
+        }
+    }
+    /// Mark a provider as failed; the chain's transport skips it until the cooldown ends
+    pub fn mark_provider_failure(&self, chain_id: u64, name: &str, cooldown: std::time::Duration) {
+        if let Some(provider) = self.chain(chain_id) {
+            provider.rotation.lock().unwrap().mark_provider_failure(name, cooldown);
+        }
+    }
+    pub async fn new(settings: Arc<Settings>) -> Result<Self, ProviderManagerError> {
+        // Remote signer if configured, else the encrypted keystore, else PRIVATE_KEY
+        let wallet = ExecutorSigner::from_settings(&settings)
+            .await
+            .map_err(|e| ProviderManagerError::WalletError(e.to_string()))?;
 
-        // --- Try connecting to BSC Mainnet ---
-        let bsc_provider = Self::connect_chain(
-            &settings.provider_priority_order,
-            &settings,
-            Chain::BscMainnet,
-            wallet.clone(), // Clone wallet for the middleware
-        )
-        .await;
+        let quorum_metrics = Arc::new(QuorumMetrics::new());
 
-        // --- Try connecting to ETH Mainnet ---
-        let eth_provider = Self::connect_chain(
-            &settings.provider_priority_order,
-            &settings,
-            Chain::EthMainnet,
-            wallet.clone(), // Clone wallet for the middleware
-        )
-        .await;
+        // --- Connect every enabled chain from the registry ---
+        let registry = Arc::new(ChainRegistry::from_settings(&settings));
+        let mut chains = HashMap::new();
+        for chain in registry.enabled() {
+            match Self::connect_chain(
+                &settings.provider_priority_order,
+                &settings,
+                chain,
+                wallet.clone(), // Clone wallet for the middleware
+                quorum_metrics.clone(),
+            )
+            .await
+            {
+                Ok(provider) => {
+                    chains.insert(chain.chain_id, provider);
+                }
+                Err(e) => warn!("[ProviderManager] Could not connect to {} (Chain ID: {}): {}", chain.name, chain.chain_id, e),
+            }
+        }
 
-        // Check if at least one connection succeeded (adjust as needed)
-        if bsc_provider.is_err() && eth_provider.is_err() {
+        // Check if at least one connection succeeded
+        if chains.is_empty() {
             return Err(ProviderManagerError::NoValidProvider);
         }
 
-        // Prepare round-robin rotations for BSC and ETH (future use)
-        let bsc_rotation = Some(ProviderRotation::new(
-            vec![
-                ProviderEntry {
-                    name: "BSC-Primary".to_string(),
-                    url: settings.bsc_rpc_url.clone(),
-                    max_requests_per_minute: 60,
-                    monthly_limit: Some(3_000_000), // Example: Infura
-                    hourly_limit: Some(3_000_000 / 30 / 24), // ≈4,166/hour
-                    daily_limit: Some(3_000_000 / 30), // ≈100,000/day
-                    requests_this_window: 0,
-                    window_start: None,
-                    requests_this_hour: 0,
-                    hour_start: None,
-                    requests_today: 0,
-                    day_start: None,
-                    last_used: None,
-                    cooldown_until: None,
-                },
-                // Add more BSC providers here as needed
-            ],
-            std::time::Duration::from_secs(60),
-        ));
-        let eth_rotation = Some(ProviderRotation::new(
-            vec![
-                ProviderEntry {
-                    name: "ETH-Primary".to_string(),
-                    url: settings.eth_rpc_url.clone(),
-                    max_requests_per_minute: 60,
-                    monthly_limit: Some(3_000_000), // Example: Infura
-                    hourly_limit: Some(3_000_000 / 30 / 24), // ≈4,166/hour
-                    daily_limit: Some(3_000_000 / 30), // ≈100,000/day
-                    requests_this_window: 0,
-                    window_start: None,
-                    requests_this_hour: 0,
-                    hour_start: None,
-                    requests_today: 0,
-                    day_start: None,
-                    last_used: None,
-                    cooldown_until: None,
-                },
-                // Add more ETH providers here as needed
-            ],
-            std::time::Duration::from_secs(60),
-        ));
+        // Restore persisted quota usage so restarts don't reset the counters
+        let quota_store = match QuotaStore::open(&settings.quota_store_path) {
+            Ok(store) => Some(Arc::new(store)),
+            Err(e) => {
+                warn!("[ProviderManager] Quota store {} unavailable: {}", settings.quota_store_path, e);
+                None
+            }
+        };
+        if let Some(store) = &quota_store {
+            for (chain_id, provider) in &chains {
+                store.restore(&chain_id.to_string(), &mut provider.rotation.lock().unwrap());
+            }
+        }
+
+        // Per-wallet signer middleware on every connected chain, sharing each chain's transport
+        let providers: Vec<(u64, HttpProvider)> =
+            chains.iter().map(|(chain_id, p)| (*chain_id, p.http_provider.inner().clone())).collect();
+        let wallet_pool = WalletPool::from_settings(&settings, wallet.clone(), &providers)
+            .await
+            .map_err(|e| ProviderManagerError::WalletError(e.to_string()))?;
 
         Ok(Self {
-            bsc_provider: bsc_provider.ok(), // Store Ok result, None otherwise
-            eth_provider: eth_provider.ok(), // Store Ok result, None otherwise
-            bsc_rotation,
-            eth_rotation,
+            chains,
+            registry,
+            wallet_pool: Arc::new(wallet_pool),
+            quorum_metrics,
+            quota_store,
+            nonce_manager: Arc::new(NonceManager::new(std::time::Duration::from_millis(
+                settings.nonce_gap_grace_ms,
+            ))),
             wallet, // Store the original wallet
         })
     }
 
-    // Helper to connect to a specific chain based on priority
+    // Helper to connect to a specific chain: every provider in priority order that answers with
+    // the expected chain ID joins the rotation behind a single failover transport.
     async fn connect_chain(
         priority: &[String],
         settings: &Settings,
-        chain: Chain,
-        wallet: LocalWallet,
+        chain: &ChainConfig,
+        wallet: ExecutorSigner,
+        quorum_metrics: Arc<QuorumMetrics>,
     ) -> Result<ChainProvider, ProviderManagerError> {
-        type ProviderUrlFn = fn(&Settings, &str) -> Option<String>;
-        let (chain_id, get_urls): (u64, ProviderUrlFn) = match chain {
-            Chain::BscMainnet => (56, |s, p| match p {
-                "Infura" => Some(s.bsc_rpc_url.clone()),
-                "Alchemy" => Some(s.alchemy_bsc_rpc_url.clone()),
-                "NodeReal" => Some(s.nodereal_bsc_rpc_url.clone()),
-                _ => None,
-            }),
-            Chain::EthMainnet => (1, |s, p| match p {
-                "Infura" => Some(s.eth_rpc_url.clone()),
-                "Alchemy" => Some(s.alchemy_eth_rpc_url.clone()),
-                "NodeReal" => Some(s.nodereal_eth_rpc_url.clone()),
-                _ => None,
-            }),
-            // Add other chains (BscTestnet, EthSepolia) here if needed
-        };
-
-        println!(
-            "Attempting to connect to {:?} (Chain ID: {})...",
-            chain, chain_id
-        );
+        let chain_id = chain.chain_id;
+        info!("[ProviderManager] Connecting to {} (Chain ID: {})...", chain.name, chain_id);
 
-        for provider_name in priority {
-            if let Some(mut rpc_url_str) = get_urls(settings, provider_name) {
-                // Substitute env vars at runtime
-                rpc_url_str = Self::substitute_provider_keys(&rpc_url_str);
-                println!(
-                    "  Trying provider: {} at URL: {}",
-                    provider_name, rpc_url_str
-                );
-                let url = match Url::parse(&rpc_url_str) {
-                    Ok(url) => url,
-                    Err(e) => {
-                        eprintln!("    Warning: Invalid URL for {}: {}", provider_name, e);
-                        continue;
-                    }
-                };
-                let http_client = Http::new(url);
-                let provider = Provider::new(http_client);
-                // Check connection with a simple call like getting chain ID
-                match provider.get_chainid().await {
-                    Ok(id) if id.as_u64() == chain_id => {
-                        println!("    Successfully connected to {}!", provider_name);
-                        // Add wallet middleware
-                        let signer_provider = ethers::middleware::SignerMiddleware::new(
-                            provider,
-                            wallet.with_chain_id(chain_id), // Ensure wallet has correct chain ID
-                        );
-                        return Ok(ChainProvider {
-                            http_provider: Arc::new(signer_provider),
-                            chain_id,
-                        });
-                    }
-                    Ok(id) => eprintln!(
-                        "    Warning: Connected to {} but chain ID mismatch (Expected: {}, Got: {})",
-                        provider_name, chain_id, id
-                    ),
-                    Err(e) => eprintln!(
-                        "    Warning: Failed to verify connection to {}: {}",
-                        provider_name, e
-                    ),
+        let mut entries = Vec::new();
+        for (provider_name, rpc_url_str) in chain.rpc_endpoints(priority) {
+            info!("[ProviderManager] Trying provider {} for {}", provider_name, chain.name);
+            let url = match Url::parse(&rpc_url_str) {
+                Ok(url) => url,
+                Err(e) => {
+                    warn!("[ProviderManager] Invalid URL for {}: {}", provider_name, e);
+                    continue;
                 }
-            } else {
-                eprintln!(
-                    "    Skipping unsupported provider for this chain: {}",
-                    provider_name
-                );
+            };
+            let provider = Provider::new(Http::new(url));
+            // Check connection with a simple call like getting chain ID
+            match provider.get_chainid().await {
+                Ok(id) if id.as_u64() == chain_id => {
+                    info!("[ProviderManager] Connected to {} on {}", provider_name, chain.name);
+                    entries.push(Self::rotation_entry(settings, &provider_name, &rpc_url_str));
+                }
+                Ok(id) => warn!(
+                    "[ProviderManager] Connected to {} but chain ID mismatch (Expected: {}, Got: {})",
+                    provider_name, chain_id, id
+                ),
+                Err(e) => warn!("[ProviderManager] Failed to verify connection to {}: {}", provider_name, e),
             }
         }
 
-        Err(ProviderManagerError::NoValidProvider) // Return error if no provider worked
+        if entries.is_empty() {
+            return Err(ProviderManagerError::NoValidProvider); // Return error if no provider worked
+        }
+        let endpoints: Vec<(String, String)> =
+            entries.iter().map(|e| (e.name.clone(), e.url.clone())).collect();
+        let connected = entries.len();
+        let rotation: SharedRotation = Arc::new(Mutex::new(ProviderRotation::new(
+            entries,
+            std::time::Duration::from_secs(60),
+        )));
+        let health = Arc::new(ProviderHealth::new(&endpoints)?.with_rotation(rotation.clone()));
+        let quorum_provider = if settings.quorum_enabled && connected >= settings.quorum_threshold {
+            let transport = QuorumHttp::new(
+                &endpoints,
+                settings.quorum_threshold,
+                std::time::Duration::from_millis(settings.quorum_timeout_ms),
+                quorum_metrics,
+            )?
+            .with_rotation(rotation.clone());
+            info!(
+                "[ProviderManager] Quorum reads enabled for {}: {} of {} providers must agree",
+                chain.name,
+                settings.quorum_threshold,
+                endpoints.len()
+            );
+            Some(Arc::new(Provider::new(transport)))
+        } else {
+            if settings.quorum_enabled {
+                warn!(
+                    "[ProviderManager] Quorum disabled for {}: {} provider(s) connected, threshold is {}",
+                    chain.name,
+                    connected,
+                    settings.quorum_threshold
+                );
+            }
+            None
+        };
+        let transport = FailoverHttp::new(rotation.clone(), DEFAULT_FAILURE_COOLDOWN)?.with_health(health.clone());
+        // Add wallet middleware
+        let signer_provider = ethers::middleware::SignerMiddleware::new(
+            Provider::new(transport),
+            wallet.with_chain_id(chain_id), // Ensure wallet has correct chain ID
+        );
+        Ok(ChainProvider {
+            http_provider: Arc::new(signer_provider),
+            chain_id,
+            config: chain.clone(),
+            rotation,
+            quorum_provider,
+            health,
+            gas_oracle: Arc::new(GasOracle::new(chain_id, chain.gas_model, GasOracleConfig::from_settings(settings, chain))),
+        })
     }
 
-    /// Substitute ${INFURA_API_KEY}, ${ALCHEMY_API_KEY}, ${NODEREAL_API_KEY} in a URL string
-    fn substitute_provider_keys(url: &str) -> String {
-        let mut out = url.to_string();
-        if let Ok(val) = std::env::var("INFURA_API_KEY") {
-            out = out.replace("${INFURA_API_KEY}", &val);
-        }
-        if let Ok(val) = std::env::var("ALCHEMY_API_KEY") {
-            out = out.replace("${ALCHEMY_API_KEY}", &val);
+    // Rotation entry with the provider's configured request limits and quota policy
+    fn rotation_entry(settings: &Settings, name: &str, url: &str) -> ProviderEntry {
+        let (monthly_limit, daily_limit, hourly_limit) = settings.provider_quota_limits(name);
+        ProviderEntry {
+            name: name.to_string(),
+            url: url.to_string(),
+            max_requests_per_minute: settings.provider_rate_limit(name),
+            monthly_limit,
+            hourly_limit,
+            daily_limit,
+            requests_this_window: 0,
+            window_start: None,
+            requests_this_hour: 0,
+            hour_start: None,
+            requests_today: 0,
+            day_start: None,
+            last_used: None,
+            cooldown_until: None,
+            requests_this_month: 0,
+            month_start: None,
+            billing_reset_day: settings.billing_reset_day(name),
+            quota_warn_percentage: settings.quota_warn_percentage,
+            quota_hard_stop_percentage: settings.quota_hard_stop_percentage,
+            quota_warned: false,
+            quota_exhausted_logged: false,
         }
-        if let Ok(val) = std::env::var("NODEREAL_API_KEY") {
-            out = out.replace("${NODEREAL_API_KEY}", &val);
-        }
-        out
     }
-    pub fn get_wallet(&self) -> &LocalWallet {
+
+    pub fn get_wallet(&self) -> &ExecutorSigner {
         &self.wallet
     }
 }
 
-// Enum to represent supported chains easily
-#[derive(Debug, Clone, Copy)]
-enum Chain {
-    BscMainnet,
-    EthMainnet,
-    // BscTestnet,
-    // EthSepolia,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chain_registry::GasModel;
+    use ethers::signers::LocalWallet;
     use std::time::Duration;
 
     #[test]
     fn test_provider_manager_rotation_integration() {
-        let mut pm = ProviderManager {
-            bsc_provider: None,
-            eth_provider: None,
-            wallet: LocalWallet::from_bytes(&[1u8; 32]).unwrap(),
-            bsc_rotation: Some(ProviderRotation::new(
-                vec![ProviderEntry {
-                    name: "BSC-1".to_string(),
-                    url: "http://bsc1".to_string(),
-                    max_requests_per_minute: 2,
-                    monthly_limit: Some(0),
-                    hourly_limit: Some(0),
-                    daily_limit: Some(0),
-                    requests_this_window: 0,
-                    window_start: None,
-                    requests_this_hour: 0,
-                    hour_start: None,
-                    requests_today: 0,
-                    day_start: None,
-                    last_used: None,
-                    cooldown_until: None,
-                }],
-                Duration::from_secs(60),
+        let rotation: SharedRotation = Arc::new(Mutex::new(ProviderRotation::new(
+            vec![ProviderEntry {
+                name: "BSC-1".to_string(),
+                url: "http://bsc1".to_string(),
+                max_requests_per_minute: 2,
+                monthly_limit: Some(0),
+                hourly_limit: Some(0),
+                daily_limit: Some(0),
+                requests_this_window: 0,
+                window_start: None,
+                requests_this_hour: 0,
+                hour_start: None,
+                requests_today: 0,
+                day_start: None,
+                last_used: None,
+                cooldown_until: None,
+                requests_this_month: 0,
+                month_start: None,
+                billing_reset_day: 1,
+                quota_warn_percentage: 80.0,
+                quota_hard_stop_percentage: 100.0,
+                quota_warned: false,
+                quota_exhausted_logged: false,
+            }],
+            Duration::from_secs(60),
+        )));
+        let wallet = ExecutorSigner::Local(LocalWallet::from_bytes(&[1u8; 32]).unwrap());
+        let transport = FailoverHttp::new(rotation.clone(), DEFAULT_FAILURE_COOLDOWN).unwrap();
+        let config = ChainConfig { chain_id: 56, name: "BSC".to_string(), ..Default::default() };
+        let bsc = ChainProvider {
+            http_provider: Arc::new(ethers::middleware::SignerMiddleware::new(
+                Provider::new(transport),
+                wallet.clone().with_chain_id(56u64),
             )),
-            eth_rotation: None,
+            chain_id: 56,
+            gas_oracle: Arc::new(GasOracle::new(56, GasModel::Legacy, GasOracleConfig::from_settings(&Settings::default(), &config))),
+            config,
+            rotation,
+            quorum_provider: None,
+            health: Arc::new(ProviderHealth::new(&[("BSC-1".to_string(), "http://bsc1".to_string())]).unwrap()),
+        };
+        let pm = ProviderManager {
+            chains: HashMap::from([(56, bsc)]),
+            registry: Arc::new(ChainRegistry::default()),
+            wallet,
+            quorum_metrics: Arc::new(QuorumMetrics::new()),
+            quota_store: None,
+            nonce_manager: Arc::new(NonceManager::new(Duration::from_secs(60))),
+            wallet_pool: Arc::new(WalletPool::new(crate::wallet_pool::WalletStrategy::LeastPending, 0.into())),
         };
-        assert!(pm.next_bsc_provider().is_some());
-        pm.mark_bsc_provider_failure("BSC-1", Duration::from_millis(100));
+        assert!(pm.next_provider(56).is_some());
+        assert!(pm.next_provider(1).is_none());
+        pm.mark_provider_failure(56, "BSC-1", Duration::from_millis(100));
         // Should be unavailable during cooldown
-        assert!(pm.next_bsc_provider().is_none());
+        assert!(pm.next_provider(56).is_none());
     }
 }
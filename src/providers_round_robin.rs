@@ -1,17 +1,23 @@
 // Robust round-robin provider rotation with rate limiting and cooldowns
 // This module is intended to be used by ProviderManager for safe, production-grade provider selection
 
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use log::warn;
 use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+/// Rotation shared between ProviderManager and the failover transport.
+pub type SharedRotation = Arc<Mutex<ProviderRotation>>;
+
 #[derive(Debug, Clone)]
 pub struct ProviderEntry {
     pub name: String,
     pub url: String,
     pub max_requests_per_minute: u32,
-    pub monthly_limit: Option<u64>, // e.g., 3_000_000 for Infura
-    pub hourly_limit: Option<u64>,  // calculated from monthly_limit
-    pub daily_limit: Option<u64>,   // calculated from monthly_limit
+    pub monthly_limit: Option<u64>, // provider_monthly_limits; None = unlimited
+    pub hourly_limit: Option<u64>,  // provider_hourly_limits; None = unlimited
+    pub daily_limit: Option<u64>,   // provider_daily_limits; None = unlimited
     pub requests_this_window: u32,
     pub window_start: Option<Instant>,
     pub requests_this_hour: u64,
@@ -20,9 +26,72 @@ pub struct ProviderEntry {
     pub day_start: Option<chrono::DateTime<chrono::Utc>>,
     pub last_used: Option<Instant>,
     pub cooldown_until: Option<Instant>,
+    pub requests_this_month: u64,
+    pub month_start: Option<DateTime<Utc>>, // start of the current billing period
+    pub billing_reset_day: u32,             // day of month (UTC) the provider resets its quota
+    pub quota_warn_percentage: f64,         // warn once usage reaches this % of monthly_limit
+    pub quota_hard_stop_percentage: f64,    // stop routing once usage reaches this % of monthly_limit
+    pub quota_warned: bool,                 // the warn-threshold message was logged this period
+    pub quota_exhausted_logged: bool,       // the hard-stop message was logged this period
+}
+
+/// Start of the billing period containing `now` for a quota that resets on `reset_day`
+/// (clamped to 1..=28 so every month has that day).
+pub fn billing_period_start(now: DateTime<Utc>, reset_day: u32) -> DateTime<Utc> {
+    let reset_day = reset_day.clamp(1, 28);
+    let (year, month) = if now.day() >= reset_day {
+        (now.year(), now.month())
+    } else if now.month() == 1 {
+        (now.year() - 1, 12)
+    } else {
+        (now.year(), now.month() - 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, reset_day)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+        .unwrap_or(now)
 }
 
 impl ProviderEntry {
+    /// Reset the monthly counter at the billing-period boundary and check the monthly quota.
+    /// Logs a warning once usage crosses `quota_warn_percentage`; returns false once it
+    /// reaches `quota_hard_stop_percentage`.
+    pub fn enforce_quota(&mut self) -> bool {
+        let period_start = billing_period_start(Utc::now(), self.billing_reset_day);
+        if self.month_start.is_none_or(|start| start < period_start) {
+            self.month_start = Some(period_start);
+            self.requests_this_month = 0;
+            self.quota_warned = false;
+            self.quota_exhausted_logged = false;
+        }
+        let Some(monthly) = self.monthly_limit else {
+            return true;
+        };
+        let used_percentage = if monthly == 0 {
+            100.0
+        } else {
+            self.requests_this_month as f64 / monthly as f64 * 100.0
+        };
+        if used_percentage >= self.quota_hard_stop_percentage {
+            if !self.quota_exhausted_logged {
+                warn!(
+                    "[ProviderRotation] {} reached {:.1}% of its monthly quota ({}/{}); routing stopped until reset",
+                    self.name, used_percentage, self.requests_this_month, monthly
+                );
+                self.quota_exhausted_logged = true;
+            }
+            return false;
+        }
+        if used_percentage >= self.quota_warn_percentage && !self.quota_warned {
+            warn!(
+                "[ProviderRotation] {} at {:.1}% of its monthly quota ({}/{})",
+                self.name, used_percentage, self.requests_this_month, monthly
+            );
+            self.quota_warned = true;
+        }
+        true
+    }
+
     pub fn enforce_steady_limits(&mut self) -> bool {
         use chrono::Utc;
         let now = Utc::now();
@@ -48,16 +117,16 @@ impl ProviderEntry {
             self.requests_today = 0;
         }
         // Enforce hourly limit
-        if let Some(hourly) = self.hourly_limit {
-            if self.requests_this_hour >= hourly {
-                return false;
-            }
+        if let Some(hourly) = self.hourly_limit
+            && self.requests_this_hour >= hourly
+        {
+            return false;
         }
         // Enforce daily limit
-        if let Some(daily) = self.daily_limit {
-            if self.requests_today >= daily {
-                return false;
-            }
+        if let Some(daily) = self.daily_limit
+            && self.requests_today >= daily
+        {
+            return false;
         }
         true
     }
@@ -73,6 +142,7 @@ impl ProviderEntry {
     }
 }
 
+#[derive(Debug)]
 pub struct ProviderRotation {
     providers: VecDeque<ProviderEntry>,
     window: Duration,
@@ -117,6 +187,75 @@ impl ProviderRotation {
         None // All providers exhausted or on cooldown
     }
 
+    /// Pick the available provider with the lowest `score` (e.g. ProviderHealth::score) among
+    /// those still within their per-minute, hourly and daily limits. Ties keep rotation order.
+    pub fn next_best_provider(&mut self, score: impl Fn(&ProviderEntry) -> f64) -> Option<&mut ProviderEntry> {
+        let now = Instant::now();
+        let mut best: Option<(usize, f64)> = None;
+        for (index, entry) in self.providers.iter_mut().enumerate() {
+            match entry.window_start {
+                Some(start) if now.duration_since(start) <= self.window => {}
+                _ => {
+                    entry.window_start = Some(now);
+                    entry.requests_this_window = 0;
+                }
+            }
+            if !entry.is_available()
+                || entry.requests_this_window >= entry.max_requests_per_minute
+                || !entry.enforce_steady_limits()
+                || !entry.enforce_quota()
+            {
+                continue;
+            }
+            let entry_score = score(entry);
+            if best.is_none_or(|(_, best_score)| entry_score < best_score) {
+                best = Some((index, entry_score));
+            }
+        }
+        let (index, _) = best?;
+        // Move the chosen entry to the back so ties rotate
+        let mut entry = self.providers.remove(index)?;
+        entry.last_used = Some(now);
+        entry.requests_this_window += 1;
+        entry.requests_this_hour += 1;
+        entry.requests_today += 1;
+        entry.requests_this_month += 1;
+        self.providers.push_back(entry);
+        self.providers.back_mut()
+    }
+
+    /// Count a request sent to `name` outside the rotation (e.g. quorum fan-out) against its
+    /// per-minute, hourly, daily and monthly counters.
+    pub fn record_request(&mut self, name: &str) {
+        let now = Instant::now();
+        let window = self.window;
+        let Some(entry) = self.providers.iter_mut().find(|e| e.name == name) else {
+            return;
+        };
+        match entry.window_start {
+            Some(start) if now.duration_since(start) <= window => {}
+            _ => {
+                entry.window_start = Some(now);
+                entry.requests_this_window = 0;
+            }
+        }
+        // Roll the hour, day and billing period over before counting
+        entry.enforce_steady_limits();
+        entry.enforce_quota();
+        entry.requests_this_window += 1;
+        entry.requests_this_hour += 1;
+        entry.requests_today += 1;
+        entry.requests_this_month += 1;
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ProviderEntry> {
+        self.providers.iter()
+    }
+
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut ProviderEntry> {
+        self.providers.iter_mut()
+    }
+
     pub fn mark_provider_failure(&mut self, name: &str, cooldown: Duration) {
         for entry in self.providers.iter_mut() {
             if entry.name == name {
@@ -150,6 +289,13 @@ mod tests {
                     day_start: Some(chrono::Utc::now()),
                     last_used: None,
                     cooldown_until: None,
+                    requests_this_month: 0,
+                    month_start: None,
+                    billing_reset_day: 1,
+                    quota_warn_percentage: 80.0,
+                    quota_hard_stop_percentage: 100.0,
+                    quota_warned: false,
+                    quota_exhausted_logged: false,
                 },
                 ProviderEntry {
                     name: "B".into(),
@@ -166,6 +312,13 @@ mod tests {
                     day_start: Some(chrono::Utc::now()),
                     last_used: None,
                     cooldown_until: None,
+                    requests_this_month: 0,
+                    month_start: None,
+                    billing_reset_day: 1,
+                    quota_warn_percentage: 80.0,
+                    quota_hard_stop_percentage: 100.0,
+                    quota_warned: false,
+                    quota_exhausted_logged: false,
                 },
             ],
             Duration::from_secs(60),
@@ -203,6 +356,13 @@ mod tests {
                 cooldown_until: None,
                 requests_this_window: 0,
                 window_start: None,
+                requests_this_month: 0,
+                month_start: None,
+                billing_reset_day: 1,
+                quota_warn_percentage: 80.0,
+                quota_hard_stop_percentage: 100.0,
+                quota_warned: false,
+                quota_exhausted_logged: false,
             }],
             Duration::from_secs(60),
         );
@@ -212,4 +372,135 @@ mod tests {
         sleep(Duration::from_secs(1));
         assert!(rotation.next_provider().is_some());
     }
+
+    #[test]
+    fn test_next_best_provider_prefers_lowest_score_within_limits() {
+        let entry = |name: &str, hourly: Option<u64>| ProviderEntry {
+            name: name.into(),
+            url: format!("url{}", name),
+            max_requests_per_minute: 2,
+            monthly_limit: None,
+            hourly_limit: hourly,
+            daily_limit: None,
+            requests_this_window: 0,
+            window_start: None,
+            requests_this_hour: 0,
+            hour_start: None,
+            requests_today: 0,
+            day_start: None,
+            last_used: None,
+            cooldown_until: None,
+            requests_this_month: 0,
+            month_start: None,
+            billing_reset_day: 1,
+            quota_warn_percentage: 80.0,
+            quota_hard_stop_percentage: 100.0,
+            quota_warned: false,
+            quota_exhausted_logged: false,
+        };
+        let mut rotation = ProviderRotation::new(
+            vec![entry("Slow", None), entry("Fast", None), entry("Capped", Some(0))],
+            Duration::from_secs(60),
+        );
+        let score = |e: &ProviderEntry| match e.name.as_str() {
+            "Fast" => 10.0,
+            "Capped" => 0.0,
+            _ => 100.0,
+        };
+        // Capped is best-scored but over its hourly limit
+        assert_eq!(rotation.next_best_provider(score).unwrap().name, "Fast");
+        assert_eq!(rotation.next_best_provider(score).unwrap().name, "Fast");
+        // Fast has used its per-minute budget
+        let slow = rotation.next_best_provider(score).unwrap();
+        assert_eq!(slow.name, "Slow");
+        assert_eq!(slow.requests_this_hour, 1);
+        assert_eq!(slow.requests_today, 1);
+    }
+
+    #[test]
+    fn test_record_request_counts_against_quota() {
+        let mut rotation = ProviderRotation::new(
+            vec![ProviderEntry {
+                name: "A".into(),
+                url: "urlA".into(),
+                max_requests_per_minute: 100,
+                monthly_limit: Some(2),
+                hourly_limit: None,
+                daily_limit: None,
+                requests_this_window: 0,
+                window_start: None,
+                requests_this_hour: 0,
+                hour_start: None,
+                requests_today: 0,
+                day_start: None,
+                last_used: None,
+                cooldown_until: None,
+                requests_this_month: 0,
+                month_start: None,
+                billing_reset_day: 1,
+                quota_warn_percentage: 80.0,
+                quota_hard_stop_percentage: 100.0,
+                quota_warned: false,
+                quota_exhausted_logged: false,
+            }],
+            Duration::from_secs(60),
+        );
+        rotation.record_request("A");
+        rotation.record_request("A");
+        rotation.record_request("Unknown");
+        let entry = rotation.entries().next().unwrap();
+        assert_eq!((entry.requests_this_window, entry.requests_today, entry.requests_this_month), (2, 2, 2));
+        // The monthly quota is used up by requests the rotation never handed out
+        assert!(rotation.next_best_provider(|_| 0.0).is_none());
+    }
+
+    #[test]
+    fn test_billing_period_start() {
+        let at = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(12, 0, 0).unwrap().and_utc();
+        let start = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc();
+        assert_eq!(billing_period_start(at(2024, 3, 20), 15), start(2024, 3, 15));
+        assert_eq!(billing_period_start(at(2024, 3, 10), 15), start(2024, 2, 15));
+        assert_eq!(billing_period_start(at(2024, 1, 3), 15), start(2023, 12, 15));
+        // Reset days past the 28th are clamped
+        assert_eq!(billing_period_start(at(2024, 2, 28), 31), start(2024, 2, 28));
+    }
+
+    #[test]
+    fn test_quota_warn_and_hard_stop() {
+        let mut entry = ProviderEntry {
+            name: "A".into(),
+            url: "urlA".into(),
+            max_requests_per_minute: 100,
+            monthly_limit: Some(10),
+            hourly_limit: None,
+            daily_limit: None,
+            requests_this_window: 0,
+            window_start: None,
+            requests_this_hour: 0,
+            hour_start: None,
+            requests_today: 0,
+            day_start: None,
+            last_used: None,
+            cooldown_until: None,
+            requests_this_month: 0,
+            month_start: None,
+            billing_reset_day: 1,
+            quota_warn_percentage: 50.0,
+            quota_hard_stop_percentage: 90.0,
+            quota_warned: false,
+            quota_exhausted_logged: false,
+        };
+        assert!(entry.enforce_quota());
+        entry.requests_this_month = 5;
+        assert!(entry.enforce_quota());
+        assert!(entry.quota_warned);
+        entry.requests_this_month = 9;
+        assert!(!entry.enforce_quota());
+        // The hard stop is reported even though the warning already was
+        assert!(entry.quota_exhausted_logged);
+        // A new billing period clears the counter
+        entry.month_start = Some(billing_period_start(Utc::now(), 1) - chrono::Duration::days(40));
+        assert!(entry.enforce_quota());
+        assert_eq!(entry.requests_this_month, 0);
+    }
 }
@@ -0,0 +1,279 @@
+// Quorum JSON-RPC transport: sends each request to every configured endpoint concurrently and
+// only returns a result once `threshold` of them agree. Used for critical reads (reserves,
+// balances, nonces) where a single lagging or faulty provider must not be trusted.
+
+use crate::providers_round_robin::SharedRotation;
+use async_trait::async_trait;
+use ethers::providers::{Http, HttpClientError, JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use futures::future::join_all;
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum QuorumError {
+    #[error(transparent)]
+    Client(#[from] HttpClientError),
+    #[error("Failed to serialize request params: {0}")]
+    Serialize(serde_json::Error),
+    #[error("Failed to deserialize quorum result: {0}")]
+    Deserialize(serde_json::Error),
+    #[error("No quorum for {method}: best agreement {agreeing}/{responded} responses, {threshold} required")]
+    NoQuorum { method: String, agreeing: usize, responded: usize, threshold: usize },
+}
+
+impl From<QuorumError> for ProviderError {
+    fn from(src: QuorumError) -> Self {
+        match src {
+            QuorumError::Client(e) => e.into(),
+            e => ProviderError::CustomError(e.to_string()),
+        }
+    }
+}
+
+impl RpcError for QuorumError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            QuorumError::Client(e) => e.as_error_response(),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            QuorumError::Client(e) => e.as_serde_error(),
+            QuorumError::Serialize(e) | QuorumError::Deserialize(e) => Some(e),
+            QuorumError::NoQuorum { .. } => None,
+        }
+    }
+}
+
+/// Per-provider quorum outcome counters.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProviderQuorumStats {
+    /// Responses matching the quorum result.
+    pub agreed: u64,
+    /// Responses that differ from the quorum result.
+    pub dissented: u64,
+    /// Numeric responses (block numbers, nonces) lower than the quorum result, or timeouts.
+    pub lagging: u64,
+    pub errors: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuorumMetricsSnapshot {
+    pub requests: u64,
+    pub quorum_failures: u64,
+    pub disagreements: u64,
+    pub providers: HashMap<String, ProviderQuorumStats>,
+}
+
+/// Quorum counters, shared between the quorum transports of all chains.
+#[derive(Debug, Default)]
+pub struct QuorumMetrics {
+    requests: AtomicU64,
+    quorum_failures: AtomicU64,
+    disagreements: AtomicU64,
+    providers: Mutex<HashMap<String, ProviderQuorumStats>>,
+}
+
+impl QuorumMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> QuorumMetricsSnapshot {
+        QuorumMetricsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            quorum_failures: self.quorum_failures.load(Ordering::Relaxed),
+            disagreements: self.disagreements.load(Ordering::Relaxed),
+            providers: self.providers.lock().unwrap().clone(),
+        }
+    }
+
+    fn record(&self, provider: &str, update: impl FnOnce(&mut ProviderQuorumStats)) {
+        let mut providers = self.providers.lock().unwrap();
+        update(providers.entry(provider.to_string()).or_default());
+    }
+}
+
+enum Outcome {
+    Result(Value),
+    Error(HttpClientError),
+    Timeout,
+}
+
+/// Parse a hex quantity ("0x1a") result, used to tell laggards from genuinely different answers.
+fn as_quantity(value: &Value) -> Option<u128> {
+    let hex = value.as_str()?.strip_prefix("0x")?;
+    u128::from_str_radix(hex, 16).ok()
+}
+
+#[derive(Debug)]
+pub struct QuorumHttp {
+    endpoints: Vec<(String, Http)>,
+    threshold: usize,
+    timeout: Duration,
+    metrics: Arc<QuorumMetrics>,
+    // Every fan-out request is counted against the providers' rotation quotas
+    rotation: Option<SharedRotation>,
+}
+
+impl QuorumHttp {
+    /// `endpoints` are (name, url) pairs; `threshold` is the number of identical responses required.
+    pub fn new(
+        endpoints: &[(String, String)],
+        threshold: usize,
+        timeout: Duration,
+        metrics: Arc<QuorumMetrics>,
+    ) -> Result<Self, url::ParseError> {
+        let endpoints = endpoints
+            .iter()
+            .map(|(name, url)| Ok((name.clone(), Http::new(Url::parse(url)?))))
+            .collect::<Result<Vec<_>, url::ParseError>>()?;
+        Ok(Self { endpoints, threshold: threshold.max(1), timeout, metrics, rotation: None })
+    }
+
+    /// Count each request against the per-provider limits and quotas of `rotation`
+    pub fn with_rotation(mut self, rotation: SharedRotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn endpoint_count(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn metrics(&self) -> Arc<QuorumMetrics> {
+        self.metrics.clone()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for QuorumHttp {
+    type Error = QuorumError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, QuorumError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(&params).map_err(QuorumError::Serialize)?;
+        self.metrics.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(rotation) = &self.rotation {
+            let mut rotation = rotation.lock().unwrap();
+            for (name, _) in &self.endpoints {
+                rotation.record_request(name);
+            }
+        }
+
+        let calls = self.endpoints.iter().map(|(name, client)| {
+            let params = &params;
+            async move {
+                let outcome = match tokio::time::timeout(self.timeout, client.request::<_, Value>(method, params)).await {
+                    Ok(Ok(value)) => Outcome::Result(value),
+                    Ok(Err(e)) => Outcome::Error(e),
+                    Err(_) => Outcome::Timeout,
+                };
+                (name.as_str(), outcome)
+            }
+        });
+        let outcomes = join_all(calls).await;
+
+        // Group identical results
+        let mut groups: Vec<(&Value, Vec<&str>)> = Vec::new();
+        for (name, outcome) in &outcomes {
+            if let Outcome::Result(value) = outcome {
+                match groups.iter_mut().find(|(v, _)| *v == value) {
+                    Some((_, names)) => names.push(name),
+                    None => groups.push((value, vec![name])),
+                }
+            }
+        }
+        let responded = groups.iter().map(|(_, names)| names.len()).sum::<usize>();
+        let best = groups.iter().max_by_key(|(_, names)| names.len());
+
+        let Some((consensus, agreeing)) = best.filter(|(_, names)| names.len() >= self.threshold) else {
+            self.metrics.quorum_failures.fetch_add(1, Ordering::Relaxed);
+            let agreeing = best.map(|(_, names)| names.len()).unwrap_or(0);
+            for (name, outcome) in &outcomes {
+                match outcome {
+                    Outcome::Error(_) => self.metrics.record(name, |s| s.errors += 1),
+                    Outcome::Timeout => self.metrics.record(name, |s| s.lagging += 1),
+                    Outcome::Result(_) => {}
+                }
+            }
+            warn!(
+                "[Quorum] No quorum for {}: {}/{} agreeing responses, {} required",
+                method, agreeing, responded, self.threshold
+            );
+            // A request rejected by enough providers (e.g. a revert) is a genuine error, not a quorum failure
+            let rpc_errors = outcomes
+                .iter()
+                .filter(|(_, o)| matches!(o, Outcome::Error(HttpClientError::JsonRpcError(_))))
+                .count();
+            if rpc_errors >= self.threshold
+                && let Some((_, Outcome::Error(e))) = outcomes
+                    .into_iter()
+                    .find(|(_, o)| matches!(o, Outcome::Error(HttpClientError::JsonRpcError(_))))
+            {
+                return Err(e.into());
+            }
+            return Err(QuorumError::NoQuorum {
+                method: method.to_string(),
+                agreeing,
+                responded,
+                threshold: self.threshold,
+            });
+        };
+
+        if groups.len() > 1 {
+            self.metrics.disagreements.fetch_add(1, Ordering::Relaxed);
+        }
+        let consensus_quantity = as_quantity(consensus);
+        for (name, outcome) in &outcomes {
+            match outcome {
+                Outcome::Result(value) if value == *consensus => self.metrics.record(name, |s| s.agreed += 1),
+                Outcome::Result(value) => match (as_quantity(value), consensus_quantity) {
+                    (Some(theirs), Some(quorum)) if theirs < quorum => {
+                        warn!("[Quorum] {} is lagging on {}: {} vs quorum {}", name, method, value, consensus);
+                        self.metrics.record(name, |s| s.lagging += 1);
+                    }
+                    _ => {
+                        warn!("[Quorum] {} disagrees on {}: {} vs quorum {}", name, method, value, consensus);
+                        self.metrics.record(name, |s| s.dissented += 1);
+                    }
+                },
+                Outcome::Error(e) => {
+                    warn!("[Quorum] {} failed on {}: {}", name, method, e);
+                    self.metrics.record(name, |s| s.errors += 1);
+                }
+                Outcome::Timeout => {
+                    warn!("[Quorum] {} timed out on {} after {:?}", name, method, self.timeout);
+                    self.metrics.record(name, |s| s.lagging += 1);
+                }
+            }
+        }
+        if agreeing.len() < outcomes.len() {
+            warn!(
+                "[Quorum] {} reached quorum with {}/{} providers ({})",
+                method,
+                agreeing.len(),
+                outcomes.len(),
+                agreeing.join(", ")
+            );
+        }
+        serde_json::from_value((*consensus).clone()).map_err(QuorumError::Deserialize)
+    }
+}
@@ -0,0 +1,138 @@
+// Persistent RPC quota accounting: hourly/daily/monthly request counters of every
+// ProviderEntry are saved to a local JSON file and restored at startup, so restarts
+// don't reset usage against provider quotas.
+
+use crate::providers_round_robin::{ProviderEntry, ProviderRotation, SharedRotation};
+use chrono::{DateTime, TimeZone, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum QuotaStoreError {
+    #[error("Quota store I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Quota store is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Persisted counters for one provider. Period starts are unix seconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaRecord {
+    pub requests_this_month: u64,
+    pub month_start: Option<i64>,
+    pub requests_today: u64,
+    pub day_start: Option<i64>,
+    pub requests_this_hour: u64,
+    pub hour_start: Option<i64>,
+}
+
+fn to_unix(ts: Option<DateTime<Utc>>) -> Option<i64> {
+    ts.map(|t| t.timestamp())
+}
+
+fn from_unix(ts: Option<i64>) -> Option<DateTime<Utc>> {
+    ts.and_then(|t| Utc.timestamp_opt(t, 0).single())
+}
+
+impl QuotaRecord {
+    fn from_entry(entry: &ProviderEntry) -> Self {
+        Self {
+            requests_this_month: entry.requests_this_month,
+            month_start: to_unix(entry.month_start),
+            requests_today: entry.requests_today,
+            day_start: to_unix(entry.day_start),
+            requests_this_hour: entry.requests_this_hour,
+            hour_start: to_unix(entry.hour_start),
+        }
+    }
+
+    fn apply_to(&self, entry: &mut ProviderEntry) {
+        entry.requests_this_month = self.requests_this_month;
+        entry.month_start = from_unix(self.month_start);
+        entry.requests_today = self.requests_today;
+        entry.day_start = from_unix(self.day_start);
+        entry.requests_this_hour = self.requests_this_hour;
+        entry.hour_start = from_unix(self.hour_start);
+    }
+}
+
+/// JSON-file backed store of quota counters, keyed by "<scope>:<provider name>"
+/// (the scope is the chain ID, as each chain has its own rotation).
+pub struct QuotaStore {
+    path: PathBuf,
+    records: Mutex<HashMap<String, QuotaRecord>>,
+}
+
+impl QuotaStore {
+    /// Open the store at `path`; a missing file starts empty.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, QuotaStoreError> {
+        let path = path.as_ref().to_path_buf();
+        let records = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, records: Mutex::new(records) })
+    }
+
+    fn key(scope: &str, name: &str) -> String {
+        format!("{}:{}", scope, name)
+    }
+
+    pub fn record(&self, scope: &str, name: &str) -> Option<QuotaRecord> {
+        self.records.lock().unwrap().get(&Self::key(scope, name)).cloned()
+    }
+
+    /// Load persisted counters into the matching entries of `rotation`.
+    pub fn restore(&self, scope: &str, rotation: &mut ProviderRotation) {
+        let records = self.records.lock().unwrap();
+        for entry in rotation.entries_mut() {
+            if let Some(record) = records.get(&Self::key(scope, &entry.name)) {
+                record.apply_to(entry);
+                info!(
+                    "[QuotaStore] Restored {} usage for {}: {} this month",
+                    scope, entry.name, entry.requests_this_month
+                );
+            }
+        }
+    }
+
+    /// Copy the current counters of `rotation` into the store (in memory).
+    pub fn update(&self, scope: &str, rotation: &ProviderRotation) {
+        let mut records = self.records.lock().unwrap();
+        for entry in rotation.entries() {
+            records.insert(Self::key(scope, &entry.name), QuotaRecord::from_entry(entry));
+        }
+    }
+
+    /// Write the store to disk atomically (temp file + rename).
+    pub fn flush(&self) -> Result<(), QuotaStoreError> {
+        let contents = serde_json::to_string_pretty(&*self.records.lock().unwrap())?;
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Snapshot `rotations` and flush every `interval`, forever.
+    pub async fn run_persistence(self: Arc<Self>, rotations: Vec<(String, SharedRotation)>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            for (scope, rotation) in &rotations {
+                self.update(scope, &rotation.lock().unwrap());
+            }
+            if let Err(e) = self.flush() {
+                warn!("[QuotaStore] Failed to persist quota counters to {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
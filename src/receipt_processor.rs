@@ -0,0 +1,289 @@
+// Receipt processing: once an executor transaction is final, its receipt gives the real gas
+// used and effective gas price, and its logs give the realized profit - the contract's
+// ArbitrageExecuted event and the ERC20 Transfer logs paying the profit wallet. Amounts are
+// converted to token units and USD and written back to the matching ExecutionLog record.
+
+use crate::execution_log::ExecutionLog;
+use crate::execution_planner::ExecutionPlanner;
+use crate::token_amount::TokenAmount;
+use ethers::prelude::*;
+use ethers::utils::keccak256;
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
+static ARBITRAGE_EXECUTED_TOPIC: Lazy<H256> = Lazy::new(|| H256::from(keccak256("ArbitrageExecuted(address,uint256)")));
+static TRANSFER_TOPIC: Lazy<H256> = Lazy::new(|| H256::from(keccak256("Transfer(address,address,uint256)")));
+
+/// Profit received in one token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenProfit {
+    pub token: Address,
+    pub raw: U256,
+    /// None if the token's decimals are unknown
+    pub amount: Option<TokenAmount>,
+    pub usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealizedExecution {
+    pub tx_hash: TxHash,
+    pub success: bool,
+    pub gas_used: U256,
+    pub effective_gas_price: U256,
+    /// gas_used × effective_gas_price, in native units
+    pub gas_cost_native: f64,
+    pub gas_cost_usd: Option<f64>,
+    /// Profit reported by the executor's ArbitrageExecuted event (raw loan-token units)
+    pub reported_profit: Option<U256>,
+    /// Transfers into the profit wallet, per token
+    pub profits: Vec<TokenProfit>,
+    /// Sum of `profits` that could be valued
+    pub profit_usd: f64,
+    /// `profit_usd` less the gas cost; None if gas couldn't be valued
+    pub net_profit_usd: Option<f64>,
+}
+
+fn address_topic(address: Address) -> H256 {
+    H256::from(address)
+}
+
+/// Raw amounts transferred to `recipient` in `receipt`, summed per token in log order.
+pub fn decode_profit_transfers(receipt: &TransactionReceipt, recipient: Address) -> Vec<(Address, U256)> {
+    let mut transfers: Vec<(Address, U256)> = Vec::new();
+    for log in &receipt.logs {
+        if log.topics.len() != 3 || log.topics[0] != *TRANSFER_TOPIC || log.topics[2] != address_topic(recipient) {
+            continue;
+        }
+        if log.data.len() < 32 {
+            continue;
+        }
+        let value = U256::from_big_endian(&log.data[..32]);
+        match transfers.iter_mut().find(|(token, _)| *token == log.address) {
+            Some((_, total)) => *total += value,
+            None => transfers.push((log.address, value)),
+        }
+    }
+    transfers
+}
+
+/// Profit from the executor contract's `ArbitrageExecuted(initiator, profit)` event.
+pub fn decode_reported_profit(receipt: &TransactionReceipt, executor: Address) -> Option<U256> {
+    receipt
+        .logs
+        .iter()
+        .find(|log| log.address == executor && log.topics.first() == Some(&*ARBITRAGE_EXECUTED_TOPIC))
+        .filter(|log| log.data.len() >= 32)
+        .map(|log| U256::from_big_endian(&log.data[..32]))
+}
+
+pub struct ReceiptProcessor {
+    executor: Address,
+    profit_wallet: Address,
+    // Token registry and router quotes for USD valuation
+    planner: Arc<ExecutionPlanner>,
+    execution_log: Arc<ExecutionLog>,
+}
+
+impl ReceiptProcessor {
+    pub fn new(executor: Address, profit_wallet: Address, planner: Arc<ExecutionPlanner>, execution_log: Arc<ExecutionLog>) -> Self {
+        Self { executor, profit_wallet, planner, execution_log }
+    }
+
+    /// Realized gas and profit of a mined executor transaction.
+    pub async fn process<M: Middleware + 'static>(&self, client: Arc<M>, receipt: &TransactionReceipt) -> RealizedExecution {
+        let registry = self.planner.token_registry();
+        let chain_id = self.planner.chain_id();
+        let gas_used = receipt.gas_used.unwrap_or_default();
+        let effective_gas_price = receipt.effective_gas_price.unwrap_or_default();
+        let gas_cost = TokenAmount::new(gas_used.saturating_mul(effective_gas_price), 18);
+        let gas_cost_native = gas_cost.to_f64();
+        // Gas is valued at the wrapped native token's matrix price, then its router quote. The gas
+        // is spent either way, so a stale matrix price beats not counting it at all.
+        let mut native_price = self.planner.native_price_usd();
+        if native_price.is_none()
+            && let Some(wrapped) = self.planner.wrapped_native()
+        {
+            native_price = self.planner.price_usd(client.clone(), wrapped).await;
+        }
+        if native_price.is_none() {
+            native_price = self.planner.last_native_price_usd();
+            if let Some(price) = native_price {
+                warn!("[ReceiptProcessor] No fresh native price; valuing gas at the last known ${:.2}", price);
+            }
+        }
+        let gas_cost_usd = native_price.and_then(|price| gas_cost.usd(price));
+
+        let mut profits = Vec::new();
+        for (token, raw) in decode_profit_transfers(receipt, self.profit_wallet) {
+            if registry.get(chain_id, token).is_some_and(|t| t.decimals.is_none())
+                && let Err(e) = registry.fetch_metadata(chain_id, token, client.clone()).await
+            {
+                warn!("[ReceiptProcessor] {}", e);
+            }
+            let amount = registry.amount(chain_id, token, raw).ok();
+            let usd = match amount {
+                Some(amount) => self.planner.price_usd(client.clone(), token).await.and_then(|price| amount.usd(price)),
+                None => None,
+            };
+            if usd.is_none() {
+                warn!("[ReceiptProcessor] Cannot value profit of {} raw {:?}", raw, token);
+            }
+            profits.push(TokenProfit { token, raw, amount, usd });
+        }
+        let profit_usd: f64 = profits.iter().filter_map(|p| p.usd).sum();
+        RealizedExecution {
+            tx_hash: receipt.transaction_hash,
+            success: receipt.status == Some(U64::one()),
+            gas_used,
+            effective_gas_price,
+            gas_cost_native,
+            gas_cost_usd,
+            reported_profit: decode_reported_profit(receipt, self.executor),
+            profits,
+            profit_usd,
+            net_profit_usd: gas_cost_usd.map(|gas| profit_usd - gas),
+        }
+    }
+
+    /// Fetch the receipt of `tx_hash`, process it and update its ExecutionLog record.
+    pub async fn record<M: Middleware + 'static>(&self, client: Arc<M>, tx_hash: TxHash) -> Option<RealizedExecution> {
+        let receipt = match client.get_transaction_receipt(tx_hash).await {
+            Ok(Some(receipt)) => receipt,
+            Ok(None) => {
+                warn!("[ReceiptProcessor] No receipt for 0x{:x}", tx_hash);
+                return None;
+            }
+            Err(e) => {
+                warn!("[ReceiptProcessor] Receipt lookup for 0x{:x} failed: {}", tx_hash, e);
+                return None;
+            }
+        };
+        let realized = self.process(client, &receipt).await;
+        let updated = self.execution_log.update_by_tx_hash(&format!("0x{:x}", tx_hash), |record| {
+            record.gas_used = Some(realized.gas_used.low_u64());
+            record.gas_cost_native = Some(realized.gas_cost_native);
+            record.profit = realized.net_profit_usd.unwrap_or(realized.profit_usd);
+        });
+        if !updated {
+            warn!("[ReceiptProcessor] No execution record for 0x{:x}", tx_hash);
+        }
+        info!(
+            "[ReceiptProcessor] 0x{:x}: profit ${:.2}, gas {} ({:.6} native), net {:?}",
+            tx_hash, realized.profit_usd, realized.gas_used, realized.gas_cost_native, realized.net_profit_usd
+        );
+        Some(realized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::execution_log::{ExecutionRecord, TxStatus};
+    use crate::chain_registry::ChainConfig;
+    use crate::token_registry::{TokenRegistry, BSC_MAINNET_CHAIN_ID};
+
+    fn transfer_log(token: Address, from: Address, to: Address, value: u64) -> Log {
+        let mut data = [0u8; 32];
+        U256::from(value).to_big_endian(&mut data);
+        Log {
+            address: token,
+            topics: vec![*TRANSFER_TOPIC, address_topic(from), address_topic(to)],
+            data: Bytes::from(data.to_vec()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_realized_profit_from_receipt() {
+        let (executor, profit_wallet, pool) =
+            (Address::from_low_u64_be(0xe0), Address::from_low_u64_be(0xf0), Address::from_low_u64_be(0xaa));
+        let busd = Address::from_low_u64_be(0xb0);
+        let registry = Arc::new(TokenRegistry::new());
+        registry.register(BSC_MAINNET_CHAIN_ID, "BUSD", busd, Some(18));
+        registry.register(BSC_MAINNET_CHAIN_ID, "WBNB", Address::from_low_u64_be(0xbb), Some(18));
+        let bsc = ChainConfig { chain_id: BSC_MAINNET_CHAIN_ID, wrapped_native_token: "WBNB".to_string(), ..Default::default() };
+        let planner = Arc::new(ExecutionPlanner::new(Arc::new(Settings::default()), registry, &bsc));
+        let log = Arc::new(ExecutionLog::new());
+        let processor = ReceiptProcessor::new(executor, profit_wallet, planner, log.clone());
+
+        let tx_hash = TxHash::from_low_u64_be(0x77);
+        let mut profit_data = [0u8; 32];
+        U256::exp10(19).to_big_endian(&mut profit_data);
+        let receipt = TransactionReceipt {
+            transaction_hash: tx_hash,
+            status: Some(U64::one()),
+            gas_used: Some(U256::from(200_000)),
+            effective_gas_price: Some(U256::from(5_000_000_000u64)),
+            logs: vec![
+                // Loan repayment to the pool is not profit
+                transfer_log(busd, executor, pool, 1_000),
+                transfer_log(busd, executor, profit_wallet, 6_000_000_000_000_000_000),
+                transfer_log(busd, executor, profit_wallet, 4_000_000_000_000_000_000),
+                Log {
+                    address: executor,
+                    topics: vec![*ARBITRAGE_EXECUTED_TOPIC, address_topic(executor)],
+                    data: Bytes::from(profit_data.to_vec()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(decode_profit_transfers(&receipt, profit_wallet), vec![(busd, U256::exp10(19))]);
+        assert_eq!(decode_reported_profit(&receipt, executor), Some(U256::exp10(19)));
+
+        log.log(ExecutionRecord {
+            timestamp: chrono::Utc::now(),
+            protocol: "Venus".to_string(),
+            account: "0xabc".to_string(),
+            debt: 1.0,
+            collateral: 2.0,
+            success: true,
+            profit: 0.0,
+            gas_used: None,
+            gas_cost_native: None,
+            tx_hash: Some(format!("0x{:x}", tx_hash)),
+            error: None,
+            status: TxStatus::Confirmed,
+            transfer: None,
+        });
+        // The WBNB price quote fails (no router responds), so gas can't be valued in USD:
+        // the record falls back to gross profit
+        let (provider, mock) = Provider::mocked();
+        mock.push(receipt).unwrap();
+        let realized = processor.record(Arc::new(provider), tx_hash).await.unwrap();
+        assert_eq!(realized.profit_usd, 10.0);
+        assert_eq!(realized.gas_cost_native, 0.001);
+        assert_eq!(realized.gas_cost_usd, None);
+
+        let record = &log.all()[0];
+        assert_eq!(record.profit, 10.0);
+        assert_eq!(record.gas_used, Some(200_000));
+        assert_eq!(record.gas_cost_native, Some(0.001));
+    }
+
+    #[tokio::test]
+    async fn test_reverted_gas_valued_at_last_known_price() {
+        let registry = Arc::new(TokenRegistry::new());
+        registry.register(BSC_MAINNET_CHAIN_ID, "WBNB", Address::from_low_u64_be(0xbb), Some(18));
+        let bsc = ChainConfig { chain_id: BSC_MAINNET_CHAIN_ID, wrapped_native_token: "WBNB".to_string(), ..Default::default() };
+        let matrix = Arc::new(std::sync::Mutex::new(crate::matrix2d::Matrix2D::new(vec!["PancakeSwap".into()], vec!["WBNB".into()])));
+        matrix.lock().unwrap().update_price("PancakeSwap", "WBNB", 600.0);
+        matrix.lock().unwrap().prices[0][0].timestamp = 1;
+        let planner = ExecutionPlanner::new(Arc::new(Settings::default()), registry, &bsc).with_matrix(matrix);
+        let processor = ReceiptProcessor::new(Address::zero(), Address::from_low_u64_be(0xf0), Arc::new(planner), Arc::new(ExecutionLog::new()));
+
+        let receipt = TransactionReceipt {
+            status: Some(U64::zero()),
+            gas_used: Some(U256::from(200_000)),
+            effective_gas_price: Some(U256::from(5_000_000_000u64)),
+            ..Default::default()
+        };
+        let (provider, _mock) = Provider::mocked();
+        let realized = processor.process(Arc::new(provider), &receipt).await;
+        assert!(!realized.success);
+        // 0.001 BNB at the last known $600
+        assert!((realized.net_profit_usd.unwrap() + 0.6).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,219 @@
+// Remote signing through a Web3Signer-compatible `eth1` JSON-RPC API, so the executor key
+// never has to live in the bot process. `ExecutorSigner` lets the provider stack use either
+// the remote signer or a local wallet behind the same SignerMiddleware type.
+
+use crate::config::Settings;
+use crate::secrets::{load_wallet, SecretsError};
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::Eip712;
+use ethers::utils::rlp::Rlp;
+use log::info;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RemoteSignerError {
+    #[error("Invalid remote signer URL: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("Remote signer did not answer {0} within the timeout")]
+    Timeout(&'static str),
+    #[error("Remote signer request failed: {0}")]
+    Request(#[from] ProviderError),
+    #[error("Remote signer has no key for {0:?}")]
+    UnknownAccount(Address),
+    #[error("Invalid remote_signer_address: {0}")]
+    InvalidAddress(String),
+    #[error("Remote signer has no keys loaded")]
+    NoAccounts,
+    #[error("Failed to encode transaction for the remote signer: {0}")]
+    Encode(#[from] serde_json::Error),
+    #[error("Remote signer returned an invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("Remote signer signature recovers to {recovered:?}, expected {expected:?}")]
+    WrongSigner { expected: Address, recovered: Address },
+    #[error("EIP-712 typed data signing is not supported by the remote signer")]
+    TypedDataUnsupported,
+}
+
+/// `Signer` that forwards signing requests to a Web3Signer `eth1` endpoint
+/// (`eth_accounts`, `eth_sign`, `eth_signTransaction`).
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    client: Provider<Http>,
+    address: Address,
+    chain_id: u64,
+    timeout: Duration,
+}
+
+impl RemoteSigner {
+    /// Connect to the signer at `url` and select `address` (or the first key it holds).
+    pub async fn connect(url: &str, address: Option<Address>, timeout: Duration) -> Result<Self, RemoteSignerError> {
+        let client = Provider::new(Http::new(url::Url::parse(url)?));
+        let accounts: Vec<Address> = Self::call(&client, timeout, "eth_accounts", ()).await?;
+        let address = match address {
+            Some(address) if accounts.contains(&address) => address,
+            Some(address) => return Err(RemoteSignerError::UnknownAccount(address)),
+            None => *accounts.first().ok_or(RemoteSignerError::NoAccounts)?,
+        };
+        info!("[RemoteSigner] Using remote key {:?} ({} key(s) available)", address, accounts.len());
+        Ok(Self { client, address, chain_id: 1, timeout })
+    }
+
+    async fn call<P, R>(client: &Provider<Http>, timeout: Duration, method: &'static str, params: P) -> Result<R, RemoteSignerError>
+    where
+        P: std::fmt::Debug + serde::Serialize + Send + Sync,
+        R: serde::Serialize + serde::de::DeserializeOwned + std::fmt::Debug + Send,
+    {
+        tokio::time::timeout(timeout, client.request(method, params))
+            .await
+            .map_err(|_| RemoteSignerError::Timeout(method))?
+            .map_err(RemoteSignerError::from)
+    }
+
+    fn check_signer(&self, signature: &Signature, hash: H256) -> Result<(), RemoteSignerError> {
+        let recovered = signature
+            .recover(hash)
+            .map_err(|e| RemoteSignerError::InvalidResponse(e.to_string()))?;
+        if recovered != self.address {
+            return Err(RemoteSignerError::WrongSigner { expected: self.address, recovered });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    type Error = RemoteSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(&self, message: S) -> Result<Signature, Self::Error> {
+        let message = message.as_ref();
+        let data = Bytes::from(message.to_vec());
+        let signature: String = Self::call(&self.client, self.timeout, "eth_sign", (self.address, data)).await?;
+        let signature: Signature = signature
+            .parse()
+            .map_err(|e: SignatureError| RemoteSignerError::InvalidResponse(e.to_string()))?;
+        self.check_signer(&signature, ethers::utils::hash_message(message))?;
+        Ok(signature)
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = tx.clone();
+        tx.set_from(self.address);
+        if tx.chain_id().is_none() {
+            tx.set_chain_id(self.chain_id);
+        }
+        // Legacy requests don't serialize chainId; the signer needs it for EIP-155
+        let mut params = serde_json::to_value(&tx)?;
+        params["chainId"] = serde_json::json!(tx.chain_id().unwrap_or_default());
+        let raw: Bytes = Self::call(&self.client, self.timeout, "eth_signTransaction", [params]).await?;
+        let (_, signature) = TypedTransaction::decode_signed(&Rlp::new(raw.as_ref()))
+            .map_err(|e| RemoteSignerError::InvalidResponse(e.to_string()))?;
+        // Only accept a signature over exactly the transaction we asked for
+        self.check_signer(&signature, tx.sighash())?;
+        Ok(signature)
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, _payload: &T) -> Result<Signature, Self::Error> {
+        Err(RemoteSignerError::TypedDataUnsupported)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExecutorSignerError {
+    #[error(transparent)]
+    Local(#[from] WalletError),
+    #[error(transparent)]
+    Remote(#[from] RemoteSignerError),
+    #[error(transparent)]
+    Secrets(#[from] SecretsError),
+}
+
+/// Signer for the executor wallet: a local key (keystore / PRIVATE_KEY) or a remote signer.
+#[derive(Debug, Clone)]
+pub enum ExecutorSigner {
+    Local(LocalWallet),
+    Remote(RemoteSigner),
+}
+
+impl ExecutorSigner {
+    /// Remote signer if `remote_signer_url` is configured, otherwise the local wallet.
+    pub async fn from_settings(settings: &Settings) -> Result<Self, ExecutorSignerError> {
+        match settings.remote_signer_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            Some(url) => {
+                let address = match settings.remote_signer_address.as_deref().filter(|a| !a.trim().is_empty()) {
+                    Some(address) => Some(
+                        address
+                            .parse::<Address>()
+                            .map_err(|e| RemoteSignerError::InvalidAddress(e.to_string()))?,
+                    ),
+                    None => None,
+                };
+                let timeout = Duration::from_millis(settings.remote_signer_timeout_ms);
+                Ok(Self::Remote(RemoteSigner::connect(url, address, timeout).await?))
+            }
+            None => Ok(Self::Local(load_wallet(settings)?)),
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for ExecutorSigner {
+    type Error = ExecutorSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(&self, message: S) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.sign_message(message).await?),
+            Self::Remote(signer) => Ok(signer.sign_message(message).await?),
+        }
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.sign_transaction(tx).await?),
+            Self::Remote(signer) => Ok(signer.sign_transaction(tx).await?),
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, payload: &T) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.sign_typed_data(payload).await?),
+            Self::Remote(signer) => Ok(signer.sign_typed_data(payload).await?),
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            Self::Local(wallet) => wallet.address(),
+            Self::Remote(signer) => signer.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            Self::Local(wallet) => wallet.chain_id(),
+            Self::Remote(signer) => signer.chain_id(),
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            Self::Local(wallet) => Self::Local(wallet.with_chain_id(chain_id)),
+            Self::Remote(signer) => Self::Remote(signer.with_chain_id(chain_id)),
+        }
+    }
+}
@@ -0,0 +1,454 @@
+// Risk manager: consulted before every execution and fed every outcome. Enforces per-trade and
+// per-token (UTC day) notional caps, counting executions still in flight, and a cooldown after
+// reverts, and trips a global circuit breaker on daily or rolling realized losses or a run of
+// consecutive failed transactions. While the breaker is engaged nothing executes; it is engaged or reset by hand through the kill-switch
+// API and raises an alert event whenever it changes state.
+
+use crate::config::Settings;
+use crate::events::WebSocketEventSender;
+use crate::secrets::SecretString;
+use chrono::{NaiveDate, Utc};
+use ethers::types::Address;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+const ALERT_SOURCE: &str = "circuit_breaker";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RiskViolation {
+    #[error("Circuit breaker engaged: {0}")]
+    Halted(String),
+    #[error("Cooling down after a revert for another {0} ms")]
+    CoolingDown(u64),
+    #[error("Trade notional cannot be valued in USD")]
+    NotionalUnknown,
+    #[error("Trade notional ${notional:.2} exceeds the ${limit:.2} per-trade limit")]
+    TradeNotional { notional: f64, limit: f64 },
+    #[error("Notional for {token:?} would reach ${total:.2}, over the ${limit:.2} daily limit")]
+    TokenNotional { token: Address, total: f64, limit: f64 },
+}
+
+/// Limits from the risk management settings. Zero disables a limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskLimits {
+    pub enabled: bool,
+    pub max_daily_loss_usd: f64,
+    pub max_rolling_loss_usd: f64,
+    pub rolling_window: Duration,
+    pub max_consecutive_failures: u32,
+    pub max_trade_notional_usd: f64,
+    pub max_token_notional_usd: f64,
+    pub revert_cooldown: Duration,
+}
+
+impl RiskLimits {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            enabled: settings.risk_management_enabled,
+            max_daily_loss_usd: settings.max_daily_loss_usd,
+            max_rolling_loss_usd: settings.max_rolling_loss_usd,
+            rolling_window: Duration::from_millis(settings.rolling_loss_window_ms),
+            max_consecutive_failures: settings.max_consecutive_failures,
+            max_trade_notional_usd: settings.max_trade_notional_usd,
+            max_token_notional_usd: settings.max_token_notional_usd,
+            revert_cooldown: Duration::from_millis(settings.revert_cooldown_ms),
+        }
+    }
+}
+
+/// How an execution that passed the risk check ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TradeOutcome {
+    /// Mined successfully; `pnl_usd` is the realized profit net of gas
+    Executed { pnl_usd: f64 },
+    /// Mined and reverted; `pnl_usd` is the (negative) gas spent
+    Reverted { pnl_usd: f64 },
+    /// Sent but never mined: dropped or cancelled
+    Failed,
+    /// Never sent: rejected by simulation or the pre-execution checks, or refused by the node.
+    /// Not a failure; only releases the reserved notional.
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskStatus {
+    pub enabled: bool,
+    pub halted: bool,
+    pub halt_reason: Option<String>,
+    pub halted_at: Option<String>,
+    pub daily_pnl_usd: f64,
+    pub rolling_pnl_usd: f64,
+    pub consecutive_failures: u32,
+    pub cooldown_remaining_ms: u64,
+    /// Executed notional today, per token address
+    pub token_notional_usd: HashMap<String, f64>,
+    /// Notional of executions still in flight, per token address
+    pub reserved_notional_usd: HashMap<String, f64>,
+}
+
+#[derive(Debug)]
+struct RiskState {
+    day: NaiveDate,
+    daily_pnl: f64,
+    // Realized PnL per outcome within the rolling window
+    recent_pnl: VecDeque<(Instant, f64)>,
+    consecutive_failures: u32,
+    cooldown_until: Option<Instant>,
+    token_notional: HashMap<Address, f64>,
+    // Notional reserved by `check_trade` until the execution's outcome is recorded
+    reserved: HashMap<Address, f64>,
+    halted: Option<(String, chrono::DateTime<Utc>)>,
+}
+
+impl RiskState {
+    fn new() -> Self {
+        Self {
+            day: Utc::now().date_naive(),
+            daily_pnl: 0.0,
+            recent_pnl: VecDeque::new(),
+            consecutive_failures: 0,
+            cooldown_until: None,
+            token_notional: HashMap::new(),
+            reserved: HashMap::new(),
+            halted: None,
+        }
+    }
+
+    // Start a new day's totals at UTC midnight and drop PnL older than the window
+    fn roll(&mut self, window: Duration) {
+        let today = Utc::now().date_naive();
+        if today != self.day {
+            self.day = today;
+            self.daily_pnl = 0.0;
+            self.token_notional.clear();
+        }
+        let now = Instant::now();
+        while self.recent_pnl.front().is_some_and(|(at, _)| now.duration_since(*at) > window) {
+            self.recent_pnl.pop_front();
+        }
+    }
+
+    fn rolling_pnl(&self) -> f64 {
+        self.recent_pnl.iter().map(|(_, pnl)| pnl).sum()
+    }
+
+    fn release(&mut self, token: Address, notional: f64) {
+        if let Some(reserved) = self.reserved.get_mut(&token) {
+            *reserved -= notional;
+            if *reserved <= 1e-9 {
+                self.reserved.remove(&token);
+            }
+        }
+    }
+}
+
+pub struct RiskManager {
+    limits: RiskLimits,
+    state: Mutex<RiskState>,
+    // Bearer token for the kill-switch API
+    admin_token: Option<SecretString>,
+    alerts: Option<Arc<WebSocketEventSender>>,
+}
+
+impl RiskManager {
+    pub fn new(limits: RiskLimits, admin_token: Option<SecretString>) -> Self {
+        Self {
+            limits,
+            state: Mutex::new(RiskState::new()),
+            admin_token: admin_token.filter(|t| !t.is_unset()),
+            alerts: None,
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(RiskLimits::from_settings(settings), settings.risk_admin_token.clone())
+    }
+
+    /// Broadcast breaker state changes as alert events
+    pub fn with_alerts(mut self, alerts: Arc<WebSocketEventSender>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub fn limits(&self) -> &RiskLimits {
+        &self.limits
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state.lock().unwrap().halted.is_some()
+    }
+
+    /// Whether an execution may start now: the breaker is not engaged and no revert cooldown is
+    /// running. The breaker halts execution even with risk management disabled.
+    pub fn check_ready(&self) -> Result<(), RiskViolation> {
+        let state = self.state.lock().unwrap();
+        if let Some((reason, _)) = &state.halted {
+            return Err(RiskViolation::Halted(reason.clone()));
+        }
+        if !self.limits.enabled {
+            return Ok(());
+        }
+        if let Some(until) = state.cooldown_until {
+            let remaining = until.saturating_duration_since(Instant::now());
+            if !remaining.is_zero() {
+                return Err(RiskViolation::CoolingDown(remaining.as_millis() as u64));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a trade of `notional_usd` borrowing `token` may be executed. Its notional counts
+    /// against the token's daily cap from now on, so concurrent executions can't overshoot it;
+    /// every accepted trade must be followed by a `record` with the same notional.
+    pub fn check_trade(&self, token: Address, notional_usd: Option<f64>) -> Result<(), RiskViolation> {
+        self.check_ready()?;
+        if !self.limits.enabled {
+            return Ok(());
+        }
+        let (trade_limit, token_limit) = (self.limits.max_trade_notional_usd, self.limits.max_token_notional_usd);
+        let Some(notional) = notional_usd.filter(|n| n.is_finite()) else {
+            if trade_limit <= 0.0 && token_limit <= 0.0 {
+                return Ok(());
+            }
+            return Err(RiskViolation::NotionalUnknown);
+        };
+        if trade_limit > 0.0 && notional > trade_limit {
+            return Err(RiskViolation::TradeNotional { notional, limit: trade_limit });
+        }
+        let mut state = self.state.lock().unwrap();
+        state.roll(self.limits.rolling_window);
+        let total = state.token_notional.get(&token).copied().unwrap_or(0.0)
+            + state.reserved.get(&token).copied().unwrap_or(0.0)
+            + notional;
+        if token_limit > 0.0 && total > token_limit {
+            return Err(RiskViolation::TokenNotional { token, total, limit: token_limit });
+        }
+        *state.reserved.entry(token).or_default() += notional;
+        Ok(())
+    }
+
+    /// Record how an execution of `notional_usd` borrowing `token` ended, releasing its
+    /// reservation and tripping the breaker if a loss or failure limit is now exceeded.
+    pub fn record(&self, token: Address, notional_usd: Option<f64>, outcome: TradeOutcome) {
+        if !self.limits.enabled {
+            return;
+        }
+        let trip_reason = {
+            let mut state = self.state.lock().unwrap();
+            state.roll(self.limits.rolling_window);
+            if let Some(notional) = notional_usd.filter(|n| n.is_finite()) {
+                state.release(token, notional);
+            }
+            let pnl = match outcome {
+                TradeOutcome::Executed { pnl_usd } => {
+                    state.consecutive_failures = 0;
+                    if let Some(notional) = notional_usd {
+                        *state.token_notional.entry(token).or_default() += notional;
+                    }
+                    pnl_usd
+                }
+                TradeOutcome::Reverted { pnl_usd } => {
+                    state.consecutive_failures += 1;
+                    state.cooldown_until = Some(Instant::now() + self.limits.revert_cooldown);
+                    pnl_usd
+                }
+                TradeOutcome::Failed => {
+                    state.consecutive_failures += 1;
+                    0.0
+                }
+                TradeOutcome::Rejected => 0.0,
+            };
+            if pnl.is_finite() && pnl != 0.0 {
+                state.daily_pnl += pnl;
+                state.recent_pnl.push_back((Instant::now(), pnl));
+            }
+            self.breached_limit(&state)
+        };
+        if let Some(reason) = trip_reason {
+            self.engage(&reason);
+        }
+    }
+
+    fn breached_limit(&self, state: &RiskState) -> Option<String> {
+        let limits = &self.limits;
+        if limits.max_daily_loss_usd > 0.0 && -state.daily_pnl >= limits.max_daily_loss_usd {
+            return Some(format!("daily loss ${:.2} reached the ${:.2} limit", -state.daily_pnl, limits.max_daily_loss_usd));
+        }
+        let rolling = state.rolling_pnl();
+        if limits.max_rolling_loss_usd > 0.0 && -rolling >= limits.max_rolling_loss_usd {
+            return Some(format!(
+                "loss ${:.2} within {}s reached the ${:.2} limit",
+                -rolling,
+                limits.rolling_window.as_secs(),
+                limits.max_rolling_loss_usd
+            ));
+        }
+        if limits.max_consecutive_failures > 0 && state.consecutive_failures >= limits.max_consecutive_failures {
+            return Some(format!("{} consecutive failed executions", state.consecutive_failures));
+        }
+        None
+    }
+
+    /// Engage the circuit breaker. Already-engaged breakers keep their original reason.
+    pub fn engage(&self, reason: &str) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.halted.is_some() {
+                return;
+            }
+            state.halted = Some((reason.to_string(), Utc::now()));
+        }
+        error!("[RiskManager] Circuit breaker engaged: {}", reason);
+        self.alert("critical", &format!("Circuit breaker engaged: {}", reason));
+    }
+
+    /// Release the circuit breaker and clear the failure run and revert cooldown. Loss totals
+    /// are kept, so a further loss past a limit trips it again.
+    pub fn reset(&self, note: &str) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.halted = None;
+            state.consecutive_failures = 0;
+            state.cooldown_until = None;
+        }
+        warn!("[RiskManager] Circuit breaker reset: {}", note);
+        self.alert("info", &format!("Circuit breaker reset: {}", note));
+    }
+
+    /// Whether `token` is the configured kill-switch admin token. Always false if none is set.
+    pub fn authorize(&self, token: &str) -> bool {
+        let Some(expected) = &self.admin_token else { return false };
+        let (expected, token) = (expected.expose().as_bytes(), token.as_bytes());
+        // Constant time in the token's contents
+        expected.len() == token.len() && expected.iter().zip(token).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+
+    pub fn kill_switch_enabled(&self) -> bool {
+        self.admin_token.is_some()
+    }
+
+    pub fn status(&self) -> RiskStatus {
+        let mut state = self.state.lock().unwrap();
+        state.roll(self.limits.rolling_window);
+        RiskStatus {
+            enabled: self.limits.enabled,
+            halted: state.halted.is_some(),
+            halt_reason: state.halted.as_ref().map(|(reason, _)| reason.clone()),
+            halted_at: state.halted.as_ref().map(|(_, at)| at.to_rfc3339()),
+            daily_pnl_usd: state.daily_pnl,
+            rolling_pnl_usd: state.rolling_pnl(),
+            consecutive_failures: state.consecutive_failures,
+            cooldown_remaining_ms: state
+                .cooldown_until
+                .map(|until| until.saturating_duration_since(Instant::now()).as_millis() as u64)
+                .unwrap_or(0),
+            token_notional_usd: state.token_notional.iter().map(|(t, n)| (format!("{:?}", t), *n)).collect(),
+            reserved_notional_usd: state.reserved.iter().map(|(t, n)| (format!("{:?}", t), *n)).collect(),
+        }
+    }
+
+    fn alert(&self, severity: &str, message: &str) {
+        match &self.alerts {
+            Some(alerts) => alerts.send_alert_event(ALERT_SOURCE, severity, message),
+            None => info!("[RiskManager] No alert channel for: {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::WebSocketEvent;
+
+    fn limits() -> RiskLimits {
+        RiskLimits {
+            enabled: true,
+            max_daily_loss_usd: 100.0,
+            max_rolling_loss_usd: 50.0,
+            rolling_window: Duration::from_secs(3600),
+            max_consecutive_failures: 3,
+            max_trade_notional_usd: 10_000.0,
+            max_token_notional_usd: 15_000.0,
+            revert_cooldown: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_notional_caps_and_revert_cooldown() {
+        let risk = RiskManager::new(limits(), None);
+        let token = Address::from_low_u64_be(1);
+        assert!(risk.check_trade(token, Some(8_000.0)).is_ok());
+        assert!(matches!(risk.check_trade(token, Some(12_000.0)), Err(RiskViolation::TradeNotional { .. })));
+        assert_eq!(risk.check_trade(token, None), Err(RiskViolation::NotionalUnknown));
+
+        risk.record(token, Some(8_000.0), TradeOutcome::Executed { pnl_usd: 5.0 });
+        assert!(matches!(risk.check_trade(token, Some(8_000.0)), Err(RiskViolation::TokenNotional { .. })));
+        assert!(risk.check_trade(Address::from_low_u64_be(2), Some(8_000.0)).is_ok());
+        risk.record(Address::from_low_u64_be(2), Some(8_000.0), TradeOutcome::Rejected);
+
+        assert!(risk.check_trade(token, Some(1_000.0)).is_ok());
+        risk.record(token, Some(1_000.0), TradeOutcome::Reverted { pnl_usd: -1.0 });
+        assert!(matches!(risk.check_ready(), Err(RiskViolation::CoolingDown(_))));
+        assert!(!risk.is_halted());
+    }
+
+    #[test]
+    fn test_in_flight_notional_is_reserved() {
+        let risk = RiskManager::new(limits(), None);
+        let token = Address::from_low_u64_be(1);
+        // Two concurrent 8k trades would overshoot the 15k daily cap
+        assert!(risk.check_trade(token, Some(8_000.0)).is_ok());
+        assert!(matches!(risk.check_trade(token, Some(8_000.0)), Err(RiskViolation::TokenNotional { .. })));
+        assert_eq!(risk.status().reserved_notional_usd.len(), 1);
+
+        // A rejected trade frees its reservation without counting as a failure
+        risk.record(token, Some(8_000.0), TradeOutcome::Rejected);
+        assert!(risk.status().reserved_notional_usd.is_empty());
+        assert_eq!(risk.status().consecutive_failures, 0);
+        assert!(risk.check_trade(token, Some(8_000.0)).is_ok());
+        risk.record(token, Some(8_000.0), TradeOutcome::Executed { pnl_usd: 1.0 });
+        assert!(risk.status().reserved_notional_usd.is_empty());
+        assert_eq!(risk.status().token_notional_usd.values().sum::<f64>(), 8_000.0);
+    }
+
+    #[test]
+    fn test_breaker_trips_alerts_and_resets() {
+        let (tx, mut rx) = tokio::sync::broadcast::channel(8);
+        let risk = RiskManager::new(limits(), Some(SecretString::new("s3cret-admin-token")))
+            .with_alerts(Arc::new(WebSocketEventSender::new(tx)));
+        let token = Address::from_low_u64_be(1);
+
+        risk.record(token, None, TradeOutcome::Failed);
+        risk.record(token, None, TradeOutcome::Failed);
+        assert!(risk.check_ready().is_ok());
+        risk.record(token, None, TradeOutcome::Failed);
+        assert!(matches!(risk.check_ready(), Err(RiskViolation::Halted(reason)) if reason.contains("3 consecutive")));
+        match rx.try_recv().unwrap() {
+            WebSocketEvent::Alert(alert) => {
+                assert_eq!((alert.source.as_str(), alert.severity.as_str()), ("circuit_breaker", "critical"));
+            }
+            other => panic!("Expected an alert, got {:?}", other),
+        }
+
+        risk.reset("operator");
+        assert!(risk.check_ready().is_ok());
+        assert!(matches!(rx.try_recv().unwrap(), WebSocketEvent::Alert(alert) if alert.severity == "info"));
+
+        // Rolling loss: two losing executions inside the window
+        risk.record(token, Some(100.0), TradeOutcome::Executed { pnl_usd: -30.0 });
+        assert!(!risk.is_halted());
+        risk.record(token, Some(100.0), TradeOutcome::Executed { pnl_usd: -25.0 });
+        let status = risk.status();
+        assert!(status.halted);
+        assert!(status.halt_reason.unwrap().starts_with("loss $55.00"));
+        assert_eq!(status.daily_pnl_usd, -55.0);
+
+        assert!(risk.authorize("s3cret-admin-token"));
+        assert!(!risk.authorize("s3cret-admin-tokeN"));
+        assert!(!RiskManager::new(limits(), None).authorize(""));
+    }
+}
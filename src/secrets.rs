@@ -0,0 +1,238 @@
+// Secret handling: a redacting string type for keys in Settings, wallet loading from an
+// encrypted JSON keystore (or the PRIVATE_KEY fallback), and a logger that scrubs every
+// registered secret from log lines.
+
+use crate::config::Settings;
+use ethers::signers::LocalWallet;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::io::{BufRead, IsTerminal, Write};
+use std::sync::RwLock;
+use thiserror::Error;
+
+const REDACTED: &str = "[REDACTED]";
+// Shorter values are not scrubbed from logs, they would match ordinary text
+const MIN_REDACTED_LEN: usize = 6;
+
+static REGISTERED_SECRETS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+#[derive(Debug, Error)]
+pub enum SecretsError {
+    #[error("No signing key configured: set keystore_path or PRIVATE_KEY")]
+    NoKey,
+    #[error("Invalid private key: {0}")]
+    InvalidPrivateKey(String),
+    #[error("Failed to read keystore password: {0}")]
+    Password(#[from] std::io::Error),
+    #[error("Failed to decrypt keystore {path}: {message}")]
+    Keystore { path: String, message: String },
+}
+
+/// A string that never shows up in `Debug` output. Use `expose()` where the value is needed.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Unset, empty, or an unexpanded `${VAR}` placeholder from the config file
+    pub fn is_unset(&self) -> bool {
+        let value = self.0.trim();
+        value.is_empty() || value.starts_with("${")
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Add a value to the set scrubbed from log lines.
+pub fn register_secret(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_REDACTED_LEN {
+        return;
+    }
+    let mut secrets = REGISTERED_SECRETS.write().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+        // Longest first, so a secret containing another is replaced whole
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// Register the keys from `settings` and the API key environment variables substituted into
+/// provider URLs.
+pub fn register_settings_secrets(settings: &Settings) {
+    let configured = [
+        &settings.private_key,
+        &settings.infura_api_key,
+        &settings.alchemy_api_key,
+        &settings.nodereal_api_key,
+        &settings.etherscan_api_key,
+        &settings.bundle_auth_private_key,
+        &settings.risk_admin_token,
+    ];
+    let matrix_keys = settings.matrix_wallets().into_iter().filter_map(|(_, key, _)| key);
+    for secret in configured.into_iter().flatten().chain(matrix_keys) {
+        if !secret.is_unset() {
+            register_secret(secret.expose());
+        }
+    }
+    for var in ["PRIVATE_KEY", "INFURA_API_KEY", "ALCHEMY_API_KEY", "NODEREAL_API_KEY", "ETHERSCAN_API_KEY", "RISK_ADMIN_TOKEN"] {
+        if let Ok(value) = std::env::var(var) {
+            register_secret(&value);
+        }
+    }
+}
+
+/// Replace every registered secret in `text` with a redaction marker.
+pub fn redact(text: &str) -> String {
+    let secrets = REGISTERED_SECRETS.read().unwrap();
+    let mut out = text.to_string();
+    for secret in secrets.iter() {
+        if out.contains(secret.as_str()) {
+            out = out.replace(secret.as_str(), REDACTED);
+        }
+    }
+    out
+}
+
+/// Initialize env_logger with a format that passes every message through `redact`.
+pub fn init_redacting_logger() {
+    let _ = env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                redact(&record.args().to_string())
+            )
+        })
+        .try_init();
+}
+
+// Keystore password: password file, then KEYSTORE_PASSWORD, then an interactive prompt
+fn keystore_password(settings: &Settings) -> Result<String, SecretsError> {
+    if let Some(path) = settings.keystore_password_file.as_deref().filter(|p| !p.trim().is_empty()) {
+        let password = std::fs::read_to_string(path)?;
+        return Ok(password.trim_end_matches(['\r', '\n']).to_string());
+    }
+    if let Ok(password) = std::env::var("KEYSTORE_PASSWORD") {
+        return Ok(password);
+    }
+    prompt_password("Keystore password: ")
+}
+
+fn prompt_password(prompt: &str) -> Result<String, SecretsError> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Err(SecretsError::Password(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no keystore_password_file or KEYSTORE_PASSWORD and stdin is not a terminal",
+        )));
+    }
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    // Turn off terminal echo while the password is typed (best effort)
+    let set_echo = |flag: &str| {
+        std::process::Command::new("stty")
+            .arg(flag)
+            .stdin(std::process::Stdio::inherit())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    };
+    let echo_off = set_echo("-echo");
+    let mut password = String::new();
+    let result = stdin.lock().read_line(&mut password);
+    if echo_off {
+        set_echo("echo");
+        eprintln!();
+    }
+    result?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn parse_private_key(raw_key: &str) -> Result<LocalWallet, SecretsError> {
+    let raw_key = raw_key.trim();
+    raw_key
+        .strip_prefix("0x")
+        .unwrap_or(raw_key)
+        .parse::<LocalWallet>()
+        // The parse error may echo the input, so it is not passed on
+        .map_err(|_| SecretsError::InvalidPrivateKey(format!("expected 32 hex-encoded bytes, got {} characters", raw_key.len())))
+}
+
+/// Load the executor wallet: the encrypted keystore at `keystore_path` if configured,
+/// otherwise `private_key` (PRIVATE_KEY).
+pub fn load_wallet(settings: &Settings) -> Result<LocalWallet, SecretsError> {
+    if let Some(path) = settings.keystore_path.as_deref().filter(|p| !p.trim().is_empty()) {
+        let password = keystore_password(settings)?;
+        log::info!("[Secrets] Decrypting keystore {}", path);
+        return LocalWallet::decrypt_keystore(path, password)
+            .map_err(|e| SecretsError::Keystore { path: path.to_string(), message: e.to_string() });
+    }
+    match &settings.private_key {
+        Some(key) if !key.is_unset() => {
+            register_secret(key.expose());
+            parse_private_key(key.expose())
+        }
+        _ => Err(SecretsError::NoKey),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::Signer;
+
+    #[test]
+    fn test_secret_string_debug_is_redacted() {
+        let settings = Settings {
+            private_key: Some(SecretString::new("0xdeadbeefcafebabe")),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", settings);
+        assert!(!debug.contains("deadbeef"));
+        assert!(debug.contains(REDACTED));
+    }
+
+    #[test]
+    fn test_redact_registered_secrets() {
+        register_secret("abc"); // too short to scrub
+        register_secret("sk_live_9f8e7d6c");
+        let line = redact("GET https://bsc.infura.io/v3/sk_live_9f8e7d6c failed (abc)");
+        assert_eq!(line, "GET https://bsc.infura.io/v3/[REDACTED] failed (abc)");
+    }
+
+    #[test]
+    fn test_load_wallet_from_keystore_and_private_key() {
+        let dir = std::env::temp_dir().join(format!("fusion-keystore-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (wallet, file) = LocalWallet::new_keystore(&dir, &mut ethers::core::rand::thread_rng(), "hunter22", None).unwrap();
+        let password_file = dir.join("password");
+        std::fs::write(&password_file, "hunter22\n").unwrap();
+        let settings = Settings {
+            keystore_path: Some(dir.join(file).display().to_string()),
+            keystore_password_file: Some(password_file.display().to_string()),
+            ..Default::default()
+        };
+        assert_eq!(load_wallet(&settings).unwrap().address(), wallet.address());
+
+        let key = ethers::utils::hex::encode(wallet.signer().to_bytes());
+        let settings = Settings { private_key: Some(SecretString::new(key)), ..Default::default() };
+        assert_eq!(load_wallet(&settings).unwrap().address(), wallet.address());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+// Pre-flight simulation of executor transactions: eth_call and eth_estimateGas against the
+// pending block, revert reason decoding (Error(string), Panic(uint256) and the contract's
+// custom errors), and extraction of the simulated profit from the return data or from the
+// profit recipient's token balance delta.
+
+use ethers::abi::{Abi, Token};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::id;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::fmt;
+use thiserror::Error;
+
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Decoded reason for a reverted call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RevertReason {
+    /// `require(cond, "message")` / `revert("message")`
+    Message(String),
+    /// Compiler-inserted `Panic(uint256)` (overflow, division by zero, ...)
+    Panic(U256),
+    /// A custom error declared in the contract ABI
+    Custom { name: String, args: Vec<Token> },
+    /// Revert data that matches nothing known (empty for a bare `revert()`)
+    Unknown(Bytes),
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Message(message) => write!(f, "{}", message),
+            RevertReason::Panic(code) => write!(f, "Panic(0x{:x})", code),
+            RevertReason::Custom { name, args } => {
+                let args: Vec<String> = args.iter().map(|t| t.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            RevertReason::Unknown(data) if data.is_empty() => write!(f, "reverted without a reason"),
+            RevertReason::Unknown(data) => write!(f, "unknown revert data {}", data),
+        }
+    }
+}
+
+/// Decode revert data, using the custom errors declared in `abi` if given.
+pub fn decode_revert(data: &[u8], abi: Option<&Abi>) -> RevertReason {
+    if data.len() < 4 {
+        return RevertReason::Unknown(Bytes::from(data.to_vec()));
+    }
+    let (selector, payload) = data.split_at(4);
+    if selector == ERROR_STRING_SELECTOR {
+        if let Ok(tokens) = ethers::abi::decode(&[ethers::abi::ParamType::String], payload)
+            && let Some(Token::String(message)) = tokens.into_iter().next()
+        {
+            return RevertReason::Message(message);
+        }
+    } else if selector == PANIC_SELECTOR {
+        if let Ok(tokens) = ethers::abi::decode(&[ethers::abi::ParamType::Uint(256)], payload)
+            && let Some(Token::Uint(code)) = tokens.into_iter().next()
+        {
+            return RevertReason::Panic(code);
+        }
+    } else if let Some(abi) = abi {
+        for error in abi.errors() {
+            if error.signature().as_bytes()[..4] == *selector
+                && let Ok(args) = error.decode(payload)
+            {
+                return RevertReason::Custom { name: error.name.clone(), args };
+            }
+        }
+    }
+    RevertReason::Unknown(Bytes::from(data.to_vec()))
+}
+
+#[derive(Debug, Error)]
+pub enum SimulationError {
+    #[error("Simulation reverted: {0}")]
+    Reverted(RevertReason),
+    #[error("Simulation request failed: {0}")]
+    Rpc(String),
+    #[error("Simulated profit {profit} is below the minimum {minimum}")]
+    BelowThreshold { profit: U256, minimum: U256 },
+}
+
+// Revert data carried by a middleware error, if the node reported a revert
+fn revert_or_rpc<E: MiddlewareError>(error: E, abi: Option<&Abi>) -> SimulationError {
+    match error.as_error_response().and_then(|e| e.as_revert_data()) {
+        Some(data) => SimulationError::Reverted(decode_revert(&data, abi)),
+        None => SimulationError::Rpc(error.to_string()),
+    }
+}
+
+/// Where the profit of a simulated transaction lands and how much is required.
+#[derive(Debug, Clone)]
+pub struct ProfitCheck {
+    pub token: Address,
+    pub recipient: Address,
+    /// Minimum profit in raw `token` units
+    pub min_profit: U256,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationOutcome {
+    pub gas_estimate: U256,
+    pub return_data: Bytes,
+    /// Simulated profit in raw token units; None if neither the return data nor a balance
+    /// delta was available
+    pub profit: Option<U256>,
+}
+
+fn balance_of_call(token: Address, owner: Address) -> Value {
+    let mut data = id("balanceOf(address)").to_vec();
+    data.extend(ethers::abi::encode(&[Token::Address(owner)]));
+    json!({ "to": token, "data": Bytes::from(data) })
+}
+
+fn call_return_u256(call: &Value) -> Option<U256> {
+    if call.get("status").and_then(Value::as_str) != Some("0x1") {
+        return None;
+    }
+    let data: Bytes = serde_json::from_value(call.get("returnData")?.clone()).ok()?;
+    (data.len() >= 32).then(|| U256::from_big_endian(&data[..32]))
+}
+
+// Balance delta of the profit recipient across the transaction, via eth_simulateV1
+// (balanceOf, transaction, balanceOf in one simulated block). None if unsupported.
+async fn simulated_balance_delta<M: Middleware>(client: &M, tx: &TypedTransaction, check: &ProfitCheck) -> Option<U256> {
+    let balance_call = balance_of_call(check.token, check.recipient);
+    let tx_call = json!({
+        "from": tx.from(),
+        "to": tx.to_addr(),
+        "data": tx.data(),
+        "value": tx.value(),
+        "gas": tx.gas(),
+    });
+    let params = json!([{ "blockStateCalls": [{ "calls": [balance_call, tx_call, balance_call] }] }, "latest"]);
+    let blocks: Value = match client.provider().request("eth_simulateV1", params).await {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            debug!("[Simulation] eth_simulateV1 unavailable: {}", e);
+            return None;
+        }
+    };
+    let calls = blocks.get(0)?.get("calls")?.as_array()?;
+    let before = call_return_u256(calls.first()?)?;
+    let after = call_return_u256(calls.get(2)?)?;
+    Some(after.saturating_sub(before))
+}
+
+/// Simulate `tx` against the pending block. Fails on revert (with the decoded reason) and,
+/// given a `check`, when the simulated profit is below its minimum.
+pub async fn simulate<M: Middleware>(
+    client: &M,
+    tx: &TypedTransaction,
+    abi: Option<&Abi>,
+    check: Option<&ProfitCheck>,
+) -> Result<SimulationOutcome, SimulationError> {
+    let block = Some(BlockId::Number(BlockNumber::Pending));
+    let return_data = client.call(tx, block).await.map_err(|e| revert_or_rpc(e, abi))?;
+    let gas_estimate = client.estimate_gas(tx, block).await.map_err(|e| revert_or_rpc(e, abi))?;
+
+    let profit = if return_data.len() >= 32 {
+        Some(U256::from_big_endian(&return_data[..32]))
+    } else if let Some(check) = check {
+        simulated_balance_delta(client, tx, check).await
+    } else {
+        None
+    };
+    if let Some(check) = check {
+        match profit {
+            Some(profit) if profit < check.min_profit => {
+                return Err(SimulationError::BelowThreshold { profit, minimum: check.min_profit });
+            }
+            Some(_) => {}
+            None => warn!("[Simulation] Call succeeded but its profit could not be measured"),
+        }
+    }
+    debug!("[Simulation] OK: gas estimate {}, profit {:?}", gas_estimate, profit);
+    Ok(SimulationOutcome { gas_estimate, return_data, profit })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{MockResponse, JsonRpcError};
+
+    fn error_string(message: &str) -> Vec<u8> {
+        let mut data = ERROR_STRING_SELECTOR.to_vec();
+        data.extend(ethers::abi::encode(&[Token::String(message.to_string())]));
+        data
+    }
+
+    #[test]
+    fn test_decode_revert_reasons() {
+        assert_eq!(decode_revert(&error_string("Not profitable"), None), RevertReason::Message("Not profitable".into()));
+
+        let mut panic = PANIC_SELECTOR.to_vec();
+        panic.extend(ethers::abi::encode(&[Token::Uint(U256::from(0x11))]));
+        assert_eq!(decode_revert(&panic, None).to_string(), "Panic(0x11)");
+
+        let abi = ethers::abi::parse_abi(&["error InsufficientProfit(uint256 expected, uint256 actual)"]).unwrap();
+        let mut custom = id("InsufficientProfit(uint256,uint256)").to_vec();
+        custom.extend(ethers::abi::encode(&[Token::Uint(U256::from(100)), Token::Uint(U256::from(7))]));
+        assert_eq!(decode_revert(&custom, Some(&abi)).to_string(), "InsufficientProfit(64, 7)");
+        assert!(matches!(decode_revert(&custom, None), RevertReason::Unknown(_)));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_revert_and_threshold() {
+        let (provider, mock) = Provider::mocked();
+        let tx: TypedTransaction = TransactionRequest::new().to(Address::from_low_u64_be(9)).into();
+
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted: Not profitable".to_string(),
+            data: Some(json!(Bytes::from(error_string("Not profitable")))),
+        }));
+        match simulate(&provider, &tx, None, None).await {
+            Err(SimulationError::Reverted(RevertReason::Message(m))) => assert_eq!(m, "Not profitable"),
+            other => panic!("expected revert, got {:?}", other),
+        }
+
+        // Profit returned as uint256 (responses are popped LIFO: estimateGas, then call)
+        let check = ProfitCheck { token: Address::zero(), recipient: Address::zero(), min_profit: U256::from(50) };
+        mock.push::<U256, _>(U256::from(210_000)).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(ethers::abi::encode(&[Token::Uint(U256::from(40))]))).unwrap();
+        match simulate(&provider, &tx, None, Some(&check)).await {
+            Err(SimulationError::BelowThreshold { profit, .. }) => assert_eq!(profit, U256::from(40)),
+            other => panic!("expected threshold failure, got {:?}", other),
+        }
+
+        mock.push::<U256, _>(U256::from(210_000)).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(ethers::abi::encode(&[Token::Uint(U256::from(60))]))).unwrap();
+        let outcome = simulate(&provider, &tx, None, Some(&check)).await.unwrap();
+        assert_eq!(outcome.gas_estimate, U256::from(210_000));
+        assert_eq!(outcome.profit, Some(U256::from(60)));
+    }
+}
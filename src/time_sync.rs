@@ -0,0 +1,238 @@
+// Clock synchronization against an SNTP server and timestamp validation.
+// Timestamped data (e.g. Matrix2D price cells) reads the corrected clock via `now_millis()`.
+
+use crate::config::Settings;
+use log::{error, info, warn};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::net::UdpSocket;
+
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
+const NTP_UNIX_EPOCH_DELTA_SECS: u64 = 2_208_988_800;
+const SNTP_PACKET_LEN: usize = 48;
+const SNTP_DEFAULT_PORT: u16 = 123;
+const SNTP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Process-wide correction applied on top of the system clock (milliseconds).
+static CLOCK_OFFSET_MS: AtomicI64 = AtomicI64::new(0);
+
+#[derive(Debug, Error)]
+pub enum TimeSyncError {
+    #[error("SNTP socket error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("SNTP request to {0} timed out")]
+    Timeout(String),
+    #[error("Invalid SNTP response: {0}")]
+    InvalidResponse(String),
+    #[error("Clock drift of {offset_ms}ms exceeds max_timestamp_deviation_ms ({max_ms}ms)")]
+    DriftExceeded { offset_ms: i64, max_ms: u64 },
+    #[error("Timestamp {timestamp} is {ahead_ms}ms ahead of the corrected clock (max {max_ms}ms)")]
+    TimestampInFuture { timestamp: u64, ahead_ms: u64, max_ms: u64 },
+}
+
+fn system_now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+/// Current unix time in milliseconds, corrected by the last applied SNTP offset.
+pub fn now_millis() -> u64 {
+    (system_now_millis() + CLOCK_OFFSET_MS.load(Ordering::Relaxed)).max(0) as u64
+}
+
+/// Offset currently applied by `now_millis()` (milliseconds, positive = local clock is behind).
+pub fn clock_offset_ms() -> i64 {
+    CLOCK_OFFSET_MS.load(Ordering::Relaxed)
+}
+
+fn set_clock_offset_ms(offset_ms: i64) {
+    CLOCK_OFFSET_MS.store(offset_ms, Ordering::Relaxed);
+}
+
+fn unix_millis_to_ntp(millis: i64) -> [u8; 8] {
+    let millis = millis.max(0) as u64;
+    let secs = millis / 1000 + NTP_UNIX_EPOCH_DELTA_SECS;
+    let frac = ((millis % 1000) << 32) / 1000;
+    let mut out = [0u8; 8];
+    out[..4].copy_from_slice(&(secs as u32).to_be_bytes());
+    out[4..].copy_from_slice(&(frac as u32).to_be_bytes());
+    out
+}
+
+fn ntp_to_unix_millis(bytes: &[u8]) -> i64 {
+    let secs = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64;
+    let frac = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as i64;
+    (secs - NTP_UNIX_EPOCH_DELTA_SECS as i64) * 1000 + ((frac * 1000) >> 32)
+}
+
+/// `server` with the SNTP port appended unless it already has one. Accepts "host",
+/// "host:port", IPv4/IPv6 literals ("::1", "[::1]") and "[v6]:port".
+pub fn sntp_address(server: &str) -> String {
+    if server.parse::<SocketAddr>().is_ok() {
+        return server.to_string();
+    }
+    let host = server.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return SocketAddr::new(ip, SNTP_DEFAULT_PORT).to_string();
+    }
+    match server.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => server.to_string(),
+        _ => format!("{}:{}", server, SNTP_DEFAULT_PORT),
+    }
+}
+
+/// Query `server` (see `sntp_address`) once and return the local clock offset in milliseconds.
+pub async fn query_sntp_offset(server: &str, timeout: Duration) -> Result<i64, TimeSyncError> {
+    let addr = sntp_address(server);
+    let target = tokio::net::lookup_host(&addr)
+        .await?
+        .next()
+        .ok_or_else(|| TimeSyncError::InvalidResponse(format!("{} did not resolve", addr)))?;
+    let socket = UdpSocket::bind(if target.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }).await?;
+    socket.connect(target).await?;
+
+    // LI = 0, VN = 3, Mode = 3 (client)
+    let mut request = [0u8; SNTP_PACKET_LEN];
+    request[0] = 0x1B;
+    let t1 = system_now_millis();
+    let t1_ntp = unix_millis_to_ntp(t1);
+    request[40..48].copy_from_slice(&t1_ntp);
+    socket.send(&request).await?;
+
+    let mut response = [0u8; SNTP_PACKET_LEN];
+    let len = tokio::time::timeout(timeout, socket.recv(&mut response))
+        .await
+        .map_err(|_| TimeSyncError::Timeout(addr.clone()))??;
+    let t4 = system_now_millis();
+
+    if len < SNTP_PACKET_LEN {
+        return Err(TimeSyncError::InvalidResponse(format!("short packet ({} bytes)", len)));
+    }
+    if response[0] & 0x07 != 4 {
+        return Err(TimeSyncError::InvalidResponse(format!("unexpected mode {}", response[0] & 0x07)));
+    }
+    if response[1] == 0 {
+        return Err(TimeSyncError::InvalidResponse("kiss-o'-death (stratum 0)".to_string()));
+    }
+    if response[24..32] != t1_ntp {
+        return Err(TimeSyncError::InvalidResponse("originate timestamp mismatch".to_string()));
+    }
+
+    let t2 = ntp_to_unix_millis(&response[32..40]);
+    let t3 = ntp_to_unix_millis(&response[40..48]);
+    Ok(((t2 - t1) + (t3 - t4)) / 2)
+}
+
+/// Periodically measures local clock drift and validates data timestamps against it.
+pub struct TimeSync {
+    ntp_server: String,
+    sync_interval: Duration,
+    max_deviation_ms: u64,
+    validation_enabled: bool,
+    drift_correction: bool,
+    last_offset_ms: AtomicI64,
+    drift_exceeded: AtomicBool,
+}
+
+impl TimeSync {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            ntp_server: settings.ntp_server.clone(),
+            sync_interval: Duration::from_millis(settings.timestamp_sync_interval_ms.max(1)),
+            max_deviation_ms: settings.max_timestamp_deviation_ms,
+            validation_enabled: settings.timestamp_validation_enabled,
+            drift_correction: settings.clock_drift_correction,
+            last_offset_ms: AtomicI64::new(0),
+            drift_exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// Last offset measured against the SNTP server (milliseconds).
+    pub fn last_offset_ms(&self) -> i64 {
+        self.last_offset_ms.load(Ordering::Relaxed)
+    }
+
+    /// Measure the clock offset once, apply it to `now_millis()` if drift correction is
+    /// enabled, and fail if the drift exceeds `max_timestamp_deviation_ms`.
+    pub async fn sync_once(&self) -> Result<i64, TimeSyncError> {
+        let offset_ms = query_sntp_offset(&self.ntp_server, SNTP_TIMEOUT).await?;
+        self.last_offset_ms.store(offset_ms, Ordering::Relaxed);
+        if self.drift_correction {
+            set_clock_offset_ms(offset_ms);
+        }
+        let exceeded = offset_ms.unsigned_abs() > self.max_deviation_ms;
+        let was_exceeded = self.drift_exceeded.swap(exceeded, Ordering::Relaxed);
+        if exceeded && self.validation_enabled {
+            error!(
+                "[TimeSync] Local clock drift {}ms against {} exceeds max_timestamp_deviation_ms ({}ms)",
+                offset_ms, self.ntp_server, self.max_deviation_ms
+            );
+            return Err(TimeSyncError::DriftExceeded { offset_ms, max_ms: self.max_deviation_ms });
+        }
+        if was_exceeded && !exceeded {
+            info!("[TimeSync] Clock drift back within bounds ({}ms)", offset_ms);
+        }
+        Ok(offset_ms)
+    }
+
+    /// Validate a unix-millis timestamp against the corrected clock.
+    /// Fails while the measured drift exceeds the configured maximum.
+    pub fn validate_timestamp(&self, timestamp: u64) -> Result<(), TimeSyncError> {
+        if !self.validation_enabled {
+            return Ok(());
+        }
+        if self.drift_exceeded.load(Ordering::Relaxed) {
+            let offset_ms = self.last_offset_ms();
+            error!(
+                "[TimeSync] Rejecting timestamp {}: clock drift {}ms exceeds {}ms",
+                timestamp, offset_ms, self.max_deviation_ms
+            );
+            return Err(TimeSyncError::DriftExceeded { offset_ms, max_ms: self.max_deviation_ms });
+        }
+        let now = now_millis();
+        if timestamp > now + self.max_deviation_ms {
+            return Err(TimeSyncError::TimestampInFuture {
+                timestamp,
+                ahead_ms: timestamp - now,
+                max_ms: self.max_deviation_ms,
+            });
+        }
+        Ok(())
+    }
+
+    /// Run the sync loop forever at `timestamp_sync_interval_ms`.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.sync_interval);
+        loop {
+            interval.tick().await;
+            match self.sync_once().await {
+                Ok(_) | Err(TimeSyncError::DriftExceeded { .. }) => {}
+                Err(e) => warn!("[TimeSync] Sync against {} failed: {}", self.ntp_server, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntp_timestamp_roundtrip() {
+        let millis = 1_700_000_000_123i64;
+        let ntp = unix_millis_to_ntp(millis);
+        assert!((ntp_to_unix_millis(&ntp) - millis).abs() <= 1);
+    }
+
+    #[test]
+    fn test_sntp_address() {
+        assert_eq!(sntp_address("pool.ntp.org"), "pool.ntp.org:123");
+        assert_eq!(sntp_address("pool.ntp.org:1123"), "pool.ntp.org:1123");
+        assert_eq!(sntp_address("192.0.2.1"), "192.0.2.1:123");
+        assert_eq!(sntp_address("2001:db8::1"), "[2001:db8::1]:123");
+        assert_eq!(sntp_address("[2001:db8::1]"), "[2001:db8::1]:123");
+        assert_eq!(sntp_address("[2001:db8::1]:1123"), "[2001:db8::1]:1123");
+    }
+}
@@ -0,0 +1,244 @@
+// Token amounts: a raw U256 together with its token's decimals. Human amounts are parsed and
+// printed through decimal strings rather than float scaling, arithmetic is checked, and the
+// only lossy step - turning an amount into an f64 for USD reporting - is explicit.
+
+use ethers::types::{U256, U512};
+use std::fmt;
+use thiserror::Error;
+
+const BPS: u64 = 10_000;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TokenAmountError {
+    #[error("Invalid amount {0}: {1}")]
+    Invalid(String, String),
+    #[error("Amount overflow")]
+    Overflow,
+    #[error("Amount underflow")]
+    Underflow,
+    #[error("Decimals mismatch: {0} vs {1}")]
+    DecimalsMismatch(u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TokenAmount {
+    raw: U256,
+    decimals: u8,
+}
+
+/// 10^decimals, if it fits in a U256.
+fn unit(decimals: u8) -> Result<U256, TokenAmountError> {
+    U256::from(10u64).checked_pow(U256::from(decimals)).ok_or(TokenAmountError::Overflow)
+}
+
+fn narrow(value: U512) -> Result<U256, TokenAmountError> {
+    U256::try_from(value).map_err(|_| TokenAmountError::Overflow)
+}
+
+/// `fraction` (0.45 = 45%) of `raw`, to whole basis points and rounded down.
+pub fn raw_fraction(raw: U256, fraction: f64) -> Result<U256, TokenAmountError> {
+    if !fraction.is_finite() || fraction < 0.0 {
+        return Err(TokenAmountError::Invalid(fraction.to_string(), "fraction must be finite and non-negative".to_string()));
+    }
+    let bps = (fraction * BPS as f64).round();
+    if bps > u64::MAX as f64 {
+        return Err(TokenAmountError::Overflow);
+    }
+    narrow(raw.full_mul(U256::from(bps as u64)) / U512::from(BPS))
+}
+
+impl TokenAmount {
+    pub fn new(raw: U256, decimals: u8) -> Self {
+        Self { raw, decimals }
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Self::new(U256::zero(), decimals)
+    }
+
+    pub fn raw(&self) -> U256 {
+        self.raw
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw.is_zero()
+    }
+
+    /// Parse a non-negative decimal string ("1.25"). More fractional digits than the token has
+    /// is an error rather than a silent truncation.
+    pub fn parse(amount: &str, decimals: u8) -> Result<Self, TokenAmountError> {
+        let invalid = |reason: &str| TokenAmountError::Invalid(amount.to_string(), reason.to_string());
+        let amount = amount.trim().replace('_', "");
+        let (whole, fraction) = amount.split_once('.').unwrap_or((&amount, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(invalid("empty"));
+        }
+        if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid("not a non-negative decimal number"));
+        }
+        if fraction.len() > decimals as usize {
+            return Err(invalid(&format!("more than {} decimal places", decimals)));
+        }
+        let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+        let digits = digits.trim_start_matches('0');
+        if digits.is_empty() {
+            return Ok(Self::zero(decimals));
+        }
+        let raw = U256::from_dec_str(digits).map_err(|_| TokenAmountError::Overflow)?;
+        Ok(Self::new(raw, decimals))
+    }
+
+    /// From a human amount given as a float, rounded to the token's decimals. NaN, infinities
+    /// and negative amounts are rejected.
+    pub fn from_f64(amount: f64, decimals: u8) -> Result<Self, TokenAmountError> {
+        if !amount.is_finite() || amount < 0.0 {
+            return Err(TokenAmountError::Invalid(amount.to_string(), "must be finite and non-negative".to_string()));
+        }
+        Self::parse(&format!("{:.*}", decimals as usize, amount), decimals)
+    }
+
+    /// The amount in human units as an f64. Lossy beyond ~15 significant digits; meant for
+    /// USD reporting and display only.
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::INFINITY)
+    }
+
+    /// USD value at `price_usd` per whole token, if both are finite.
+    pub fn usd(&self, price_usd: f64) -> Option<f64> {
+        Some(self.to_f64() * price_usd).filter(|usd| usd.is_finite())
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Result<Self, TokenAmountError> {
+        self.same_decimals(other)?;
+        let raw = self.raw.checked_add(other.raw).ok_or(TokenAmountError::Overflow)?;
+        Ok(Self::new(raw, self.decimals))
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Result<Self, TokenAmountError> {
+        self.same_decimals(other)?;
+        let raw = self.raw.checked_sub(other.raw).ok_or(TokenAmountError::Underflow)?;
+        Ok(Self::new(raw, self.decimals))
+    }
+
+    /// `self × numerator / denominator`, rounded down.
+    pub fn mul_div(&self, numerator: U256, denominator: U256) -> Result<Self, TokenAmountError> {
+        if denominator.is_zero() {
+            return Err(TokenAmountError::Invalid(self.to_string(), "division by zero".to_string()));
+        }
+        let raw = narrow(self.raw.full_mul(numerator) / U512::from(denominator))?;
+        Ok(Self::new(raw, self.decimals))
+    }
+
+    /// `fraction` (0.45 = 45%) of this amount, to whole basis points.
+    pub fn fraction(&self, fraction: f64) -> Result<Self, TokenAmountError> {
+        Ok(Self::new(raw_fraction(self.raw, fraction)?, self.decimals))
+    }
+
+    /// This amount valued at `price`, the amount of another token paid per whole unit of this
+    /// one. The result is in the price's token.
+    pub fn value_at(&self, price: &TokenAmount) -> Result<TokenAmount, TokenAmountError> {
+        price.mul_div(self.raw, unit(self.decimals)?)
+    }
+
+    /// The same amount expressed with `decimals` places; fails if precision would be lost.
+    pub fn rescale(&self, decimals: u8) -> Result<Self, TokenAmountError> {
+        if decimals >= self.decimals {
+            let raw = self.raw.checked_mul(unit(decimals - self.decimals)?).ok_or(TokenAmountError::Overflow)?;
+            return Ok(Self::new(raw, decimals));
+        }
+        let (raw, remainder) = self.raw.div_mod(unit(self.decimals - decimals)?);
+        if !remainder.is_zero() {
+            return Err(TokenAmountError::Invalid(self.to_string(), format!("not representable with {} decimals", decimals)));
+        }
+        Ok(Self::new(raw, decimals))
+    }
+
+    fn same_decimals(&self, other: &Self) -> Result<(), TokenAmountError> {
+        if self.decimals != other.decimals {
+            return Err(TokenAmountError::DecimalsMismatch(self.decimals, other.decimals));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    /// Exact decimal representation, without trailing zeros.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.raw.to_string();
+        let decimals = self.decimals as usize;
+        if decimals == 0 {
+            return f.write_str(&digits);
+        }
+        let padded = format!("{:0>width$}", digits, width = decimals + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            f.write_str(whole)
+        } else {
+            write!(f, "{}.{}", whole, fraction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        let amount = TokenAmount::parse("1.25", 18).unwrap();
+        assert_eq!(amount.raw(), U256::from(125u64) * U256::exp10(16));
+        assert_eq!(amount.to_string(), "1.25");
+        assert_eq!(TokenAmount::parse("0.000001", 6).unwrap().raw(), U256::one());
+        assert_eq!(TokenAmount::parse("42", 0).unwrap().to_string(), "42");
+        assert_eq!(TokenAmount::parse("0", 18).unwrap(), TokenAmount::zero(18));
+        assert!(TokenAmount::parse("0.0000001", 6).is_err());
+        assert!(TokenAmount::parse("-1", 18).is_err());
+        assert!(TokenAmount::parse("1e3", 18).is_err());
+        // Above 2^256: an error, not a panic or a wrap
+        let huge = "1".repeat(80);
+        assert_eq!(TokenAmount::parse(&huge, 0), Err(TokenAmountError::Overflow));
+
+        // Amounts above 2^128 keep every digit
+        let big = TokenAmount::new(U256::MAX, 18);
+        assert_eq!(TokenAmount::parse(&big.to_string(), 18).unwrap(), big);
+        assert!(TokenAmount::from_f64(f64::NAN, 18).is_err());
+        assert_eq!(TokenAmount::from_f64(1.5, 6).unwrap().raw(), U256::from(1_500_000u64));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let a = TokenAmount::parse("1.5", 18).unwrap();
+        let b = TokenAmount::parse("0.5", 18).unwrap();
+        assert_eq!(a.checked_add(&b).unwrap().to_string(), "2");
+        assert_eq!(b.checked_sub(&a), Err(TokenAmountError::Underflow));
+        assert_eq!(a.checked_add(&TokenAmount::zero(6)), Err(TokenAmountError::DecimalsMismatch(18, 6)));
+        assert_eq!(TokenAmount::new(U256::MAX, 0).checked_add(&TokenAmount::new(U256::one(), 0)), Err(TokenAmountError::Overflow));
+
+        // No intermediate overflow: the product is taken in 512 bits
+        assert_eq!(raw_fraction(U256::MAX, 0.5).unwrap(), U256::MAX / 2);
+        assert_eq!(raw_fraction(U256::from(100u64), 0.45).unwrap(), U256::from(45u64));
+        assert!(raw_fraction(U256::one(), f64::INFINITY).is_err());
+        assert_eq!(TokenAmount::new(U256::MAX, 0).mul_div(2.into(), 1.into()), Err(TokenAmountError::Overflow));
+
+        assert_eq!(TokenAmount::parse("1.5", 6).unwrap().rescale(18).unwrap(), a);
+        assert!(TokenAmount::parse("1.0000005", 18).unwrap().rescale(6).is_err());
+    }
+
+    #[test]
+    fn test_value_at_price() {
+        // 2.5 WBNB at 600.10 BUSD each
+        let wbnb = TokenAmount::parse("2.5", 18).unwrap();
+        let price = TokenAmount::parse("600.10", 18).unwrap();
+        assert_eq!(wbnb.value_at(&price).unwrap().to_string(), "1500.25");
+        // Price in a 6-decimal stablecoin
+        let price = TokenAmount::parse("600.1", 6).unwrap();
+        let value = wbnb.value_at(&price).unwrap();
+        assert_eq!((value.to_string().as_str(), value.decimals()), ("1500.25", 6));
+        assert_eq!(value.usd(1.0), Some(1500.25));
+    }
+}
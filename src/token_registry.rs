@@ -0,0 +1,292 @@
+// Token registry: symbol <-> address resolution per chain, cached ERC20 metadata and
+// liquidity categories. All human <-> raw amount conversions should go through here.
+
+use crate::config::Settings;
+use crate::token_amount::TokenAmount;
+use ethers::abi::Abi;
+use ethers::prelude::*;
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+/// Chain ID the `token_*` settings refer to.
+pub const BSC_MAINNET_CHAIN_ID: u64 = 56;
+
+static ERC20_ABI: Lazy<Abi> = Lazy::new(|| {
+    serde_json::from_str(include_str!("abi/ERC20.json")).expect("ABI parse error")
+});
+
+const STABLE_SYMBOLS: &[&str] = &["BUSD", "USDT", "USDC", "DAI", "TUSD", "FRAX", "VAI", "MIM", "USDP"];
+const MAJOR_SYMBOLS: &[&str] = &["WBNB", "BNB", "ETH", "WETH", "BTCB", "WBTC"];
+const MEME_SYMBOLS: &[&str] = &[
+    "DOGE", "SHIB", "FLOKI", "BABYDOGE", "SAFEMOON", "CATE", "ELONGATE", "LOWB", "SAFEMARS",
+];
+
+#[derive(Debug, Error)]
+pub enum TokenRegistryError {
+    #[error("Unknown token {0}")]
+    UnknownToken(String),
+    #[error("Decimals not loaded for token {0}")]
+    DecimalsUnknown(String),
+    #[error("Invalid amount for {0}: {1}")]
+    InvalidAmount(String, String),
+    #[error("Contract call failed for {0}: {1}")]
+    Contract(String, String),
+}
+
+/// Liquidity category, matching the `max_liquidity_utilization_*` settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenCategory {
+    Stable,
+    Major,
+    Alt,
+    Meme,
+}
+
+impl TokenCategory {
+    pub fn classify(symbol: &str) -> Self {
+        let symbol = symbol.to_uppercase();
+        if STABLE_SYMBOLS.contains(&symbol.as_str()) {
+            TokenCategory::Stable
+        } else if MAJOR_SYMBOLS.contains(&symbol.as_str()) {
+            TokenCategory::Major
+        } else if MEME_SYMBOLS.contains(&symbol.as_str()) {
+            TokenCategory::Meme
+        } else {
+            TokenCategory::Alt
+        }
+    }
+
+    /// The configured `max_liquidity_utilization_*` value for this category.
+    pub fn max_liquidity_utilization(&self, settings: &Settings) -> f64 {
+        match self {
+            TokenCategory::Stable => settings.max_liquidity_utilization_stable,
+            TokenCategory::Major => settings.max_liquidity_utilization_major,
+            TokenCategory::Alt => settings.max_liquidity_utilization_alt,
+            TokenCategory::Meme => settings.max_liquidity_utilization_meme,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    pub chain_id: u64,
+    /// Configured symbol (upper-case), used by Matrix2D and the matrix settings.
+    pub symbol: String,
+    pub address: Address,
+    /// `decimals()` as reported on-chain; `None` until fetched or registered.
+    pub decimals: Option<u8>,
+    /// `symbol()` as reported on-chain, if fetched.
+    pub onchain_symbol: Option<String>,
+    pub category: TokenCategory,
+}
+
+#[derive(Default)]
+struct RegistryInner {
+    by_address: HashMap<(u64, Address), TokenInfo>,
+    by_symbol: HashMap<(u64, String), Address>,
+}
+
+#[derive(Default)]
+pub struct TokenRegistry {
+    inner: RwLock<RegistryInner>,
+}
+
+impl TokenRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the registry from the `token_*` settings (BSC mainnet) and the `tokens` table of
+    /// every `[[chains]]` entry. Decimals are fetched lazily.
+    pub fn from_settings(settings: &Settings) -> Self {
+        let registry = Self::new();
+        let chain_tokens = settings.chains.iter().flat_map(|chain| {
+            chain.tokens.iter().map(move |(symbol, addr)| (chain.chain_id, symbol.as_str(), addr.as_str()))
+        });
+        let bsc_tokens = settings.token_addresses().into_iter().map(|(symbol, addr)| (BSC_MAINNET_CHAIN_ID, symbol, addr));
+        for (chain_id, symbol, addr) in bsc_tokens.chain(chain_tokens) {
+            match addr.parse::<Address>() {
+                Ok(address) => registry.register(chain_id, symbol, address, None),
+                Err(e) => warn!("[TokenRegistry] Invalid address for {} on chain {}: {} ({})", symbol, chain_id, addr, e),
+            }
+        }
+        registry
+    }
+
+    /// Register (or replace) a token. `decimals` may be supplied when known up front.
+    pub fn register(&self, chain_id: u64, symbol: &str, address: Address, decimals: Option<u8>) {
+        let symbol = symbol.to_uppercase();
+        let info = TokenInfo {
+            chain_id,
+            symbol: symbol.clone(),
+            address,
+            decimals,
+            onchain_symbol: None,
+            category: TokenCategory::classify(&symbol),
+        };
+        let mut inner = self.inner.write().unwrap();
+        inner.by_symbol.insert((chain_id, symbol), address);
+        inner.by_address.insert((chain_id, address), info);
+    }
+
+    pub fn resolve_symbol(&self, chain_id: u64, symbol: &str) -> Option<Address> {
+        let inner = self.inner.read().unwrap();
+        inner.by_symbol.get(&(chain_id, symbol.to_uppercase())).copied()
+    }
+
+    pub fn symbol_of(&self, chain_id: u64, address: Address) -> Option<String> {
+        self.get(chain_id, address).map(|t| t.symbol)
+    }
+
+    pub fn get(&self, chain_id: u64, address: Address) -> Option<TokenInfo> {
+        let inner = self.inner.read().unwrap();
+        inner.by_address.get(&(chain_id, address)).cloned()
+    }
+
+    pub fn get_by_symbol(&self, chain_id: u64, symbol: &str) -> Option<TokenInfo> {
+        let address = self.resolve_symbol(chain_id, symbol)?;
+        self.get(chain_id, address)
+    }
+
+    pub fn tokens(&self, chain_id: u64) -> Vec<TokenInfo> {
+        let inner = self.inner.read().unwrap();
+        inner.by_address.values().filter(|t| t.chain_id == chain_id).cloned().collect()
+    }
+
+    /// Chains with at least one registered token, in ascending order.
+    pub fn chain_ids(&self) -> Vec<u64> {
+        let inner = self.inner.read().unwrap();
+        let mut chain_ids: Vec<u64> = inner.by_address.keys().map(|(chain_id, _)| *chain_id).collect();
+        chain_ids.sort_unstable();
+        chain_ids.dedup();
+        chain_ids
+    }
+
+    /// Category of a token by address; unknown tokens are treated as alts.
+    pub fn category(&self, chain_id: u64, address: Address) -> TokenCategory {
+        self.get(chain_id, address).map(|t| t.category).unwrap_or(TokenCategory::Alt)
+    }
+
+    pub fn decimals(&self, chain_id: u64, address: Address) -> Result<u8, TokenRegistryError> {
+        let info = self
+            .get(chain_id, address)
+            .ok_or_else(|| TokenRegistryError::UnknownToken(format!("{:?}", address)))?;
+        info.decimals.ok_or(TokenRegistryError::DecimalsUnknown(info.symbol))
+    }
+
+    /// Fetch `decimals()`/`symbol()` for a registered token unless already cached.
+    pub async fn fetch_metadata<M: Middleware + 'static>(
+        &self,
+        chain_id: u64,
+        address: Address,
+        client: Arc<M>,
+    ) -> Result<TokenInfo, TokenRegistryError> {
+        let info = self
+            .get(chain_id, address)
+            .ok_or_else(|| TokenRegistryError::UnknownToken(format!("{:?}", address)))?;
+        if info.decimals.is_some() && info.onchain_symbol.is_some() {
+            return Ok(info);
+        }
+        let erc20 = Contract::new(address, ERC20_ABI.clone(), client);
+        let decimals: u8 = erc20
+            .method::<_, u8>("decimals", ())
+            .map_err(|e| TokenRegistryError::Contract(info.symbol.clone(), e.to_string()))?
+            .call()
+            .await
+            .map_err(|e| TokenRegistryError::Contract(info.symbol.clone(), e.to_string()))?;
+        // Some tokens return bytes32 symbols; a failed symbol() call is not fatal.
+        let onchain_symbol = match erc20.method::<_, String>("symbol", ()) {
+            Ok(call) => call.call().await.ok(),
+            Err(_) => None,
+        };
+        if let Some(onchain) = onchain_symbol.as_ref().filter(|s| !s.eq_ignore_ascii_case(&info.symbol)) {
+            warn!(
+                "[TokenRegistry] Configured symbol {} differs from on-chain symbol {} at {:?}",
+                info.symbol, onchain, address
+            );
+        }
+        let mut inner = self.inner.write().unwrap();
+        let entry = inner
+            .by_address
+            .get_mut(&(chain_id, address))
+            .ok_or_else(|| TokenRegistryError::UnknownToken(format!("{:?}", address)))?;
+        entry.decimals = Some(decimals);
+        entry.onchain_symbol = onchain_symbol;
+        Ok(entry.clone())
+    }
+
+    /// Fetch metadata for every registered token on `chain_id`, logging failures.
+    pub async fn load_all_metadata<M: Middleware + 'static>(&self, chain_id: u64, client: Arc<M>) {
+        let tokens = self.tokens(chain_id);
+        let mut loaded = 0usize;
+        for token in &tokens {
+            match self.fetch_metadata(chain_id, token.address, client.clone()).await {
+                Ok(_) => loaded += 1,
+                Err(e) => warn!("[TokenRegistry] {}", e),
+            }
+        }
+        info!("[TokenRegistry] Loaded metadata for {}/{} tokens on chain {}", loaded, tokens.len(), chain_id);
+    }
+
+    /// `raw` units of `address` as a TokenAmount.
+    pub fn amount(&self, chain_id: u64, address: Address, raw: U256) -> Result<TokenAmount, TokenRegistryError> {
+        Ok(TokenAmount::new(raw, self.decimals(chain_id, address)?))
+    }
+
+    /// A human-readable amount of `address` as a TokenAmount, rounded to the token's decimals.
+    pub fn parse_amount(&self, chain_id: u64, address: Address, amount: f64) -> Result<TokenAmount, TokenRegistryError> {
+        let decimals = self.decimals(chain_id, address)?;
+        TokenAmount::from_f64(amount, decimals).map_err(|e| TokenRegistryError::InvalidAmount(format!("{:?}", address), e.to_string()))
+    }
+
+    /// Convert a human-readable amount into raw token units.
+    pub fn to_raw(&self, chain_id: u64, address: Address, amount: f64) -> Result<U256, TokenRegistryError> {
+        self.parse_amount(chain_id, address, amount).map(|a| a.raw())
+    }
+
+    /// Convert raw token units into a human-readable amount.
+    pub fn from_raw(&self, chain_id: u64, address: Address, raw: U256) -> Result<f64, TokenRegistryError> {
+        self.amount(chain_id, address, raw).map(|a| a.to_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_categories() {
+        assert_eq!(TokenCategory::classify("busd"), TokenCategory::Stable);
+        assert_eq!(TokenCategory::classify("WBNB"), TokenCategory::Major);
+        assert_eq!(TokenCategory::classify("CAKE"), TokenCategory::Alt);
+        assert_eq!(TokenCategory::classify("SHIB"), TokenCategory::Meme);
+    }
+
+    #[test]
+    fn test_symbol_resolution_and_conversions() {
+        let registry = TokenRegistry::new();
+        let usdt = Address::from_low_u64_be(1);
+        registry.register(BSC_MAINNET_CHAIN_ID, "usdt", usdt, Some(18));
+        assert_eq!(registry.resolve_symbol(BSC_MAINNET_CHAIN_ID, "USDT"), Some(usdt));
+        assert_eq!(registry.symbol_of(BSC_MAINNET_CHAIN_ID, usdt).as_deref(), Some("USDT"));
+        assert!(registry.resolve_symbol(1, "USDT").is_none());
+
+        let raw = registry.to_raw(BSC_MAINNET_CHAIN_ID, usdt, 1.5).unwrap();
+        assert_eq!(raw, U256::from(15u64) * U256::exp10(17));
+        assert_eq!(registry.from_raw(BSC_MAINNET_CHAIN_ID, usdt, raw).unwrap(), 1.5);
+    }
+
+    #[test]
+    fn test_conversion_requires_decimals() {
+        let registry = TokenRegistry::new();
+        let token = Address::from_low_u64_be(2);
+        registry.register(BSC_MAINNET_CHAIN_ID, "CAKE", token, None);
+        assert!(matches!(
+            registry.to_raw(BSC_MAINNET_CHAIN_ID, token, 1.0),
+            Err(TokenRegistryError::DecimalsUnknown(_))
+        ));
+    }
+}
@@ -0,0 +1,370 @@
+// Transaction lifecycle tracking: follows each submitted transaction until it has
+// `transaction_confirmation_blocks` confirmations. After `transaction_timeout_ms` without being
+// mined it is re-sent with bumped fees (`auto_retry_failed_transactions`), and once the
+// speed-ups are used up the nonce is cancelled with a zero-value self-transfer. A semaphore
+// caps concurrent pending transactions at `max_pending_transactions`, and the final status is
+// written back to the ExecutionLog.
+
+use crate::config::Settings;
+use crate::execution_log::{ExecutionLog, TxStatus};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Replacement transactions must outbid the original; nodes require at least +10%
+const FEE_BUMP_PCT: u64 = 15;
+// Fee-bumped re-sends of the original transaction before it is cancelled
+const MAX_SPEED_UPS: u32 = 2;
+const CANCEL_GAS_LIMIT: u64 = 21_000;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct TxTrackerConfig {
+    pub confirmations: u64,
+    /// How long a transaction may stay unmined before it is replaced
+    pub timeout: Duration,
+    /// Speed up stuck transactions before cancelling them; without it they are only cancelled
+    pub auto_retry: bool,
+    pub poll_interval: Duration,
+}
+
+impl TxTrackerConfig {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            confirmations: settings.transaction_confirmation_blocks.max(1),
+            timeout: Duration::from_millis(settings.transaction_timeout_ms),
+            auto_retry: settings.auto_retry_failed_transactions,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+}
+
+/// How a tracked transaction ended. `tx_hash` is the hash that was mined, which differs from
+/// the submitted one after a replacement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxOutcome {
+    Confirmed { tx_hash: TxHash, block: u64, gas_used: Option<U256> },
+    Reverted { tx_hash: TxHash, block: u64, gas_used: Option<U256> },
+    Cancelled { tx_hash: TxHash },
+    Dropped,
+}
+
+impl TxOutcome {
+    pub fn status(&self) -> TxStatus {
+        match self {
+            TxOutcome::Confirmed { .. } => TxStatus::Confirmed,
+            TxOutcome::Reverted { .. } => TxStatus::Reverted,
+            TxOutcome::Cancelled { .. } => TxStatus::Cancelled,
+            TxOutcome::Dropped => TxStatus::Dropped,
+        }
+    }
+}
+
+/// Raise the fees of `tx` by `pct` percent, keeping its type.
+pub fn bump_fees(tx: &mut TypedTransaction, pct: u64) {
+    let bump = |fee: U256| fee * (100 + pct) / 100 + 1;
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            inner.max_fee_per_gas = inner.max_fee_per_gas.map(bump);
+            inner.max_priority_fee_per_gas = inner.max_priority_fee_per_gas.map(bump);
+        }
+        _ => {
+            if let Some(gas_price) = tx.gas_price() {
+                tx.set_gas_price(bump(gas_price));
+            }
+        }
+    }
+}
+
+// Zero-value self-transfer at the same nonce, priced above `tx`
+fn cancellation_of(tx: &TypedTransaction) -> Option<TypedTransaction> {
+    let from = *tx.from()?;
+    let mut cancel = tx.clone();
+    cancel.set_to(from);
+    cancel.set_value(U256::zero());
+    cancel.set_data(Bytes::new());
+    cancel.set_gas(CANCEL_GAS_LIMIT);
+    bump_fees(&mut cancel, FEE_BUMP_PCT);
+    Some(cancel)
+}
+
+/// Slot for one pending transaction; released when dropped.
+pub struct PendingSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+pub struct TxTracker {
+    config: TxTrackerConfig,
+    slots: Arc<Semaphore>,
+    max_pending: usize,
+    execution_log: Arc<ExecutionLog>,
+}
+
+impl TxTracker {
+    pub fn new(config: TxTrackerConfig, max_pending: usize, execution_log: Arc<ExecutionLog>) -> Self {
+        let max_pending = max_pending.max(1);
+        Self { config, slots: Arc::new(Semaphore::new(max_pending)), max_pending, execution_log }
+    }
+
+    pub fn from_settings(settings: &Settings, execution_log: Arc<ExecutionLog>) -> Self {
+        Self::new(TxTrackerConfig::from_settings(settings), settings.max_pending_transactions as usize, execution_log)
+    }
+
+    /// The log final statuses are written back to
+    pub fn execution_log(&self) -> &Arc<ExecutionLog> {
+        &self.execution_log
+    }
+
+    /// Wait for a free pending-transaction slot. Hold it from before submission until
+    /// `track` returns.
+    pub async fn reserve(&self) -> PendingSlot {
+        let permit = self.slots.clone().acquire_owned().await.expect("pending transaction semaphore closed");
+        PendingSlot { _permit: permit }
+    }
+
+    /// Transactions currently holding a slot.
+    pub fn pending(&self) -> usize {
+        self.max_pending - self.slots.available_permits()
+    }
+
+    /// Follow `tx` (as sent, with its nonce and fees) from `tx_hash` to its final state,
+    /// replacing it after each timeout, and record the outcome against `tx_hash` in the
+    /// ExecutionLog.
+    pub async fn track<M: Middleware + 'static>(&self, client: Arc<M>, tx: TypedTransaction, tx_hash: TxHash) -> TxOutcome {
+        let outcome = self.follow(&*client, tx, tx_hash).await;
+        let submitted = format!("0x{:x}", tx_hash);
+        let updated = self.execution_log.update_by_tx_hash(&submitted, |record| {
+            record.status = outcome.status();
+            match &outcome {
+                TxOutcome::Confirmed { tx_hash, gas_used, .. } => {
+                    record.success = true;
+                    record.gas_used = gas_used.map(|g| g.low_u64());
+                    record.tx_hash = Some(format!("0x{:x}", tx_hash));
+                }
+                TxOutcome::Reverted { tx_hash, gas_used, .. } => {
+                    record.success = false;
+                    record.gas_used = gas_used.map(|g| g.low_u64());
+                    record.tx_hash = Some(format!("0x{:x}", tx_hash));
+                    record.error = Some("Transaction reverted".to_string());
+                }
+                TxOutcome::Cancelled { tx_hash } => {
+                    record.success = false;
+                    record.tx_hash = Some(format!("0x{:x}", tx_hash));
+                    record.error = Some(format!("Cancelled after {:?} unmined", self.config.timeout));
+                }
+                TxOutcome::Dropped => {
+                    record.success = false;
+                    record.error = Some("Transaction was never mined".to_string());
+                }
+            }
+        });
+        if !updated {
+            warn!("[TxTracker] No execution record for {}", submitted);
+        }
+        outcome
+    }
+
+    async fn follow<M: Middleware>(&self, client: &M, mut tx: TypedTransaction, tx_hash: TxHash) -> TxOutcome {
+        // Every hash sent for this nonce; any one of them may be the one that gets mined
+        let mut sent = vec![tx_hash];
+        let mut cancel_hash: Option<TxHash> = None;
+        let mut speed_ups = 0;
+        let mut deadline = Instant::now() + self.config.timeout;
+        loop {
+            tokio::time::sleep(self.config.poll_interval).await;
+
+            let mut mined = None;
+            for hash in &sent {
+                match client.get_transaction_receipt(*hash).await {
+                    Ok(Some(receipt)) => {
+                        mined = Some(receipt);
+                        break;
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("[TxTracker] Receipt lookup for 0x{:x} failed: {}", hash, e),
+                }
+            }
+            if let Some(receipt) = mined {
+                let Some(block) = receipt.block_number.map(|b| b.as_u64()) else {
+                    continue;
+                };
+                let head = match client.get_block_number().await {
+                    Ok(head) => head.as_u64(),
+                    Err(e) => {
+                        warn!("[TxTracker] Block number lookup failed: {}", e);
+                        continue;
+                    }
+                };
+                if head + 1 < block + self.config.confirmations {
+                    continue;
+                }
+                let hash = receipt.transaction_hash;
+                let gas_used = receipt.gas_used;
+                info!("[TxTracker] 0x{:x} final in block {} (status {:?})", hash, block, receipt.status);
+                return if Some(hash) == cancel_hash {
+                    TxOutcome::Cancelled { tx_hash: hash }
+                } else if receipt.status == Some(U64::one()) {
+                    TxOutcome::Confirmed { tx_hash: hash, block, gas_used }
+                } else {
+                    TxOutcome::Reverted { tx_hash: hash, block, gas_used }
+                };
+            }
+
+            if Instant::now() < deadline {
+                continue;
+            }
+            if cancel_hash.is_some() {
+                warn!("[TxTracker] 0x{:x} and its replacements were never mined", tx_hash);
+                return TxOutcome::Dropped;
+            }
+            let (replacement, cancelling) = if self.config.auto_retry && speed_ups < MAX_SPEED_UPS {
+                speed_ups += 1;
+                bump_fees(&mut tx, FEE_BUMP_PCT);
+                (tx.clone(), false)
+            } else {
+                match cancellation_of(&tx) {
+                    Some(cancel) => (cancel, true),
+                    None => return TxOutcome::Dropped,
+                }
+            };
+            match client.send_transaction(replacement, None).await {
+                Ok(pending) => {
+                    let hash = pending.tx_hash();
+                    if cancelling {
+                        info!("[TxTracker] Cancelling 0x{:x} with 0x{:x}", tx_hash, hash);
+                        cancel_hash = Some(hash);
+                    } else {
+                        info!("[TxTracker] Sped up 0x{:x} with 0x{:x} (attempt {})", tx_hash, hash, speed_ups);
+                    }
+                    sent.push(hash);
+                }
+                // The original may have been mined in the meantime ("nonce too low"); keep polling
+                Err(e) => warn!("[TxTracker] Replacement for 0x{:x} failed: {}", tx_hash, e),
+            }
+            deadline = Instant::now() + self.config.timeout;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution_log::ExecutionRecord;
+
+    fn config(timeout: Duration) -> TxTrackerConfig {
+        TxTrackerConfig { confirmations: 2, timeout, auto_retry: true, poll_interval: Duration::ZERO }
+    }
+
+    fn record(tx_hash: TxHash) -> ExecutionRecord {
+        ExecutionRecord {
+            timestamp: chrono::Utc::now(),
+            protocol: "Venus".to_string(),
+            account: "0xabc".to_string(),
+            debt: 1.0,
+            collateral: 2.0,
+            success: false,
+            profit: 0.0,
+            gas_used: None,
+            gas_cost_native: None,
+            tx_hash: Some(format!("0x{:x}", tx_hash)),
+            error: None,
+            status: TxStatus::Pending,
+            transfer: None,
+        }
+    }
+
+    fn receipt(tx_hash: TxHash, block: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: tx_hash,
+            block_number: Some(block.into()),
+            gas_used: Some(U256::from(180_000)),
+            status: Some(U64::one()),
+            ..Default::default()
+        }
+    }
+
+    fn legacy_tx() -> TypedTransaction {
+        TransactionRequest::new()
+            .from(Address::from_low_u64_be(1))
+            .to(Address::from_low_u64_be(2))
+            .gas(300_000)
+            .gas_price(1_000)
+            .nonce(7)
+            .into()
+    }
+
+    #[test]
+    fn test_bump_fees_and_cancellation() {
+        let mut tx = legacy_tx();
+        bump_fees(&mut tx, 15);
+        assert_eq!(tx.gas_price(), Some(U256::from(1_151)));
+
+        let mut eip1559: TypedTransaction = Eip1559TransactionRequest::new()
+            .max_fee_per_gas(2_000)
+            .max_priority_fee_per_gas(100)
+            .into();
+        bump_fees(&mut eip1559, 15);
+        if let TypedTransaction::Eip1559(inner) = &eip1559 {
+            assert_eq!(inner.max_fee_per_gas, Some(U256::from(2_301)));
+            assert_eq!(inner.max_priority_fee_per_gas, Some(U256::from(116)));
+        }
+
+        let cancel = cancellation_of(&tx).unwrap();
+        assert_eq!(cancel.to_addr(), Some(&Address::from_low_u64_be(1)));
+        assert_eq!(cancel.nonce(), Some(&U256::from(7)));
+        assert_eq!(cancel.gas(), Some(&U256::from(CANCEL_GAS_LIMIT)));
+        assert!(cancel.gas_price().unwrap() > tx.gas_price().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_confirmations_and_updates_log() {
+        let (provider, mock) = Provider::mocked();
+        let log = Arc::new(ExecutionLog::new());
+        let tracker = TxTracker::new(config(Duration::from_secs(60)), 1, log.clone());
+        let hash = TxHash::from_low_u64_be(0xaa);
+        log.log(record(hash));
+
+        // Responses are popped LIFO: the last pushed is the first call
+        mock.push::<U64, _>(U64::from(11)).unwrap(); // block number: 2 confirmations
+        mock.push(receipt(hash, 10)).unwrap();
+        mock.push::<U64, _>(U64::from(10)).unwrap(); // block number: 1 confirmation
+        mock.push(receipt(hash, 10)).unwrap();
+        mock.push::<Option<TransactionReceipt>, _>(None).unwrap(); // not mined yet
+
+        let slot = tracker.reserve().await;
+        assert_eq!(tracker.pending(), 1);
+        let outcome = tracker.track(Arc::new(provider), legacy_tx(), hash).await;
+        drop(slot);
+        assert_eq!(outcome, TxOutcome::Confirmed { tx_hash: hash, block: 10, gas_used: Some(U256::from(180_000)) });
+        assert_eq!(tracker.pending(), 0);
+
+        let record = &log.all()[0];
+        assert_eq!(record.status, TxStatus::Confirmed);
+        assert!(record.success);
+        assert_eq!(record.gas_used, Some(180_000));
+    }
+
+    #[tokio::test]
+    async fn test_speeds_up_after_timeout() {
+        let (provider, mock) = Provider::mocked();
+        let log = Arc::new(ExecutionLog::new());
+        let tracker = TxTracker::new(TxTrackerConfig { confirmations: 1, ..config(Duration::ZERO) }, 1, log.clone());
+        let (original, replacement) = (TxHash::from_low_u64_be(0xaa), TxHash::from_low_u64_be(0xbb));
+        log.log(record(original));
+
+        mock.push::<U64, _>(U64::from(5)).unwrap();
+        mock.push(receipt(replacement, 5)).unwrap(); // replacement mined
+        mock.push::<Option<TransactionReceipt>, _>(None).unwrap(); // original still unmined
+        mock.push(replacement).unwrap(); // eth_sendTransaction for the speed-up
+        mock.push::<Option<TransactionReceipt>, _>(None).unwrap(); // original unmined, timed out
+
+        let outcome = tracker.track(Arc::new(provider), legacy_tx(), original).await;
+        assert!(matches!(outcome, TxOutcome::Confirmed { tx_hash, .. } if tx_hash == replacement));
+        let record = &log.all()[0];
+        assert_eq!(record.tx_hash, Some(format!("0x{:x}", replacement)));
+        assert_eq!(record.status, TxStatus::Confirmed);
+    }
+}
@@ -0,0 +1,340 @@
+// Pool of executor wallets so parallel opportunities don't queue behind one account's nonce.
+// Transactions are assigned to wallets by matrix, round-robin or least-pending; each wallet
+// has its own signer middleware per chain, its own nonce sequence (via the shared
+// NonceManager, keyed by address) and a tracked native balance.
+
+use crate::config::Settings;
+use crate::providers::{HttpProvider, QuorumProvider, SignerHttpProvider};
+use crate::remote_signer::{ExecutorSigner, ExecutorSignerError, RemoteSigner, RemoteSignerError};
+use ethers::prelude::*;
+use ethers::utils::parse_ether;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WalletPoolError {
+    #[error("No executor wallet with sufficient balance on chain {0}")]
+    NoWallet(u64),
+    #[error("Invalid key for {label}: {message}")]
+    InvalidKey { label: String, message: String },
+    #[error("Invalid wallet_assignment_strategy: {0}")]
+    InvalidStrategy(String),
+    #[error(transparent)]
+    Signer(#[from] ExecutorSignerError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletStrategy {
+    /// The wallet configured for the opportunity's matrix, else least-pending
+    ByMatrix,
+    RoundRobin,
+    LeastPending,
+}
+
+impl std::str::FromStr for WalletStrategy {
+    type Err = WalletPoolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "matrix" | "by_matrix" => Ok(Self::ByMatrix),
+            "round_robin" => Ok(Self::RoundRobin),
+            "least_pending" => Ok(Self::LeastPending),
+            other => Err(WalletPoolError::InvalidStrategy(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PooledWallet {
+    /// "default" for the primary signer, "matrixN" for per-matrix wallets
+    pub label: String,
+    pub matrix: Option<usize>,
+    pub signer: ExecutorSigner,
+    clients: HashMap<u64, Arc<SignerHttpProvider>>,
+    pending: AtomicUsize,
+    balances: Mutex<HashMap<u64, U256>>,
+}
+
+impl PooledWallet {
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Signer middleware for this wallet on `chain_id`
+    pub fn client(&self, chain_id: u64) -> Option<Arc<SignerHttpProvider>> {
+        self.clients.get(&chain_id).cloned()
+    }
+
+    /// Transactions assigned to this wallet and not yet released
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    pub fn balance(&self, chain_id: u64) -> Option<U256> {
+        self.balances.lock().unwrap().get(&chain_id).copied()
+    }
+
+    pub fn record_balance(&self, chain_id: u64, balance: U256) {
+        self.balances.lock().unwrap().insert(chain_id, balance);
+    }
+}
+
+/// A wallet assigned to one transaction. Dropping the lease releases the pending slot, so
+/// hold it until the transaction is confirmed or abandoned.
+#[derive(Debug)]
+pub struct WalletLease {
+    wallet: Arc<PooledWallet>,
+}
+
+impl WalletLease {
+    pub fn wallet(&self) -> &PooledWallet {
+        &self.wallet
+    }
+
+    pub fn address(&self) -> Address {
+        self.wallet.address()
+    }
+
+    pub fn client(&self, chain_id: u64) -> Option<Arc<SignerHttpProvider>> {
+        self.wallet.client(chain_id)
+    }
+}
+
+impl Drop for WalletLease {
+    fn drop(&mut self) {
+        self.wallet.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WalletReport {
+    pub label: String,
+    pub address: String,
+    pub matrix: Option<usize>,
+    pub pending: usize,
+    /// Balance in wei per chain ID, as of the last refresh
+    pub balances: HashMap<u64, String>,
+}
+
+#[derive(Debug)]
+pub struct WalletPool {
+    wallets: Vec<Arc<PooledWallet>>,
+    strategy: WalletStrategy,
+    // Wallets below this balance (wei) on a chain get no new transactions there
+    min_balance: U256,
+    next: AtomicUsize,
+}
+
+impl WalletPool {
+    pub fn new(strategy: WalletStrategy, min_balance: U256) -> Self {
+        Self { wallets: Vec::new(), strategy, min_balance, next: AtomicUsize::new(0) }
+    }
+
+    /// Add a wallet with a signer middleware on each of `providers` (chain ID, provider).
+    pub fn add_wallet(
+        &mut self,
+        label: &str,
+        matrix: Option<usize>,
+        signer: ExecutorSigner,
+        providers: &[(u64, HttpProvider)],
+    ) {
+        let clients = providers
+            .iter()
+            .map(|(chain_id, provider)| {
+                let client = SignerMiddleware::new(provider.clone(), signer.clone().with_chain_id(*chain_id));
+                (*chain_id, Arc::new(client))
+            })
+            .collect();
+        info!("[WalletPool] Added {} wallet {:?}", label, signer.address());
+        self.wallets.push(Arc::new(PooledWallet {
+            label: label.to_string(),
+            matrix,
+            signer,
+            clients,
+            pending: AtomicUsize::new(0),
+            balances: Mutex::new(HashMap::new()),
+        }));
+    }
+
+    pub fn wallets(&self) -> &[Arc<PooledWallet>] {
+        &self.wallets
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.wallets.iter().map(|w| w.address()).collect()
+    }
+
+    // Unknown balances (not refreshed yet) don't block a wallet
+    fn is_funded(&self, wallet: &PooledWallet, chain_id: u64) -> bool {
+        wallet.balance(chain_id).is_none_or(|balance| balance >= self.min_balance)
+    }
+
+    /// Assign a wallet for a transaction on `chain_id`, optionally for an opportunity of
+    /// `matrix` (1-based, as in the `matrixN_*` settings).
+    pub fn acquire(&self, chain_id: u64, matrix: Option<usize>) -> Result<WalletLease, WalletPoolError> {
+        let funded: Vec<&Arc<PooledWallet>> =
+            self.wallets.iter().filter(|w| self.is_funded(w, chain_id)).collect();
+        let least_pending = || funded.iter().min_by_key(|w| w.pending()).copied();
+        let chosen = match self.strategy {
+            WalletStrategy::ByMatrix => funded
+                .iter()
+                .find(|w| matrix.is_some() && w.matrix == matrix)
+                .copied()
+                .or_else(least_pending),
+            WalletStrategy::RoundRobin if !funded.is_empty() => {
+                Some(funded[self.next.fetch_add(1, Ordering::SeqCst) % funded.len()])
+            }
+            WalletStrategy::RoundRobin => None,
+            WalletStrategy::LeastPending => least_pending(),
+        };
+        let wallet = chosen.ok_or(WalletPoolError::NoWallet(chain_id))?.clone();
+        wallet.pending.fetch_add(1, Ordering::SeqCst);
+        Ok(WalletLease { wallet })
+    }
+
+    /// Fetch the native balance of every wallet on `chain_id`, warning about low balances.
+    /// Balances are read through `reads` (the chain's quorum provider) when given.
+    pub async fn refresh_balances(&self, chain_id: u64, reads: Option<&QuorumProvider>) {
+        for wallet in &self.wallets {
+            let Some(client) = wallet.client(chain_id) else { continue };
+            let balance = match reads {
+                Some(reads) => reads.get_balance(wallet.address(), None).await.map_err(|e| e.to_string()),
+                None => client.get_balance(wallet.address(), None).await.map_err(|e| e.to_string()),
+            };
+            match balance {
+                Ok(balance) => {
+                    if balance < self.min_balance {
+                        warn!(
+                            "[WalletPool] {} ({:?}) balance {} wei on chain {} is below the minimum; skipping it",
+                            wallet.label, wallet.address(), balance, chain_id
+                        );
+                    }
+                    wallet.record_balance(chain_id, balance);
+                }
+                Err(e) => warn!("[WalletPool] Balance check for {} on chain {} failed: {}", wallet.label, chain_id, e),
+            }
+        }
+    }
+
+    /// Refresh balances on every chain of `chains` (chain ID, quorum provider if any) every
+    /// `interval`, forever.
+    pub async fn run_balance_monitor(self: Arc<Self>, chains: Vec<(u64, Option<Arc<QuorumProvider>>)>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            for (chain_id, reads) in &chains {
+                self.refresh_balances(*chain_id, reads.as_deref()).await;
+            }
+        }
+    }
+
+    pub fn reports(&self) -> Vec<WalletReport> {
+        self.wallets
+            .iter()
+            .map(|w| WalletReport {
+                label: w.label.clone(),
+                address: format!("{:?}", w.address()),
+                matrix: w.matrix,
+                pending: w.pending(),
+                balances: w.balances.lock().unwrap().iter().map(|(c, b)| (*c, b.to_string())).collect(),
+            })
+            .collect()
+    }
+
+    /// Build the pool from `primary` plus every configured `matrixN_wallet_*` slot. A slot
+    /// with a private key gets a local wallet; one with only an address uses that key on the
+    /// remote signer.
+    pub async fn from_settings(
+        settings: &Settings,
+        primary: ExecutorSigner,
+        providers: &[(u64, HttpProvider)],
+    ) -> Result<Self, WalletPoolError> {
+        let strategy = settings.wallet_assignment_strategy.parse()?;
+        let min_balance = parse_ether(settings.wallet_min_balance).unwrap_or_default();
+        let mut pool = Self::new(strategy, min_balance);
+        pool.add_wallet("default", None, primary, providers);
+        for (matrix, private_key, address) in settings.matrix_wallets() {
+            let label = format!("matrix{}", matrix);
+            let signer = match (private_key, address) {
+                (Some(key), _) => {
+                    let key = key.expose().trim();
+                    let wallet = key.strip_prefix("0x").unwrap_or(key).parse::<LocalWallet>().map_err(|_| {
+                        WalletPoolError::InvalidKey { label: label.clone(), message: "not a 32-byte hex key".to_string() }
+                    })?;
+                    ExecutorSigner::Local(wallet)
+                }
+                (None, Some(address)) => {
+                    let Some(url) = settings.remote_signer_url.as_deref().filter(|u| !u.trim().is_empty()) else {
+                        warn!("[WalletPool] {} has an address but no key and no remote signer; skipping", label);
+                        continue;
+                    };
+                    let address = address
+                        .parse::<Address>()
+                        .map_err(|e| ExecutorSignerError::from(RemoteSignerError::InvalidAddress(e.to_string())))?;
+                    let timeout = Duration::from_millis(settings.remote_signer_timeout_ms);
+                    let signer = RemoteSigner::connect(url, Some(address), timeout)
+                        .await
+                        .map_err(ExecutorSignerError::from)?;
+                    ExecutorSigner::Remote(signer)
+                }
+                (None, None) => continue,
+            };
+            if pool.addresses().contains(&signer.address()) {
+                // The same account twice would double-count its pending transactions
+                warn!("[WalletPool] {} reuses wallet {:?}; not adding it twice", label, signer.address());
+                continue;
+            }
+            pool.add_wallet(&label, Some(matrix), signer, providers);
+        }
+        Ok(pool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(strategy: WalletStrategy) -> WalletPool {
+        let mut pool = WalletPool::new(strategy, U256::from(100));
+        for (i, matrix) in [None, Some(1), Some(2)].into_iter().enumerate() {
+            let wallet = LocalWallet::from_bytes(&[i as u8 + 1; 32]).unwrap();
+            pool.add_wallet(&format!("w{}", i), matrix, ExecutorSigner::Local(wallet), &[]);
+        }
+        pool
+    }
+
+    #[test]
+    fn test_round_robin_and_least_pending() {
+        let pool = pool(WalletStrategy::RoundRobin);
+        let labels: Vec<String> = (0..4).map(|_| pool.acquire(56, None).unwrap().wallet().label.clone()).collect();
+        assert_eq!(labels, vec!["w0", "w1", "w2", "w0"]);
+
+        let pool = self::pool(WalletStrategy::LeastPending);
+        let first = pool.acquire(56, None).unwrap();
+        let second = pool.acquire(56, None).unwrap();
+        assert_ne!(first.address(), second.address());
+        assert_eq!(pool.wallets().iter().map(|w| w.pending()).sum::<usize>(), 2);
+        drop(first);
+        drop(second);
+        assert_eq!(pool.wallets().iter().map(|w| w.pending()).sum::<usize>(), 0);
+    }
+
+    #[test]
+    fn test_by_matrix_skips_underfunded_wallets() {
+        let pool = pool(WalletStrategy::ByMatrix);
+        assert_eq!(pool.acquire(56, Some(2)).unwrap().wallet().label, "w2");
+        // matrix 2's wallet is out of gas on BSC: fall back to another wallet
+        pool.wallets()[2].record_balance(56, U256::from(5));
+        assert_ne!(pool.acquire(56, Some(2)).unwrap().wallet().label, "w2");
+        assert_eq!(pool.acquire(1, Some(2)).unwrap().wallet().label, "w2");
+        for wallet in pool.wallets() {
+            wallet.record_balance(56, U256::zero());
+        }
+        assert!(matches!(pool.acquire(56, None), Err(WalletPoolError::NoWallet(56))));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::chain_registry::ChainRegistry;
 use crate::config::Settings;
 use ethers::providers::{Provider, Ws, PubsubClient, StreamExt};
 use crate::websockets_round_robin::{DexWebSocketEntry, DexWebSocketRotation};
@@ -100,18 +101,14 @@ impl WebSocketManager {
     pub async fn connect_all(&mut self) {
         let mut futures = FuturesUnordered::new();
 
-        // --- BSC Primary WebSocket ---
-        if let Some(url) = self.get_chain_ws_url(Chain::BscMainnet) {
-            futures.push(Self::connect_and_listen("BSC_Primary".to_string(), url));
-        } else {
-             log::warn!("Warning: Could not determine primary WebSocket URL for BSC Mainnet.");
-        }
-
-        // --- ETH Primary WebSocket ---
-         if let Some(url) = self.get_chain_ws_url(Chain::EthMainnet) {
-            futures.push(Self::connect_and_listen("ETH_Primary".to_string(), url));
-        } else {
-             log::warn!("Warning: Could not determine primary WebSocket URL for ETH Mainnet.");
+        // --- Primary chain WebSockets (every enabled chain in the registry) ---
+        let registry = ChainRegistry::from_settings(&self.settings);
+        for chain in registry.enabled() {
+            if let Some(url) = self.get_chain_ws_url(&registry, chain.chain_id) {
+                futures.push(Self::connect_and_listen(format!("{}_Primary", chain.name), url));
+            } else {
+                log::warn!("Warning: Could not determine primary WebSocket URL for {} (Chain ID: {}).", chain.name, chain.chain_id);
+            }
         }
 
         // --- DEX Specific WebSockets ---
@@ -177,39 +174,20 @@ impl WebSocketManager {
 
 
     // Helper to get the prioritized WebSocket URL for a chain
-    fn get_chain_ws_url(&self, chain: Chain) -> Option<String> {
-        let get_urls: fn(&Settings, &str) -> Option<String> = match chain {
-            Chain::BscMainnet => |s, p| match p {
-                "Infura" => Some(s.infura_bsc_websocket_url.clone()),
-                "Alchemy" => Some(s.alchemy_bsc_websocket_url.clone()),
-                "NodeReal" => Some(s.nodereal_bsc_websocket_url.clone()),
-                _ => None,
-            },
-            Chain::EthMainnet => |s, p| match p {
-                "Infura" => Some(s.infura_eth_websocket_url.clone()),
-                "Alchemy" => Some(s.alchemy_eth_websocket_url.clone()),
-                "NodeReal" => Some(s.nodereal_eth_websocket_url.clone()),
-                _ => None,
-            },
-            // Add other chains if needed
-        };
-
-        log::warn!("Searching for WebSocket URL for {:?}...", chain);
-        for provider_name in &self.settings.provider_priority_order {
-            if let Some(url) = get_urls(&self.settings, provider_name) {
-                // Basic check: is the URL non-empty?
-                if !url.is_empty() {
-                     log::warn!("   Found URL via {}: {}", provider_name, url);
-                    return Some(url);
-                } else {
-                     log::warn!("   Provider {} has empty URL for {:?}, skipping.", provider_name, chain);
-                }
-            } else {
-                 // This case should ideally not happen if priority list matches available settings
-                 log::warn!("   Provider {} not configured for {:?} WebSocket.", provider_name, chain);
+    fn get_chain_ws_url(&self, registry: &ChainRegistry, chain_id: u64) -> Option<String> {
+        let chain = registry.get(chain_id)?;
+        log::warn!("Searching for WebSocket URL for {}...", chain.name);
+        let endpoints = chain.ws_endpoints(&self.settings.provider_priority_order);
+        match endpoints.into_iter().next() {
+            Some((provider_name, url)) => {
+                log::warn!("   Found URL via {}", provider_name);
+                Some(url)
+            }
+            None => {
+                log::warn!("   No provider has a WebSocket URL configured for {}.", chain.name);
+                None
             }
         }
-        None // No suitable URL found
     }
 
     // Connects to a single WebSocket URL and spawns a listener task
@@ -299,10 +277,3 @@ pub async fn shutdown(&mut self) {
          log::warn!("{} WebSocket listeners shut down.", count);
     }
 }
-
-// Re-use Chain enum, maybe move to a shared `common` module later?
-#[derive(Debug, Clone, Copy)]
-enum Chain {
-    BscMainnet,
-    EthMainnet,
-}
@@ -31,3 +31,101 @@ async fn test_api_matrix2d_endpoint() {
         Err(e) => panic!("Failed to parse JSON: {:?} (raw: {:?})", e, raw),
     }
 }
+
+#[actix_web::test]
+async fn test_api_kill_switch_requires_admin_token() {
+    use fusion::api::{get_risk_status, post_kill_switch};
+    use fusion::risk_manager::{RiskLimits, RiskManager};
+    use fusion::secrets::SecretString;
+    use std::time::Duration;
+
+    let limits = RiskLimits {
+        enabled: true,
+        max_daily_loss_usd: 500.0,
+        max_rolling_loss_usd: 200.0,
+        rolling_window: Duration::from_secs(3600),
+        max_consecutive_failures: 5,
+        max_trade_notional_usd: 50_000.0,
+        max_token_notional_usd: 250_000.0,
+        revert_cooldown: Duration::from_secs(60),
+    };
+    let risk = Arc::new(RiskManager::new(limits, Some(SecretString::new("admin-token"))));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(risk.clone()))
+            .service(get_risk_status)
+            .service(post_kill_switch)
+    ).await;
+
+    let engage = serde_json::json!({"action": "engage", "reason": "manual halt"});
+    let req = test::TestRequest::post().uri("/api/risk/kill_switch").set_json(&engage).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+    let req = test::TestRequest::post()
+        .uri("/api/risk/kill_switch")
+        .insert_header(("Authorization", "Bearer wrong-token"))
+        .set_json(&engage)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 401);
+    assert!(!risk.is_halted());
+
+    let req = test::TestRequest::post()
+        .uri("/api/risk/kill_switch")
+        .insert_header(("Authorization", "Bearer admin-token"))
+        .set_json(&engage)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::get().uri("/api/risk").to_request();
+    let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(status["halted"], true);
+    assert_eq!(status["halt_reason"], "kill switch: manual halt");
+
+    let req = test::TestRequest::post()
+        .uri("/api/risk/kill_switch")
+        .insert_header(("Authorization", "Bearer admin-token"))
+        .set_json(serde_json::json!({"action": "reset"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    assert!(!risk.is_halted());
+}
+
+#[actix_web::test]
+async fn test_api_profit_endpoint_returns_total_pnl() {
+    use ethers::types::Address;
+    use fusion::api::{get_ledger, get_profit};
+    use fusion::chain_registry::{ChainConfig, ChainRegistry};
+    use fusion::ledger::{Ledger, NATIVE_TOKEN};
+    use fusion::token_amount::TokenAmount;
+
+    let mut matrix = Matrix2D::new(vec!["PancakeSwap".to_string()], vec!["WBNB".to_string()]);
+    matrix.update_price("PancakeSwap", "WBNB", 650.0);
+    let bsc = ChainConfig {
+        chain_id: 56,
+        native_token: "BNB".to_string(),
+        wrapped_native_token: "WBNB".to_string(),
+        ..Default::default()
+    };
+    let ledger = Arc::new(
+        Ledger::new(60_000)
+            .with_matrix(Arc::new(Mutex::new(matrix)))
+            .with_chains(Arc::new(ChainRegistry::new(vec![bsc]))),
+    );
+    let wallet = Address::from_low_u64_be(10);
+    ledger.open_position(56, wallet, NATIVE_TOKEN, "BNB", TokenAmount::parse("2", 18).unwrap(), Some(600.0));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(ledger.clone()))
+            .service(get_profit)
+            .service(get_ledger)
+    ).await;
+    // The frontend's fetch_profit reads a bare f64
+    let req = test::TestRequest::get().uri("/api/profit").to_request();
+    let profit: f64 = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(profit, 100.0);
+
+    let req = test::TestRequest::get().uri("/api/ledger").to_request();
+    let report: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["realized_pnl_usd"], 0.0);
+    assert_eq!(report["positions"][0]["symbol"], "BNB");
+    assert_eq!(report["positions"][0]["mark_price_usd"], 650.0);
+}
@@ -16,3 +16,33 @@ fn test_arbitrage_safety_check() {
     assert!(actual_profit < expected_profit);
     assert!(actual_profit < 0.0); // safety: never execute if loss
 }
+
+#[test]
+fn test_net_profit_accounts_for_gas() {
+    use ethers::types::U256;
+    use fusion::analysis::net_profit_usd;
+    use fusion::gas_oracle::FeeSuggestion;
+    // 5 gwei * 400k gas = 0.002 BNB = $1.20 at $600
+    let fees = FeeSuggestion::Legacy { gas_price: U256::from(5_000_000_000u64) };
+    let net = net_profit_usd(0.5, 1000.0, &fees, 400_000, 600.0);
+    assert!((net - 3.8).abs() < 1e-9);
+    // A thin spread on a small trade doesn't pay for its gas
+    assert!(net_profit_usd(0.1, 500.0, &fees, 400_000, 600.0) < 0.0);
+}
+
+#[tokio::test]
+async fn test_unplannable_opportunities_are_dropped() {
+    use ethers::providers::Provider;
+    use fusion::analysis::AnalysisHub;
+    use fusion::chain_registry::ChainConfig;
+    use fusion::config::Settings;
+    use fusion::execution_planner::ExecutionPlanner;
+    use fusion::token_registry::TokenRegistry;
+    use std::sync::Arc;
+    // No quote token or routers configured: nothing can be planned
+    let chain = ChainConfig { chain_id: 56, ..Default::default() };
+    let planner = ExecutionPlanner::new(Arc::new(Settings::default()), Arc::new(TokenRegistry::new()), &chain);
+    let opp = ("PancakeSwap".to_string(), "CAKE".to_string(), "Biswap".to_string(), 2.0, "CAKE".to_string(), 2.02, 1.0, 0);
+    let (provider, _mock) = Provider::mocked();
+    assert!(AnalysisHub::plan_opportunities(&[opp], &planner, Arc::new(provider), 10.0).await.is_empty());
+}
@@ -0,0 +1,120 @@
+// Tests for private bundle submission against a local mock relay
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::{hex, keccak256};
+use fusion::bundles::{sign_raw, BundleConfig, BundleOutcome, BundleSubmitter, SIGNATURE_HEADER};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Default)]
+struct MockRelay {
+    // (bundle params, address recovered from the signature header)
+    received: Mutex<Vec<(Value, Address)>>,
+}
+
+async fn relay_handler(req: HttpRequest, body: web::Bytes, relay: web::Data<Arc<MockRelay>>) -> HttpResponse {
+    let header = req.headers().get(SIGNATURE_HEADER).and_then(|h| h.to_str().ok()).unwrap_or_default();
+    let Some((address, signature)) = header.split_once(':') else {
+        return HttpResponse::Unauthorized().finish();
+    };
+    let digest = format!("0x{}", hex::encode(keccak256(&body)));
+    let recovered = Signature::from_str(signature).unwrap().recover(digest).unwrap();
+    if recovered != address.parse::<Address>().unwrap() {
+        return HttpResponse::Unauthorized().finish();
+    }
+    let request: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(request["method"], "eth_sendBundle");
+    relay.received.lock().unwrap().push((request["params"][0].clone(), recovered));
+    HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": request["id"], "result": {"bundleHash": H256::repeat_byte(0xbb)}}))
+}
+
+// Start a mock relay and return its URL
+fn spawn_relay(relay: Arc<MockRelay>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = HttpServer::new(move || {
+        App::new().app_data(web::Data::new(relay.clone())).default_service(web::post().to(relay_handler))
+    })
+    .workers(1)
+    .listen(listener)
+    .expect("Failed to listen")
+    .run();
+    actix_rt::spawn(server);
+    url
+}
+
+fn wallet(byte: u8) -> LocalWallet {
+    LocalWallet::from_bytes(&[byte; 32]).unwrap()
+}
+
+fn submitter(url: String, target_blocks: u64) -> BundleSubmitter {
+    let config = BundleConfig {
+        relays: vec![url],
+        target_blocks,
+        relay_timeout: Duration::from_secs(5),
+        poll_interval: Duration::ZERO,
+    };
+    BundleSubmitter::new(config, wallet(3)).unwrap()
+}
+
+fn executor_tx() -> TypedTransaction {
+    TransactionRequest::new()
+        .to(Address::from_low_u64_be(0x99))
+        .data(vec![0xde, 0xad])
+        .gas(400_000)
+        .gas_price(3_000_000_000u64)
+        .nonce(12)
+        .into()
+}
+
+#[actix_rt::test]
+async fn test_signed_bundle_included_after_backrun_target() {
+    let relay = Arc::new(MockRelay::default());
+    let bundles = submitter(spawn_relay(relay.clone()), 3);
+    let (provider, mock) = Provider::mocked();
+    let client = SignerMiddleware::new(provider, wallet(7).with_chain_id(56u64));
+
+    let mut tx = executor_tx();
+    let (raw_tx, tx_hash) = sign_raw(&client, &mut tx).await.unwrap();
+    assert_eq!(tx.from(), Some(&wallet(7).address()));
+
+    // Responses are popped LIFO: head 100, then block 101 mined with our transaction
+    let receipt = TransactionReceipt { transaction_hash: tx_hash, block_number: Some(101u64.into()), ..Default::default() };
+    mock.push(receipt).unwrap();
+    mock.push::<U64, _>(U64::from(101)).unwrap();
+    mock.push::<U64, _>(U64::from(100)).unwrap();
+
+    let target = Bytes::from(vec![0x02, 0x01]);
+    let outcome = bundles.submit(&client, raw_tx.clone(), tx_hash, Some(target.clone())).await.unwrap();
+    assert_eq!(outcome, BundleOutcome::Included { block: 101, tx_hash });
+
+    let received = relay.received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let (params, signer) = &received[0];
+    assert_eq!(*signer, bundles.auth_address());
+    assert_eq!(params["blockNumber"], "0x65");
+    assert_eq!(params["txs"], json!([target, raw_tx]));
+}
+
+#[actix_rt::test]
+async fn test_bundle_retargeted_until_given_up() {
+    let relay = Arc::new(MockRelay::default());
+    let bundles = submitter(spawn_relay(relay.clone()), 2);
+    let (provider, mock) = Provider::mocked();
+    let tx_hash = TxHash::repeat_byte(0x11);
+
+    mock.push::<Option<TransactionReceipt>, _>(None).unwrap();
+    mock.push::<U64, _>(U64::from(102)).unwrap();
+    mock.push::<Option<TransactionReceipt>, _>(None).unwrap();
+    mock.push::<U64, _>(U64::from(101)).unwrap();
+    mock.push::<U64, _>(U64::from(100)).unwrap();
+
+    let outcome = bundles.submit(&provider, Bytes::from(vec![0x01]), tx_hash, None).await.unwrap();
+    assert_eq!(outcome, BundleOutcome::NotIncluded { last_block: 102 });
+    let blocks: Vec<Value> = relay.received.lock().unwrap().iter().map(|(p, _)| p["blockNumber"].clone()).collect();
+    assert_eq!(blocks, vec![json!("0x65"), json!("0x66")]);
+}
@@ -0,0 +1,46 @@
+// Tests that the [[chains]] tables in config/default.toml load into the chain registry
+use config::{Config, File};
+use fusion::chain_registry::{ChainConfig, ChainRegistry, GasModel};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct ChainsOnly {
+    chains: Vec<ChainConfig>,
+}
+
+fn default_registry() -> ChainRegistry {
+    let chains: ChainsOnly = Config::builder()
+        .add_source(File::with_name("config/default.toml"))
+        .build()
+        .expect("Failed to build config")
+        .try_deserialize()
+        .expect("Failed to deserialize chains");
+    ChainRegistry::new(chains.chains)
+}
+
+#[test]
+fn test_default_chains_load() {
+    let registry = default_registry();
+    let bsc = registry.resolve("bsc").expect("BSC configured");
+    assert_eq!(bsc.chain_id, 56);
+    assert_eq!(bsc.gas_model, GasModel::Legacy);
+    assert_eq!(registry.get(1).unwrap().gas_model, GasModel::Eip1559);
+
+    let priority = vec!["Infura".to_string(), "Alchemy".to_string(), "NodeReal".to_string()];
+    let names: Vec<String> = bsc.rpc_endpoints(&priority).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, priority);
+
+    // Testnets are configured but disabled by default
+    let sepolia = registry.resolve("Sepolia").expect("Sepolia configured");
+    assert!(sepolia.testnet && !sepolia.enabled);
+    let enabled: Vec<u64> = registry.enabled().iter().map(|c| c.chain_id).collect();
+    assert_eq!(enabled, vec![1, 56]);
+
+    // Contracts are per chain: nothing is deployed on the testnets
+    assert!(bsc.executor_address().is_some());
+    assert!(sepolia.executor_address().is_none());
+    let eth = registry.get(1).unwrap();
+    assert!(eth.routers.contains_key("Uniswap"));
+    assert!(eth.flashloan.aave_pool_address_provider.is_some());
+    assert!(bsc.lending.venus().is_some() && eth.lending.compound().is_some());
+}
@@ -0,0 +1,128 @@
+// Tests for the failover JSON-RPC transport against local HTTP stand-ins
+use actix_web::{web, App, HttpResponse, HttpServer};
+use ethers::providers::{Middleware, Provider};
+use fusion::failover_transport::FailoverHttp;
+use fusion::providers_round_robin::{ProviderEntry, ProviderRotation, SharedRotation};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+enum StandIn {
+    Healthy,
+    RateLimited,
+    Reverting,
+}
+
+struct StandInState {
+    mode: StandIn,
+    hits: Arc<AtomicUsize>,
+}
+
+async fn rpc_handler(body: web::Json<Value>, state: web::Data<StandInState>) -> HttpResponse {
+    state.hits.fetch_add(1, Ordering::SeqCst);
+    let id = body.get("id").cloned().unwrap_or(json!(1));
+    match state.mode {
+        StandIn::Healthy => HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": id, "result": "0x38"})),
+        StandIn::RateLimited => HttpResponse::TooManyRequests().body("Too Many Requests"),
+        StandIn::Reverting => HttpResponse::Ok().json(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": 3, "message": "execution reverted"}
+        })),
+    }
+}
+
+// Start a JSON-RPC stand-in and return its URL and hit counter
+fn spawn_stand_in(mode: StandIn) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits_for_server = hits.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(StandInState { mode, hits: hits_for_server.clone() }))
+            .default_service(web::post().to(rpc_handler))
+    })
+    .workers(1)
+    .listen(listener)
+    .expect("Failed to listen")
+    .run();
+    actix_rt::spawn(server);
+    (url, hits)
+}
+
+fn entry(name: &str, url: &str) -> ProviderEntry {
+    ProviderEntry {
+        name: name.to_string(),
+        url: url.to_string(),
+        max_requests_per_minute: 100,
+        monthly_limit: None,
+        hourly_limit: None,
+        daily_limit: None,
+        requests_this_window: 0,
+        window_start: None,
+        requests_this_hour: 0,
+        hour_start: None,
+        requests_today: 0,
+        day_start: None,
+        last_used: None,
+        cooldown_until: None,
+        requests_this_month: 0,
+        month_start: None,
+        billing_reset_day: 1,
+        quota_warn_percentage: 80.0,
+        quota_hard_stop_percentage: 100.0,
+        quota_warned: false,
+        quota_exhausted_logged: false,
+    }
+}
+
+fn rotation(entries: Vec<ProviderEntry>) -> SharedRotation {
+    Arc::new(Mutex::new(ProviderRotation::new(entries, Duration::from_secs(60))))
+}
+
+#[actix_web::test]
+async fn test_failover_skips_rate_limited_provider() {
+    let (limited_url, limited_hits) = spawn_stand_in(StandIn::RateLimited);
+    let (healthy_url, healthy_hits) = spawn_stand_in(StandIn::Healthy);
+    let rotation = rotation(vec![entry("Limited", &limited_url), entry("Healthy", &healthy_url)]);
+    let provider = Provider::new(FailoverHttp::new(rotation.clone(), Duration::from_secs(60)).unwrap());
+
+    let chain_id = provider.get_chainid().await.unwrap();
+    assert_eq!(chain_id.as_u64(), 56);
+    assert_eq!(limited_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(healthy_hits.load(Ordering::SeqCst), 1);
+
+    // The rate-limited endpoint is cooling down, so later requests go straight to the healthy one
+    let limited = rotation.lock().unwrap().entries().find(|e| e.name == "Limited").cloned().unwrap();
+    assert!(!limited.is_available());
+    provider.get_chainid().await.unwrap();
+    assert_eq!(limited_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(healthy_hits.load(Ordering::SeqCst), 2);
+}
+
+#[actix_web::test]
+async fn test_failover_returns_rpc_errors_without_retry() {
+    let (reverting_url, reverting_hits) = spawn_stand_in(StandIn::Reverting);
+    let (healthy_url, healthy_hits) = spawn_stand_in(StandIn::Healthy);
+    let rotation = rotation(vec![entry("Reverting", &reverting_url), entry("Healthy", &healthy_url)]);
+    let provider = Provider::new(FailoverHttp::new(rotation.clone(), Duration::from_secs(60)).unwrap());
+
+    assert!(provider.get_chainid().await.is_err());
+    assert_eq!(reverting_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(healthy_hits.load(Ordering::SeqCst), 0);
+    assert!(rotation.lock().unwrap().entries().all(|e| e.is_available()));
+}
+
+#[actix_web::test]
+async fn test_failover_exhausted_when_all_providers_fail() {
+    let (a_url, _) = spawn_stand_in(StandIn::RateLimited);
+    let (b_url, _) = spawn_stand_in(StandIn::RateLimited);
+    let rotation = rotation(vec![entry("A", &a_url), entry("B", &b_url)]);
+    let provider = Provider::new(FailoverHttp::new(rotation, Duration::from_secs(60)).unwrap());
+    let err = provider.get_chainid().await.unwrap_err();
+    assert!(err.to_string().contains("All RPC providers failed"), "{}", err);
+}
@@ -17,6 +17,9 @@ impl MockProtocolHelper {
             account: "0xliquidate".to_string(),
             debt: 1000.0,
             collateral: 1200.0,
+            debt_token: None,
+            collateral_token: None,
+            observed_at_ms: fusion::time_sync::now_millis(),
         };
         self.sender.send(event).await.unwrap();
     }
@@ -0,0 +1,132 @@
+// Tests for the quorum JSON-RPC transport against local HTTP stand-ins
+use actix_web::{web, App, HttpResponse, HttpServer};
+use ethers::providers::{Middleware, Provider};
+use fusion::providers_round_robin::{ProviderEntry, ProviderRotation};
+use fusion::quorum::{QuorumHttp, QuorumMetrics};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct StandInState {
+    result: Value,
+    delay: Duration,
+}
+
+async fn rpc_handler(body: web::Json<Value>, state: web::Data<StandInState>) -> HttpResponse {
+    tokio::time::sleep(state.delay).await;
+    let id = body.get("id").cloned().unwrap_or(json!(1));
+    HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": id, "result": state.result}))
+}
+
+// Start a JSON-RPC stand-in answering every request with `result` after `delay`
+fn spawn_stand_in(result: Value, delay: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(StandInState { result: result.clone(), delay }))
+            .default_service(web::post().to(rpc_handler))
+    })
+    .workers(1)
+    .listen(listener)
+    .expect("Failed to listen")
+    .run();
+    actix_rt::spawn(server);
+    url
+}
+
+fn quorum_provider(endpoints: &[(&str, String)], threshold: usize) -> (Provider<QuorumHttp>, Arc<QuorumMetrics>) {
+    let metrics = Arc::new(QuorumMetrics::new());
+    let endpoints: Vec<(String, String)> =
+        endpoints.iter().map(|(name, url)| (name.to_string(), url.clone())).collect();
+    let transport = QuorumHttp::new(&endpoints, threshold, Duration::from_millis(500), metrics.clone()).unwrap();
+    (Provider::new(transport), metrics)
+}
+
+#[actix_web::test]
+async fn test_quorum_reports_laggard() {
+    let a = spawn_stand_in(json!("0x64"), Duration::ZERO);
+    let b = spawn_stand_in(json!("0x64"), Duration::ZERO);
+    let lagging = spawn_stand_in(json!("0x60"), Duration::ZERO);
+    let (provider, metrics) = quorum_provider(&[("A", a), ("B", b), ("Lagging", lagging)], 2);
+
+    assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 100);
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.requests, 1);
+    assert_eq!(snapshot.disagreements, 1);
+    assert_eq!(snapshot.quorum_failures, 0);
+    assert_eq!(snapshot.providers["A"].agreed, 1);
+    assert_eq!(snapshot.providers["Lagging"].lagging, 1);
+    assert_eq!(snapshot.providers["Lagging"].dissented, 0);
+}
+
+#[actix_web::test]
+async fn test_quorum_fails_without_agreement() {
+    let a = spawn_stand_in(json!("0x1"), Duration::ZERO);
+    let b = spawn_stand_in(json!("0x2"), Duration::ZERO);
+    let (provider, metrics) = quorum_provider(&[("A", a), ("B", b)], 2);
+
+    let err = provider.get_block_number().await.unwrap_err();
+    assert!(err.to_string().contains("No quorum"), "{}", err);
+    assert_eq!(metrics.snapshot().quorum_failures, 1);
+}
+
+#[actix_web::test]
+async fn test_quorum_tolerates_slow_provider() {
+    let a = spawn_stand_in(json!("0xabc"), Duration::ZERO);
+    let b = spawn_stand_in(json!("0xabc"), Duration::ZERO);
+    let slow = spawn_stand_in(json!("0xabc"), Duration::from_secs(5));
+    let (provider, metrics) = quorum_provider(&[("A", a), ("B", b), ("Slow", slow)], 2);
+
+    assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 0xabc);
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.disagreements, 0);
+    assert_eq!(snapshot.providers["Slow"].lagging, 1);
+}
+
+#[actix_web::test]
+async fn test_quorum_requests_count_against_rotation_quota() {
+    let a = spawn_stand_in(json!("0x64"), Duration::ZERO);
+    let b = spawn_stand_in(json!("0x64"), Duration::ZERO);
+    let entry = |name: &str, url: &str| ProviderEntry {
+        name: name.to_string(),
+        url: url.to_string(),
+        max_requests_per_minute: u32::MAX,
+        monthly_limit: None,
+        hourly_limit: None,
+        daily_limit: None,
+        requests_this_window: 0,
+        window_start: None,
+        requests_this_hour: 0,
+        hour_start: None,
+        requests_today: 0,
+        day_start: None,
+        last_used: None,
+        cooldown_until: None,
+        requests_this_month: 0,
+        month_start: None,
+        billing_reset_day: 1,
+        quota_warn_percentage: 80.0,
+        quota_hard_stop_percentage: 100.0,
+        quota_warned: false,
+        quota_exhausted_logged: false,
+    };
+    let rotation = Arc::new(Mutex::new(ProviderRotation::new(
+        vec![entry("A", &a), entry("B", &b)],
+        Duration::from_secs(60),
+    )));
+    let endpoints = vec![("A".to_string(), a), ("B".to_string(), b)];
+    let transport = QuorumHttp::new(&endpoints, 2, Duration::from_millis(500), Arc::new(QuorumMetrics::new()))
+        .unwrap()
+        .with_rotation(rotation.clone());
+    let provider = Provider::new(transport);
+
+    provider.get_block_number().await.unwrap();
+    provider.get_block_number().await.unwrap();
+    let rotation = rotation.lock().unwrap();
+    for entry in rotation.entries() {
+        assert_eq!(entry.requests_this_month, 2, "{}", entry.name);
+        assert_eq!(entry.requests_today, 2, "{}", entry.name);
+    }
+}
@@ -0,0 +1,55 @@
+// Tests for persisting provider quota counters across restarts
+use fusion::providers_round_robin::{ProviderEntry, ProviderRotation};
+use fusion::quota_store::QuotaStore;
+use std::time::Duration;
+
+fn entry(name: &str) -> ProviderEntry {
+    ProviderEntry {
+        name: name.to_string(),
+        url: format!("http://{}", name),
+        max_requests_per_minute: 100,
+        monthly_limit: Some(1_000),
+        hourly_limit: None,
+        daily_limit: None,
+        requests_this_window: 0,
+        window_start: None,
+        requests_this_hour: 0,
+        hour_start: None,
+        requests_today: 0,
+        day_start: None,
+        last_used: None,
+        cooldown_until: None,
+        requests_this_month: 0,
+        month_start: None,
+        billing_reset_day: 1,
+        quota_warn_percentage: 80.0,
+        quota_hard_stop_percentage: 100.0,
+        quota_warned: false,
+        quota_exhausted_logged: false,
+    }
+}
+
+#[test]
+fn test_quota_counters_survive_restart() {
+    let path = std::env::temp_dir().join(format!("fusion-quota-{}/quota.json", uuid::Uuid::new_v4()));
+
+    let mut rotation = ProviderRotation::new(vec![entry("Infura"), entry("Alchemy")], Duration::from_secs(60));
+    for _ in 0..3 {
+        rotation.next_best_provider(|e| if e.name == "Infura" { 0.0 } else { 1.0 });
+    }
+    let store = QuotaStore::open(&path).unwrap();
+    store.update("56", &rotation);
+    store.flush().unwrap();
+
+    // Fresh process: same providers, counters start at zero until restored
+    let mut restarted = ProviderRotation::new(vec![entry("Infura"), entry("Alchemy")], Duration::from_secs(60));
+    let reopened = QuotaStore::open(&path).unwrap();
+    reopened.restore("56", &mut restarted);
+    let infura = restarted.entries().find(|e| e.name == "Infura").unwrap();
+    assert_eq!(infura.requests_this_month, 3);
+    assert_eq!(infura.requests_today, 3);
+    assert!(infura.month_start.is_some());
+    assert!(reopened.record("1", "Infura").is_none());
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
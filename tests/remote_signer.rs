@@ -0,0 +1,122 @@
+// Tests for the remote signer against a local Web3Signer stand-in
+use actix_web::{web, App, HttpResponse, HttpServer};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Eip1559TransactionRequest, TransactionRequest, U256};
+use fusion::remote_signer::{RemoteSigner, RemoteSignerError};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::time::Duration;
+
+struct StandInState {
+    // Key reported by eth_accounts
+    reported: LocalWallet,
+    // Key actually used for signing (differs from `reported` for a misbehaving signer)
+    signing: LocalWallet,
+}
+
+async fn rpc_handler(body: web::Json<Value>, state: web::Data<StandInState>) -> HttpResponse {
+    let id = body.get("id").cloned().unwrap_or(json!(1));
+    let params = body.get("params").cloned().unwrap_or(Value::Null);
+    let result = match body.get("method").and_then(Value::as_str) {
+        Some("eth_accounts") => json!([state.reported.address()]),
+        Some("eth_sign") => {
+            let data = params[1].as_str().unwrap().trim_start_matches("0x");
+            let message = ethers::utils::hex::decode(data).unwrap();
+            let signature = state.signing.sign_message(message).await.unwrap();
+            json!(format!("0x{}", signature))
+        }
+        Some("eth_signTransaction") => {
+            let tx: TypedTransaction = serde_json::from_value(params[0].clone()).unwrap();
+            let signature = state.signing.sign_transaction_sync(&tx).unwrap();
+            json!(tx.rlp_signed(&signature))
+        }
+        _ => {
+            return HttpResponse::Ok().json(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": -32601, "message": "method not found"}
+            }))
+        }
+    };
+    HttpResponse::Ok().json(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+}
+
+// Start a Web3Signer stand-in and return its URL
+fn spawn_signer(reported: LocalWallet, signing: LocalWallet) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(StandInState { reported: reported.clone(), signing: signing.clone() }))
+            .default_service(web::post().to(rpc_handler))
+    })
+    .workers(1)
+    .listen(listener)
+    .expect("Failed to listen")
+    .run();
+    actix_rt::spawn(server);
+    url
+}
+
+fn wallet(byte: u8) -> LocalWallet {
+    LocalWallet::from_bytes(&[byte; 32]).unwrap()
+}
+
+#[actix_rt::test]
+async fn test_remote_signatures_match_local_key() {
+    let key = wallet(7);
+    let url = spawn_signer(key.clone(), key.clone());
+    let signer = RemoteSigner::connect(&url, None, Duration::from_secs(5)).await.unwrap().with_chain_id(56u64);
+    assert_eq!(signer.address(), key.address());
+
+    let legacy: TypedTransaction = TransactionRequest::new()
+        .to(Address::from_low_u64_be(1))
+        .value(1000)
+        .gas(21000)
+        .gas_price(3_000_000_000u64)
+        .nonce(4)
+        .chain_id(56u64)
+        .into();
+    let signature = signer.sign_transaction(&legacy).await.unwrap();
+    let mut expected_tx = legacy.clone();
+    expected_tx.set_from(key.address());
+    assert_eq!(signature, key.clone().with_chain_id(56u64).sign_transaction_sync(&expected_tx).unwrap());
+
+    let eip1559: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(Address::from_low_u64_be(1))
+        .gas(21000)
+        .max_fee_per_gas(U256::from(30_000_000_000u64))
+        .max_priority_fee_per_gas(U256::from(1_000_000_000u64))
+        .nonce(5)
+        .into();
+    let signature = signer.sign_transaction(&eip1559).await.unwrap();
+    let mut signed_tx = eip1559.clone();
+    signed_tx.set_from(key.address());
+    signed_tx.set_chain_id(56u64);
+    assert_eq!(signature.recover(signed_tx.sighash()).unwrap(), key.address());
+
+    let signature = signer.sign_message("fusion").await.unwrap();
+    assert_eq!(signature.recover("fusion").unwrap(), key.address());
+}
+
+#[actix_rt::test]
+async fn test_rejects_unknown_account_and_wrong_signer() {
+    let url = spawn_signer(wallet(7), wallet(8));
+    let missing = wallet(9).address();
+    match RemoteSigner::connect(&url, Some(missing), Duration::from_secs(5)).await {
+        Err(RemoteSignerError::UnknownAccount(address)) => assert_eq!(address, missing),
+        other => panic!("expected UnknownAccount, got {:?}", other),
+    }
+
+    // The stand-in reports key 7 but signs with key 8
+    let signer = RemoteSigner::connect(&url, None, Duration::from_secs(5)).await.unwrap();
+    let tx: TypedTransaction = TransactionRequest::new().to(Address::zero()).gas(21000).gas_price(1).nonce(0).into();
+    match signer.sign_transaction(&tx).await {
+        Err(RemoteSignerError::WrongSigner { expected, recovered }) => {
+            assert_eq!(expected, wallet(7).address());
+            assert_eq!(recovered, wallet(8).address());
+        }
+        other => panic!("expected WrongSigner, got {:?}", other),
+    }
+}
@@ -0,0 +1,81 @@
+// Tests for SNTP clock offset measurement and timestamp validation against a local UDP stand-in
+use fusion::config::Settings;
+use fusion::time_sync::{query_sntp_offset, TimeSync, TimeSyncError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+const NTP_UNIX_EPOCH_DELTA_SECS: u64 = 2_208_988_800;
+
+fn ntp_timestamp(unix_millis: u64) -> [u8; 8] {
+    let secs = unix_millis / 1000 + NTP_UNIX_EPOCH_DELTA_SECS;
+    let frac = ((unix_millis % 1000) << 32) / 1000;
+    let mut out = [0u8; 8];
+    out[..4].copy_from_slice(&(secs as u32).to_be_bytes());
+    out[4..].copy_from_slice(&(frac as u32).to_be_bytes());
+    out
+}
+
+// Spawn a minimal SNTP server whose clock runs `skew_ms` ahead of the local one.
+async fn spawn_sntp_stand_in(skew_ms: i64) -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 48];
+        loop {
+            let Ok((_, peer)) = socket.recv_from(&mut buf).await else { break };
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64 + skew_ms;
+            let mut resp = [0u8; 48];
+            resp[0] = 0x1C; // LI = 0, VN = 3, Mode = 4 (server)
+            resp[1] = 1; // stratum
+            resp[24..32].copy_from_slice(&buf[40..48]);
+            resp[32..40].copy_from_slice(&ntp_timestamp(now as u64));
+            resp[40..48].copy_from_slice(&ntp_timestamp(now as u64));
+            let _ = socket.send_to(&resp, peer).await;
+        }
+    });
+    addr
+}
+
+fn time_settings(server: String, max_deviation_ms: u64) -> Settings {
+    Settings {
+        ntp_server: server,
+        timestamp_sync_interval_ms: 1000,
+        max_timestamp_deviation_ms: max_deviation_ms,
+        timestamp_validation_enabled: true,
+        clock_drift_correction: false,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_sntp_offset_against_stand_in() {
+    let server = spawn_sntp_stand_in(5_000).await;
+    let offset = query_sntp_offset(&server, Duration::from_secs(1)).await.unwrap();
+    assert!((offset - 5_000).abs() < 100, "offset was {}", offset);
+}
+
+#[tokio::test]
+async fn test_validation_passes_within_deviation() {
+    let server = spawn_sntp_stand_in(0).await;
+    let sync = TimeSync::new(&time_settings(server, 50));
+    sync.sync_once().await.unwrap();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    assert!(sync.validate_timestamp(now).is_ok());
+    // Far-future timestamps are rejected even with a healthy clock
+    assert!(matches!(
+        sync.validate_timestamp(now + 60_000),
+        Err(TimeSyncError::TimestampInFuture { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_validation_fails_when_drift_exceeds_max() {
+    let server = spawn_sntp_stand_in(2_000).await;
+    let sync = TimeSync::new(&time_settings(server, 50));
+    assert!(matches!(sync.sync_once().await, Err(TimeSyncError::DriftExceeded { .. })));
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    assert!(matches!(
+        sync.validate_timestamp(now),
+        Err(TimeSyncError::DriftExceeded { .. })
+    ));
+}